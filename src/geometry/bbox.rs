@@ -97,6 +97,12 @@ impl BBox {
     }
     
     pub fn intersects(&self, ray: &Ray) -> bool {
+        self.intersect_range(ray).is_some()
+    }
+
+    /// Returns the distances along the ray at which it enters and leaves the box.
+    /// The entry distance is negative if the ray starts inside the box.
+    pub fn intersect_range(&self, ray: &Ray) -> Option<(f64, f64)> {
         // Using ray.inverse_dir is an optimisation. Normally, for simplicity we would do
         //
         //     let d = -ray.direction;
@@ -133,7 +139,7 @@ impl BBox {
         let ty_max = (max_y_bound.y - o.y) * ray.inverse_dir.y;
 
         if t_min > ty_max || ty_min > t_max {
-            return false
+            return None
         }
         if ty_min > t_min {
             t_min = ty_min;
@@ -151,7 +157,7 @@ impl BBox {
         let tz_max = (max_z_bound.z - o.z) * ray.inverse_dir.z;
 
         if t_min > tz_max || tz_min > t_max {
-            return false
+            return None
         }
         if tz_min > t_min {
             t_min = tz_min;
@@ -161,7 +167,11 @@ impl BBox {
         }
 
         // tmin < t1 && tmax > t0
-        t_min < ::std::f64::INFINITY && t_max > 0.0
+        if t_min < ::std::f64::INFINITY && t_max > 0.0 {
+            Some((t_min, t_max))
+        } else {
+            None
+        }
     }
 
     pub fn overlaps(&self, other: &BBox) -> bool {
@@ -244,6 +254,15 @@ impl BBox {
     pub fn len(&self) -> Vec3 {
         self.max - self.min
     }
+
+    pub fn centroid(&self) -> Vec3 {
+        self.lerp(0.5, 0.5, 0.5)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.len();
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
 }
 
 #[test]
@@ -270,6 +289,23 @@ fn it_intersects_with_a_ray() {
     assert_eq!(false, bbox.intersects(&non_intersecting_ray));
 }
 
+#[test]
+fn it_returns_entry_and_exit_distances() {
+    let bbox = BBox {
+        min: Vec3::zero(),
+        max: Vec3::one()
+    };
+
+    let outside_ray = Ray::new(Vec3 { x: 0.5, y: 3.0, z: 0.5 }, Vec3 { x: 0.0, y: -1.0, z: 0.0 });
+    assert_eq!(Some((2.0, 3.0)), bbox.intersect_range(&outside_ray));
+
+    let inside_ray = Ray::new(Vec3 { x: 0.5, y: 0.5, z: 0.5 }, Vec3 { x: 0.0, y: -1.0, z: 0.0 });
+    assert_eq!(Some((-0.5, 0.5)), bbox.intersect_range(&inside_ray));
+
+    let away_ray = Ray::new(Vec3 { x: 0.5, y: 3.0, z: 0.5 }, Vec3 { x: 0.0, y: 1.0, z: 0.0 });
+    assert_eq!(None, bbox.intersect_range(&away_ray));
+}
+
 #[test]
 fn it_unions_a_bbox_with_a_point() {
     let original_bbox = BBox {
@@ -445,3 +481,13 @@ fn it_returns_side_lengths() {
     assert_eq!(2.0, bbox.y_len());
     assert_eq!(3.0, bbox.z_len());
 }
+
+#[test]
+fn it_returns_surface_area() {
+    let bbox = BBox {
+        min: Vec3::zero(),
+        max: Vec3 { x: 1.0, y: 2.0, z: 3.0 }
+    };
+
+    assert_eq!(22.0, bbox.surface_area());
+}
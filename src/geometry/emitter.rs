@@ -0,0 +1,9 @@
+use vec3::Vec3;
+
+/// A prim that can also give its shape to an area light, see `ShapeLight`
+pub trait Emitter {
+    /// A random point on the surface, roughly uniformly distributed by area
+    fn sample_surface(&self) -> Vec3;
+
+    fn center(&self) -> Vec3;
+}
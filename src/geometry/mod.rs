@@ -1,17 +1,46 @@
 pub use self::prim::Prim;
 pub use self::mesh::Mesh;
 pub use self::bbox::{BBox, PartialBoundingBox};
+pub use self::emitter::Emitter;
 
 pub mod bbox;
+pub mod emitter;
 pub mod prim;
 pub mod mesh;
+pub mod roots;
+pub mod sdf;
+pub mod subdivision;
 
 pub mod prims {
+    pub use self::cone::Cone;
+    pub use self::csg::{Csg, CsgOp};
+    pub use self::cuboid::Cuboid;
+    pub use self::curve::Curve;
+    pub use self::cylinder::Cylinder;
+    pub use self::disk::Disk;
+    pub use self::heightfield::Heightfield;
+    pub use self::implicit::Implicit;
+    pub use self::indexedmesh::{IndexedMesh, IndexedMeshBuilder};
+    pub use self::instance::Instance;
     pub use self::plane::Plane;
+    pub use self::pointcloud::{ParticleSet, ParticleShape, PointCloud};
     pub use self::sphere::Sphere;
+    pub use self::torus::Torus;
     pub use self::triangle::{Triangle, TriangleOptions};
 
+    mod cone;
+    mod csg;
+    mod cuboid;
+    mod curve;
+    mod cylinder;
+    mod disk;
+    mod heightfield;
+    mod implicit;
+    mod indexedmesh;
+    mod instance;
     mod plane;
+    mod pointcloud;
     mod sphere;
+    mod torus;
     mod triangle;
 }
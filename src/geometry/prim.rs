@@ -1,10 +1,31 @@
+use std::f64::consts::PI;
 use geometry::{BBox, PartialBoundingBox};
 use raytracer::{Ray, Intersection};
-use mat4::Transform;
+use mat4::{Mat4, Transform};
+use vec3::Vec3;
+
+/// Relative distance skipped past each hit when stepping through all hits
+const NEXT_HIT_EPSILON: f64 = 1e-9;
 
 pub trait Prim: PartialBoundingBox {
     fn intersects<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>>;
-    
+
+    /// Every crossing of the surface in [t_min, t_max], nearest first, with
+    /// normals facing out of the prim. Used by CSG, where the crossings
+    /// bound the intervals of the ray inside each prim. By default this
+    /// steps from one nearest hit to the next.
+    fn all_hits<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<Intersection<'a>> {
+        let mut hits = Vec::new();
+        let mut t_from = t_min;
+
+        while let Some(hit) = self.intersects(ray, t_from, t_max) {
+            t_from = hit.t + NEXT_HIT_EPSILON * hit.t.abs().max(1.0);
+            hits.push(hit);
+        }
+
+        hits
+    }
+
     // fn transform(&self, transform: &Transform) -> Box<Prim+Send+Sync>;
     fn mut_transform(&mut self, transform: &Transform);
 }
@@ -13,4 +34,80 @@ impl<'a> PartialBoundingBox for Box<Prim+Send+Sync> {
     fn partial_bounding_box(&self) -> Option<BBox> {
         (**self).partial_bounding_box()
     }
-}
\ No newline at end of file
+}
+/// Any two unit directions across the surface with normal `n`
+pub fn frame_around(n: &Vec3) -> (Vec3, Vec3) {
+    let n = n.unit();
+    let axis = if n.x.abs() > 0.9 { Vec3 { x: 0.0, y: 1.0, z: 0.0 } } else { Vec3 { x: 1.0, y: 0.0, z: 0.0 } };
+    let tangent = n.cross(&axis).unit();
+    (tangent, n.cross(&tangent))
+}
+
+/// dpdu where u is the angle around the y axis from +x towards +z, over 2π
+pub fn dpdu_around_y(p: &Vec3) -> Vec3 {
+    Vec3 { x: -p.z, y: 0.0, z: p.x }.scale(2.0 * PI)
+}
+
+/// `length` straight out from the y axis through `p`, or nothing on the axis
+pub fn away_from_y(p: &Vec3, length: f64) -> Vec3 {
+    let distance = (p.x * p.x + p.z * p.z).sqrt();
+    if distance > 0.0 {
+        Vec3 { x: p.x, y: 0.0, z: p.z }.scale(length / distance)
+    } else {
+        Vec3::zero()
+    }
+}
+
+/// dpdu and dpdv of the mapping spheres use, with u around the y axis and v
+/// from the top to the bottom, at `q` from the centre
+pub fn spherical_derivatives(q: &Vec3) -> (Vec3, Vec3) {
+    let ring = (q.x * q.x + q.z * q.z).sqrt();
+    if ring < ::std::f64::EPSILON * q.len() {
+        // At the poles u has no direction
+        let (dpdu, dpdv) = frame_around(q);
+        return (dpdu.scale(2.0 * PI * q.len()), dpdv.scale(PI * q.len()));
+    }
+
+    (dpdu_around_y(q),
+     Vec3 { x: q.y * q.x / ring, y: -ring, z: q.y * q.z / ring }.scale(PI))
+}
+
+/// Intersects `ray` with geometry defined in the object space of `transform`,
+/// for prims that cannot bake a transform into themselves. `intersects` is
+/// given a unit-length object space ray; the hit is moved back to world space.
+pub fn intersects_in_object_space<'a, F>(transform: &Transform, ray: &Ray, t_min: f64, t_max: f64, intersects: F) -> Option<Intersection<'a>>
+        where F: FnOnce(&Ray, f64, f64) -> Option<Intersection<'a>> {
+    let (object_ray, scale) = object_space_ray(transform, ray);
+
+    intersects(&object_ray, t_min * scale, t_max * scale).map(|hit| world_space_hit(transform, scale, hit))
+}
+
+/// Like `intersects_in_object_space`, for `Prim::all_hits`
+pub fn all_hits_in_object_space<'a, F>(transform: &Transform, ray: &Ray, t_min: f64, t_max: f64, all_hits: F) -> Vec<Intersection<'a>>
+        where F: FnOnce(&Ray, f64, f64) -> Vec<Intersection<'a>> {
+    let (object_ray, scale) = object_space_ray(transform, ray);
+
+    all_hits(&object_ray, t_min * scale, t_max * scale).into_iter().map(|hit| world_space_hit(transform, scale, hit)).collect()
+}
+
+fn object_space_ray(transform: &Transform, ray: &Ray) -> (Ray, f64) {
+    let object_ray = Mat4::transform_ray(ray, &transform.inv);
+
+    // Distances in object space are scaled by however much the transform
+    // stretched the direction
+    let scale = object_ray.direction.len();
+    (Ray::new_at_time(object_ray.origin, object_ray.direction.scale(1.0 / scale), ray.time), scale)
+}
+
+fn world_space_hit<'a>(transform: &Transform, scale: f64, hit: Intersection<'a>) -> Intersection<'a> {
+    Intersection {
+        n: Mat4::transform_normal_inv(&hit.n, &transform.inv),
+        ng: Mat4::transform_normal_inv(&hit.ng, &transform.inv),
+        t: hit.t / scale,
+        dpdu: Mat4::mult_v(&transform.m, &hit.dpdu),
+        dpdv: Mat4::mult_v(&transform.m, &hit.dpdv),
+        position: Mat4::mult_p(&transform.m, &hit.position),
+        shading_position: Mat4::mult_p(&transform.m, &hit.shading_position),
+        ..hit
+    }
+}
@@ -0,0 +1,173 @@
+use std::f64::consts::PI;
+use rand::{thread_rng, Rng};
+use geometry::bbox::{BBox, PartialBoundingBox};
+use geometry::emitter::Emitter;
+use geometry::prim::{self, Prim};
+use geometry::roots;
+use material::Material;
+use mat4::{Mat4, Transform};
+use raytracer::{Ray, Intersection};
+use vec3::Vec3;
+
+#[cfg(test)]
+use material::materials::FlatMaterial;
+
+/// A cone with its capped base on the origin and its apex up the +y axis in object space
+#[allow(dead_code)]
+pub struct Cone {
+    pub radius: f64,
+    pub height: f64,
+    pub material: Box<Material+Send+Sync>,
+    transform: Transform
+}
+
+impl Cone {
+    pub fn new(base: Vec3, apex: Vec3, radius: f64, material: Box<Material+Send+Sync>) -> Cone {
+        let axis = apex - base;
+
+        Cone {
+            radius: radius,
+            height: axis.len(),
+            material: material,
+            transform: Transform::new(Mat4::mult_m(&Mat4::translate_matrix(&base), &Mat4::align_y_matrix(&axis)))
+        }
+    }
+
+    fn intersects_object<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        let (o, d) = (ray.origin, ray.direction);
+        let mut nearest: Option<(f64, Vec3)> = None;
+        let mut consider = |t: f64, n: Vec3| {
+            if t >= t_min && t <= t_max && nearest.map_or(true, |(nearest_t, _)| t < nearest_t) {
+                nearest = Some((t, n));
+            }
+        };
+
+        // Side: x^2 + z^2 = (k (h - y))^2 with k the radius shrinking per unit height
+        let k = self.radius / self.height;
+        let k2 = k * k;
+        let h_o = self.height - o.y;
+        let a = d.x * d.x + d.z * d.z - k2 * d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.z * d.z + k2 * h_o * d.y);
+        let c = o.x * o.x + o.z * o.z - k2 * h_o * h_o;
+        if let Some((t0, t1)) = roots::quadratic(a, b, c) {
+            for &t in [t0, t1].iter() {
+                let p = o + d.scale(t);
+                // Excludes the mirrored cone above the apex
+                if p.y >= 0.0 && p.y <= self.height {
+                    consider(t, Vec3 { x: p.x, y: k2 * (self.height - p.y), z: p.z }.unit());
+                }
+            }
+        }
+
+        // Base
+        let t = -o.y / d.y;
+        let p = o + d.scale(t);
+        if p.x * p.x + p.z * p.z <= self.radius * self.radius {
+            consider(t, Vec3 { x: 0.0, y: -1.0, z: 0.0 });
+        }
+
+        nearest.map(|(t, n)| {
+            let position = o + d.scale(t);
+            let (v, dpdv) = if n.y == -1.0 {
+                ((position.x * position.x + position.z * position.z).sqrt() / self.radius, prim::away_from_y(&position, self.radius))
+            } else {
+                // Up the side towards the apex
+                (position.y / self.height, Vec3 { x: 0.0, y: self.height, z: 0.0 } - prim::away_from_y(&position, self.radius))
+            };
+
+            Intersection {
+                n: n,
+                ng: n,
+                t: t,
+                u: 0.5 + position.z.atan2(position.x) / (2.0 * PI),
+                v: v,
+                dpdu: prim::dpdu_around_y(&position),
+                dpdv: dpdv,
+                position: position,
+                shading_position: position,
+                material: &self.material
+            }
+        })
+    }
+}
+
+impl PartialBoundingBox for Cone {
+    fn partial_bounding_box(&self) -> Option<BBox> {
+        let bbox = BBox {
+            min: Vec3 { x: -self.radius, y: 0.0, z: -self.radius },
+            max: Vec3 { x: self.radius, y: self.height, z: self.radius }
+        };
+        Some(Mat4::transform_bbox(&bbox, &self.transform.m))
+    }
+}
+
+impl Prim for Cone {
+    fn intersects<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        prim::intersects_in_object_space(&self.transform, ray, t_min, t_max, |object_ray, t_min, t_max| {
+            self.intersects_object(object_ray, t_min, t_max)
+        })
+    }
+
+    fn mut_transform(&mut self, transform: &Transform) {
+        self.transform = Transform::compose(transform, &self.transform);
+    }
+}
+
+impl Emitter for Cone {
+    fn sample_surface(&self) -> Vec3 {
+        let mut rng = thread_rng();
+        let theta = 2.0 * PI * rng.gen::<f64>();
+        let side_area = PI * self.radius * (self.radius * self.radius + self.height * self.height).sqrt();
+        let base_area = PI * self.radius * self.radius;
+
+        // The side widens linearly away from the apex, as the base does from
+        // its centre, so both take the square root for uniform area
+        let along = rng.gen::<f64>().sqrt();
+        let point = if rng.gen::<f64>() * (side_area + base_area) < side_area {
+            Vec3 { x: self.radius * along * theta.cos(), y: self.height * (1.0 - along), z: self.radius * along * theta.sin() }
+        } else {
+            Vec3 { x: self.radius * along * theta.cos(), y: 0.0, z: self.radius * along * theta.sin() }
+        };
+
+        Mat4::mult_p(&self.transform.m, &point)
+    }
+
+    /// The centroid of the side, a third of the way up
+    fn center(&self) -> Vec3 {
+        Mat4::mult_p(&self.transform.m, &Vec3 { x: 0.0, y: self.height / 3.0, z: 0.0 })
+    }
+}
+
+#[test]
+fn it_intersects_the_side_and_base() {
+    let cone = Cone::new(Vec3::zero(), Vec3 { x: 0.0, y: 2.0, z: 0.0 }, 1.0,
+                         Box::new(FlatMaterial { color: Vec3::one() }));
+
+    // Halfway up, the cone is half as wide
+    let side = Ray::new(Vec3 { x: 10.0, y: 1.0, z: 0.0 }, Vec3 { x: -1.0, y: 0.0, z: 0.0 });
+    let hit = cone.intersects(&side, 0.0, 100.0).unwrap();
+    assert!((hit.t - 9.5).abs() < 1e-9);
+    assert!((hit.n - Vec3 { x: 2.0, y: 1.0, z: 0.0 }.unit()).len() < 1e-9);
+
+    let base = Ray::new(Vec3 { x: 0.5, y: -5.0, z: 0.0 }, Vec3 { x: 0.0, y: 1.0, z: 0.0 });
+    let hit = cone.intersects(&base, 0.0, 100.0).unwrap();
+    assert!((hit.t - 5.0).abs() < 1e-9);
+    assert!((hit.n - Vec3 { x: 0.0, y: -1.0, z: 0.0 }).len() < 1e-9);
+
+    let above_apex = Ray::new(Vec3 { x: 10.0, y: 3.0, z: 0.0 }, Vec3 { x: -1.0, y: 0.0, z: 0.0 });
+    assert!(cone.intersects(&above_apex, 0.0, 100.0).is_none());
+}
+
+#[test]
+fn it_samples_points_on_the_surface() {
+    let cone = Cone::new(Vec3 { x: 1.0, y: 0.0, z: 0.0 }, Vec3 { x: 1.0, y: 0.0, z: 2.0 }, 1.0,
+                         Box::new(FlatMaterial { color: Vec3::one() }));
+
+    for _ in 0..100 {
+        let p = cone.sample_surface() - Vec3 { x: 1.0, y: 0.0, z: 0.0 };
+        let ring = (p.x * p.x + p.y * p.y).sqrt();
+        let on_side = (ring - (1.0 - p.z / 2.0)).abs() < 1e-9;
+        let on_base = p.z.abs() < 1e-9 && ring <= 1.0 + 1e-9;
+        assert!(on_side || on_base, "{:?}", p);
+    }
+}
@@ -0,0 +1,182 @@
+use std::f64;
+use geometry::bbox::{self, BBox, PartialBoundingBox};
+use geometry::prim::Prim;
+use mat4::Transform;
+use raytracer::{Ray, Intersection};
+use vec3::Vec3;
+
+#[cfg(test)]
+use material::materials::FlatMaterial;
+#[cfg(test)]
+use geometry::prims::{Cuboid, Sphere};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    /// Left with right cut away
+    Difference
+}
+
+impl CsgOp {
+    fn contains(&self, in_left: bool, in_right: bool) -> bool {
+        match *self {
+            CsgOp::Union => in_left || in_right,
+            CsgOp::Intersection => in_left && in_right,
+            CsgOp::Difference => in_left && !in_right
+        }
+    }
+}
+
+/// Constructive solid geometry: two closed prims combined by `op`.
+/// Children may themselves be `Csg`s, building up a tree.
+pub struct Csg {
+    pub op: CsgOp,
+    pub left: Box<Prim+Send+Sync>,
+    pub right: Box<Prim+Send+Sync>
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, left: Box<Prim+Send+Sync>, right: Box<Prim+Send+Sync>) -> Csg {
+        Csg {
+            op: op,
+            left: left,
+            right: right
+        }
+    }
+}
+
+/// Whether the ray starts inside a prim, judged by its first crossing
+/// leaving rather than entering the surface
+fn starts_inside(ray: &Ray, hits: &[Intersection]) -> bool {
+    hits.first().map_or(false, |hit| hit.n.dot(&ray.direction) > 0.0)
+}
+
+impl PartialBoundingBox for Csg {
+    fn partial_bounding_box(&self) -> Option<BBox> {
+        let left = self.left.partial_bounding_box();
+        let right = self.right.partial_bounding_box();
+
+        match (self.op, left, right) {
+            (CsgOp::Union, Some(l), Some(r)) => Some(bbox::union_bbox(&l, &r)),
+            (CsgOp::Union, _, _) => None,
+            (CsgOp::Intersection, Some(l), Some(r)) => {
+                Some(BBox {
+                    min: Vec3 { x: l.min.x.max(r.min.x), y: l.min.y.max(r.min.y), z: l.min.z.max(r.min.z) },
+                    max: Vec3 { x: l.max.x.min(r.max.x), y: l.max.y.min(r.max.y), z: l.max.z.min(r.max.z) }
+                })
+            },
+            (CsgOp::Intersection, l, r) => l.or(r),
+            (CsgOp::Difference, l, _) => l
+        }
+    }
+}
+
+impl Prim for Csg {
+    fn intersects<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        self.all_hits(ray, t_min, t_max).into_iter().next()
+    }
+
+    fn all_hits<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<Intersection<'a>> {
+        // Walk both children's crossings from the start of the ray, so the
+        // inside state is known by the time t_min is reached
+        let left_hits = self.left.all_hits(ray, f64::NEG_INFINITY, t_max);
+        let right_hits = self.right.all_hits(ray, f64::NEG_INFINITY, t_max);
+
+        let mut in_left = starts_inside(ray, &left_hits);
+        let mut in_right = starts_inside(ray, &right_hits);
+        let mut inside = self.op.contains(in_left, in_right);
+
+        let mut left_hits = left_hits.into_iter().peekable();
+        let mut right_hits = right_hits.into_iter().peekable();
+        let mut hits = Vec::new();
+
+        loop {
+            let from_left = match (left_hits.peek(), right_hits.peek()) {
+                (Some(l), Some(r)) => l.t <= r.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break
+            };
+
+            let hit = if from_left {
+                in_left = !in_left;
+                left_hits.next().unwrap()
+            } else {
+                in_right = !in_right;
+                right_hits.next().unwrap()
+            };
+
+            // Only crossings that change the combined state are surfaces
+            let now_inside = self.op.contains(in_left, in_right);
+            if now_inside == inside {
+                continue;
+            }
+            inside = now_inside;
+
+            if hit.t >= t_min {
+                // The cut left by the right side faces into it
+                if !from_left && self.op == CsgOp::Difference {
+                    hits.push(Intersection { n: hit.n.scale(-1.0), ng: hit.ng.scale(-1.0), ..hit });
+                } else {
+                    hits.push(hit);
+                }
+            }
+        }
+
+        hits
+    }
+
+    fn mut_transform(&mut self, transform: &Transform) {
+        self.left.mut_transform(transform);
+        self.right.mut_transform(transform);
+    }
+}
+
+#[cfg(test)]
+fn unit_sphere(x: f64) -> Box<Prim+Send+Sync> {
+    Box::new(Sphere::new(Vec3 { x: x, y: 0.0, z: 0.0 }, 1.0, Box::new(FlatMaterial { color: Vec3::one() })))
+}
+
+#[test]
+fn it_intersects_a_lens() {
+    let lens = Csg::new(CsgOp::Intersection, unit_sphere(-0.5), unit_sphere(0.5));
+    let ray = Ray::new(Vec3 { x: -10.0, y: 0.0, z: 0.0 }, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+
+    let hits = lens.all_hits(&ray, 0.0, 100.0);
+    assert_eq!(hits.len(), 2);
+    assert!((hits[0].t - 9.5).abs() < 1e-9);
+    assert!((hits[1].t - 10.5).abs() < 1e-9);
+    assert!((hits[0].n - Vec3 { x: -1.0, y: 0.0, z: 0.0 }).len() < 1e-9);
+
+    let bbox = lens.partial_bounding_box().unwrap();
+    assert!((bbox.min.x + 0.5).abs() < 1e-9 && (bbox.max.x - 0.5).abs() < 1e-9);
+
+    // Starting inside, only the way out is hit
+    let inside = Ray::new(Vec3::zero(), Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+    let hit = lens.intersects(&inside, 0.0, 100.0).unwrap();
+    assert!((hit.t - 0.5).abs() < 1e-9);
+}
+
+#[test]
+fn it_cuts_away_with_difference() {
+    let cutter = Box::new(Cuboid::new(Vec3 { x: 0.0, y: -2.0, z: -2.0 }, Vec3 { x: 2.0, y: 2.0, z: 2.0 },
+                                      Box::new(FlatMaterial { color: Vec3::one() })));
+    let half = Csg::new(CsgOp::Difference, unit_sphere(0.0), cutter);
+
+    // Through the sphere and out of the cut face
+    let ray = Ray::new(Vec3 { x: -10.0, y: 0.0, z: 0.0 }, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+    let hits = half.all_hits(&ray, 0.0, 100.0);
+    assert_eq!(hits.len(), 2);
+    assert!((hits[1].t - 10.0).abs() < 1e-9);
+    assert!((hits[1].n - Vec3 { x: 1.0, y: 0.0, z: 0.0 }).len() < 1e-9);
+
+    // Hitting the cut face from the other side
+    let back = Ray::new(Vec3 { x: 10.0, y: 0.5, z: 0.0 }, Vec3 { x: -1.0, y: 0.0, z: 0.0 });
+    let hit = half.intersects(&back, 0.0, 100.0).unwrap();
+    assert!((hit.t - 10.0).abs() < 1e-9);
+    assert!((hit.n - Vec3 { x: 1.0, y: 0.0, z: 0.0 }).len() < 1e-9);
+
+    let union = Csg::new(CsgOp::Union, unit_sphere(-0.5), unit_sphere(0.5));
+    assert_eq!(union.all_hits(&ray, 0.0, 100.0).len(), 2);
+}
@@ -0,0 +1,204 @@
+use rand::{thread_rng, Rng};
+use geometry::bbox::{BBox, PartialBoundingBox};
+use geometry::emitter::Emitter;
+use geometry::prim::{self, Prim};
+use material::Material;
+use mat4::{Mat4, Transform};
+use raytracer::{Ray, Intersection};
+use vec3::Vec3;
+
+#[cfg(test)]
+use material::materials::FlatMaterial;
+
+/// A box, axis-aligned in its own object space. Oriented boxes are ones with
+/// a rotation in their transform.
+#[allow(dead_code)]
+pub struct Cuboid {
+    pub min: Vec3,
+    pub max: Vec3,
+    pub material: Box<Material+Send+Sync>,
+    transform: Transform
+}
+
+impl Cuboid {
+    /// An axis-aligned box
+    pub fn new(min: Vec3, max: Vec3, material: Box<Material+Send+Sync>) -> Cuboid {
+        Cuboid {
+            min: min,
+            max: max,
+            material: material,
+            transform: Transform::identity()
+        }
+    }
+
+    /// A box with `half_extents` along the axes `rotation` turns x, y and z onto
+    pub fn oriented(center: Vec3, half_extents: Vec3, rotation: &Mat4, material: Box<Material+Send+Sync>) -> Cuboid {
+        Cuboid {
+            min: half_extents.scale(-1.0),
+            max: half_extents,
+            material: material,
+            transform: Transform::new(Mat4::mult_m(&Mat4::translate_matrix(&center), rotation))
+        }
+    }
+
+    fn intersects_object<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        let (t_enter, t_exit) = match (BBox { min: self.min, max: self.max }).intersect_range(ray) {
+            Some(range) => range,
+            None => return None
+        };
+
+        if t_enter >= t_min && t_enter <= t_max {
+            Some(self.hit_at(ray, t_enter))
+        } else if t_exit >= t_min && t_exit <= t_max {
+            Some(self.hit_at(ray, t_exit))
+        } else {
+            None
+        }
+    }
+
+    fn all_hits_object<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<Intersection<'a>> {
+        match (BBox { min: self.min, max: self.max }).intersect_range(ray) {
+            Some((t_enter, t_exit)) => {
+                [t_enter, t_exit].iter()
+                    .filter(|&&t| t >= t_min && t <= t_max)
+                    .map(|&t| self.hit_at(ray, t))
+                    .collect()
+            },
+            None => Vec::new()
+        }
+    }
+
+    fn hit_at<'a>(&'a self, ray: &Ray, t: f64) -> Intersection<'a> {
+        let position = ray.origin + ray.direction.scale(t);
+        let size = self.max - self.min;
+        // A flat box has no extent to spread the texture over on that axis
+        let fraction = |p: f64, min: f64, size: f64| if size > 0.0 { (p - min) / size } else { 0.0 };
+        let local = Vec3 {
+            x: fraction(position.x, self.min.x, size.x),
+            y: fraction(position.y, self.min.y, size.y),
+            z: fraction(position.z, self.min.z, size.z)
+        };
+
+        // The face hit is the one the point is nearest to. Distances rather
+        // than fractions of the size, which flat boxes have none of.
+        let offset = position - (self.min + self.max).scale(0.5);
+        let offsets = [offset.x, offset.y, offset.z];
+        let half_sizes = [size.x * 0.5, size.y * 0.5, size.z * 0.5];
+        let gap = |i: usize| (offsets[i].abs() - half_sizes[i]).abs();
+        let axis = (0..3).fold(0, |best, i| if gap(i) < gap(best) { i } else { best });
+
+        // Both faces of a flat box are in the middle: take the one facing the ray
+        let direction = [ray.direction.x, ray.direction.y, ray.direction.z];
+        let sign = if offsets[axis] < 0.0 || (offsets[axis] == 0.0 && direction[axis] > 0.0) { -1.0 } else { 1.0 };
+
+        let (n, u, v) = match axis {
+            0 => (Vec3 { x: sign, y: 0.0, z: 0.0 }, local.z, local.y),
+            1 => (Vec3 { x: 0.0, y: sign, z: 0.0 }, local.x, local.z),
+            _ => (Vec3 { x: 0.0, y: 0.0, z: sign }, local.x, local.y)
+        };
+        let (along_x, along_y, along_z) = (Vec3 { x: size.x, y: 0.0, z: 0.0 }, Vec3 { x: 0.0, y: size.y, z: 0.0 }, Vec3 { x: 0.0, y: 0.0, z: size.z });
+        let (dpdu, dpdv) = match axis {
+            0 => (along_z, along_y),
+            1 => (along_x, along_z),
+            _ => (along_x, along_y)
+        };
+
+        Intersection {
+            n: n,
+            ng: n,
+            t: t,
+            u: u,
+            v: v,
+            dpdu: dpdu,
+            dpdv: dpdv,
+            position: position,
+            shading_position: position,
+            material: &self.material
+        }
+    }
+}
+
+impl PartialBoundingBox for Cuboid {
+    fn partial_bounding_box(&self) -> Option<BBox> {
+        Some(Mat4::transform_bbox(&BBox { min: self.min, max: self.max }, &self.transform.m))
+    }
+}
+
+impl Prim for Cuboid {
+    fn intersects<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        prim::intersects_in_object_space(&self.transform, ray, t_min, t_max, |object_ray, t_min, t_max| {
+            self.intersects_object(object_ray, t_min, t_max)
+        })
+    }
+
+    fn all_hits<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<Intersection<'a>> {
+        prim::all_hits_in_object_space(&self.transform, ray, t_min, t_max, |object_ray, t_min, t_max| {
+            self.all_hits_object(object_ray, t_min, t_max)
+        })
+    }
+
+    fn mut_transform(&mut self, transform: &Transform) {
+        self.transform = Transform::compose(transform, &self.transform);
+    }
+}
+
+impl Emitter for Cuboid {
+    fn sample_surface(&self) -> Vec3 {
+        let mut rng = thread_rng();
+        let size = self.max - self.min;
+        let areas = [size.y * size.z, size.x * size.z, size.x * size.y];
+
+        // Pick a face by area, then a point on it
+        let mut pick = rng.gen::<f64>() * (areas[0] + areas[1] + areas[2]);
+        let axis = if pick < areas[0] { 0 } else { pick -= areas[0]; if pick < areas[1] { 1 } else { 2 } };
+        let mut t = [rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>()];
+        t[axis] = if rng.gen::<bool>() { 1.0 } else { 0.0 };
+
+        let point = BBox { min: self.min, max: self.max }.lerp(t[0], t[1], t[2]);
+        Mat4::mult_p(&self.transform.m, &point)
+    }
+
+    fn center(&self) -> Vec3 {
+        Mat4::mult_p(&self.transform.m, &BBox { min: self.min, max: self.max }.centroid())
+    }
+}
+
+#[test]
+fn it_intersects_oriented_boxes() {
+    let cuboid = Cuboid::oriented(Vec3 { x: 0.0, y: 0.0, z: 10.0 }, Vec3 { x: 1.0, y: 2.0, z: 3.0 },
+                                  &Mat4::rotate_y_deg_matrix(90.0), Box::new(FlatMaterial { color: Vec3::one() }));
+
+    // Turned so the long side lies along x
+    let bbox = cuboid.partial_bounding_box().unwrap();
+    assert!((bbox.min - Vec3 { x: -3.0, y: -2.0, z: 9.0 }).len() < 1e-9);
+    assert!((bbox.max - Vec3 { x: 3.0, y: 2.0, z: 11.0 }).len() < 1e-9);
+
+    let ray = Ray::new(Vec3::zero(), Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    let hit = cuboid.intersects(&ray, 0.0, 100.0).unwrap();
+    assert!((hit.t - 9.0).abs() < 1e-9);
+    assert!((hit.n - Vec3 { x: 0.0, y: 0.0, z: -1.0 }).len() < 1e-9);
+
+    // From inside, the far face is hit
+    let hit = cuboid.intersects(&ray, 10.0, 100.0).unwrap();
+    assert!((hit.t - 11.0).abs() < 1e-9);
+
+    let side = Ray::new(Vec3 { x: 10.0, y: 1.0, z: 10.5 }, Vec3 { x: -1.0, y: 0.0, z: 0.0 });
+    let hit = side.origin + side.direction.scale(cuboid.intersects(&side, 0.0, 100.0).unwrap().t);
+    assert!((hit.x - 3.0).abs() < 1e-9);
+    assert!((cuboid.center() - Vec3 { x: 0.0, y: 0.0, z: 10.0 }).len() < 1e-9);
+}
+
+#[test]
+fn it_intersects_flat_boxes() {
+    let flat = Cuboid::new(Vec3 { x: -1.0, y: 0.0, z: -1.0 }, Vec3 { x: 1.0, y: 0.0, z: 1.0 },
+                           Box::new(FlatMaterial { color: Vec3::one() }));
+
+    let down = Ray::new(Vec3 { x: 0.5, y: 5.0, z: 0.0 }, Vec3 { x: 0.0, y: -1.0, z: 0.0 });
+    let hit = flat.intersects(&down, 0.0, 100.0).unwrap();
+    assert!((hit.t - 5.0).abs() < 1e-9);
+    assert_eq!(hit.n, Vec3 { x: 0.0, y: 1.0, z: 0.0 });
+    assert!((hit.u - 0.75).abs() < 1e-9 && (hit.v - 0.5).abs() < 1e-9);
+
+    let up = Ray::new(Vec3 { x: 0.5, y: -5.0, z: 0.0 }, Vec3 { x: 0.0, y: 1.0, z: 0.0 });
+    assert_eq!(flat.intersects(&up, 0.0, 100.0).unwrap().n, Vec3 { x: 0.0, y: -1.0, z: 0.0 });
+}
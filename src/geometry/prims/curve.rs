@@ -0,0 +1,258 @@
+use geometry::bbox::{self, BBox, PartialBoundingBox};
+use geometry::prim::Prim;
+use material::Material;
+use material::materials::SharedMaterial;
+use mat4::{Mat4, Transform};
+use raytracer::{Ray, Intersection};
+use vec3::Vec3;
+
+#[cfg(test)]
+use material::materials::FlatMaterial;
+
+/// Most halvings before a piece of curve is treated as straight
+const MAX_DEPTH: i32 = 10;
+
+/// A cubic Bézier strand for hair, fur and grass, tapering from `widths.0`
+/// at the root to `widths.1` at the tip. Drawn as a ribbon that always faces
+/// the ray, with normals bent around it so it shades like a thin tube.
+pub struct Curve {
+    pub points: [Vec3; 4],
+    pub widths: (f64, f64),
+    pub material: Box<Material+Send+Sync>,
+    depth: i32
+}
+
+fn bezier(p: &[Vec3; 4], u: f64) -> Vec3 {
+    let s = 1.0 - u;
+    p[0].scale(s * s * s) + p[1].scale(3.0 * s * s * u) + p[2].scale(3.0 * s * u * u) + p[3].scale(u * u * u)
+}
+
+fn bezier_derivative(p: &[Vec3; 4], u: f64) -> Vec3 {
+    let s = 1.0 - u;
+    (p[1] - p[0]).scale(3.0 * s * s) + (p[2] - p[1]).scale(6.0 * s * u) + (p[3] - p[2]).scale(3.0 * u * u)
+}
+
+/// de Casteljau: the halves before and after `u`
+fn split_bezier(p: &[Vec3; 4], u: f64) -> ([Vec3; 4], [Vec3; 4]) {
+    let p01 = Vec3::lerp(&p[0], &p[1], u);
+    let p12 = Vec3::lerp(&p[1], &p[2], u);
+    let p23 = Vec3::lerp(&p[2], &p[3], u);
+    let p012 = Vec3::lerp(&p01, &p12, u);
+    let p123 = Vec3::lerp(&p12, &p23, u);
+    let mid = Vec3::lerp(&p012, &p123, u);
+
+    ([p[0], p01, p012, mid], [mid, p123, p23, p[3]])
+}
+
+impl Curve {
+    pub fn new(points: [Vec3; 4], widths: (f64, f64), material: Box<Material+Send+Sync>) -> Curve {
+        let mut curve = Curve {
+            points: points,
+            widths: widths,
+            material: material,
+            depth: 0
+        };
+        curve.depth = curve.refinement_depth();
+        curve
+    }
+
+    /// The curve cut into `count` consecutive curves, each with its own tight
+    /// bounds in the acceleration structure. Long or wavy strands render
+    /// faster this way.
+    pub fn pieces(points: [Vec3; 4], widths: (f64, f64), count: usize, material: &SharedMaterial) -> Vec<Curve> {
+        let mut rest = points;
+        (0..count).map(|i| {
+            let u0 = i as f64 / count as f64;
+            let u1 = (i + 1) as f64 / count as f64;
+            let piece = if i + 1 == count {
+                rest
+            } else {
+                // Split what is left so the piece ends at u1
+                let (piece, remainder) = split_bezier(&rest, (u1 - u0) / (1.0 - u0));
+                rest = remainder;
+                piece
+            };
+
+            let width = |u: f64| widths.0 + (widths.1 - widths.0) * u;
+            Curve::new(piece, (width(u0), width(u1)), Box::new(material.clone()))
+        }).collect()
+    }
+
+    fn width(&self, u: f64) -> f64 {
+        self.widths.0 + (self.widths.1 - self.widths.0) * u
+    }
+
+    /// How many halvings make each piece flat to within a fraction of the
+    /// width, from how far the control polygon bends
+    fn refinement_depth(&self) -> i32 {
+        let p = &self.points;
+        let bend = (0..2).map(|i| (p[i] - p[i + 1].scale(2.0) + p[i + 2]).len()).fold(0.0, f64::max);
+        let tolerance = self.widths.0.min(self.widths.1).max(1e-9) / 20.0;
+
+        if bend <= 0.0 {
+            return 0;
+        }
+        let depth = ((2.0f64.sqrt() * 6.0 * bend / (8.0 * tolerance)).log2() / 2.0).ceil();
+        (depth.max(0.0) as i32).min(MAX_DEPTH)
+    }
+
+    /// Searches `p`, the curve between `u0` and `u1` in ray space, where the
+    /// ray runs up the z axis from the origin. Finds the nearest (t, u, and
+    /// offset from the curve to the ray in x and y).
+    fn intersects_ray_space(&self, p: &[Vec3; 4], u0: f64, u1: f64, depth: i32, t_min: f64, t_max: f64) -> Option<(f64, f64, f64, f64)> {
+        let half_width = self.width(u0).max(self.width(u1)) * 0.5;
+        let bounds = p.iter().skip(1).fold(bbox::union_points(&p[0], &p[0]), |b, q| bbox::union_point(&b, q)).expand(half_width);
+        if bounds.min.x > 0.0 || bounds.max.x < 0.0 || bounds.min.y > 0.0 || bounds.max.y < 0.0 ||
+           bounds.max.z < t_min || bounds.min.z > t_max {
+            return None;
+        }
+
+        if depth > 0 {
+            let (near, far) = split_bezier(p, 0.5);
+            let u_mid = (u0 + u1) * 0.5;
+            return match self.intersects_ray_space(&near, u0, u_mid, depth - 1, t_min, t_max) {
+                Some(hit) => self.intersects_ray_space(&far, u_mid, u1, depth - 1, t_min, hit.0).or(Some(hit)),
+                None => self.intersects_ray_space(&far, u_mid, u1, depth - 1, t_min, t_max)
+            };
+        }
+
+        // Flat enough to find the closest point to the ray on the chord
+        let (cx, cy) = (p[3].x - p[0].x, p[3].y - p[0].y);
+        let chord = cx * cx + cy * cy;
+        let w = if chord > 0.0 { -(p[0].x * cx + p[0].y * cy) / chord } else { 0.0 };
+        if w < 0.0 || w > 1.0 {
+            return None;
+        }
+
+        let u = u0 + (u1 - u0) * w;
+        let on_curve = bezier(p, w);
+        let half_width = self.width(u) * 0.5;
+        if on_curve.x * on_curve.x + on_curve.y * on_curve.y > half_width * half_width ||
+           on_curve.z < t_min || on_curve.z > t_max {
+            return None;
+        }
+
+        Some((on_curve.z, u, -on_curve.x, -on_curve.y))
+    }
+}
+
+impl PartialBoundingBox for Curve {
+    fn partial_bounding_box(&self) -> Option<BBox> {
+        // The curve stays inside its control points
+        let p = &self.points;
+        let hull = p.iter().skip(1).fold(bbox::union_points(&p[0], &p[0]), |b, q| bbox::union_point(&b, q));
+        Some(hull.expand(self.widths.0.max(self.widths.1) * 0.5))
+    }
+}
+
+impl Prim for Curve {
+    fn intersects<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        let d = ray.direction;
+        let x = if d.x.abs() > 0.9 { Vec3 { x: 0.0, y: 1.0, z: 0.0 } } else { Vec3 { x: 1.0, y: 0.0, z: 0.0 } };
+        let x = d.cross(&x).unit();
+        let y = d.cross(&x);
+
+        let to_ray_space = |p: &Vec3| {
+            let local = *p - ray.origin;
+            Vec3 { x: local.dot(&x), y: local.dot(&y), z: local.dot(&d) }
+        };
+        let p = &self.points;
+        let ray_space = [to_ray_space(&p[0]), to_ray_space(&p[1]), to_ray_space(&p[2]), to_ray_space(&p[3])];
+
+        self.intersects_ray_space(&ray_space, 0.0, 1.0, self.depth, t_min, t_max).map(|(t, u, offset_x, offset_y)| {
+            // dpdu runs along the strand, so hair materials shade along the
+            // curve rather than some direction picked around the normal
+            let along = bezier_derivative(&self.points, u);
+            let tangent = along.unit();
+            let side = d.cross(&tangent).unit();
+            let facing = side.cross(&tangent);
+
+            // Across the ribbon from -1 to 1, bending the normal as if round
+            let half_width = self.width(u) * 0.5;
+            let edge = ((x.scale(offset_x) + y.scale(offset_y)).dot(&side) / half_width).max(-1.0).min(1.0);
+            let n = facing.scale((1.0 - edge * edge).sqrt()) + side.scale(edge);
+
+            Intersection {
+                n: n,
+                ng: facing,
+                t: t,
+                u: u,
+                v: (edge + 1.0) * 0.5,
+                dpdu: along,
+                dpdv: side.scale(self.width(u)),
+                position: ray.origin + d.scale(t),
+                shading_position: ray.origin + d.scale(t),
+                material: &self.material
+            }
+        })
+    }
+
+    fn mut_transform(&mut self, transform: &Transform) {
+        for point in self.points.iter_mut() {
+            *point = Mat4::mult_p(&transform.m, point);
+        }
+
+        // Widths grow with the average stretch of the axes
+        let stretch = [Vec3 { x: 1.0, y: 0.0, z: 0.0 }, Vec3 { x: 0.0, y: 1.0, z: 0.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 }]
+            .iter().map(|axis| Mat4::mult_v(&transform.m, axis).len()).fold(0.0, |sum, len| sum + len) / 3.0;
+        self.widths = (self.widths.0 * stretch, self.widths.1 * stretch);
+        self.depth = self.refinement_depth();
+    }
+}
+
+#[test]
+fn it_intersects_facing_the_ray() {
+    let material = Box::new(FlatMaterial { color: Vec3::one() });
+    let points = [Vec3 { x: -1.0, y: 0.0, z: 0.0 }, Vec3 { x: -0.3, y: 0.0, z: 0.0 },
+                  Vec3 { x: 0.3, y: 0.0, z: 0.0 }, Vec3 { x: 1.0, y: 0.0, z: 0.0 }];
+    let curve = Curve::new(points, (0.2, 0.2), material);
+
+    let ray = Ray::new(Vec3 { x: 0.0, y: 0.0, z: -5.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    let hit = curve.intersects(&ray, 0.0, 10.0).unwrap();
+    assert!((hit.t - 5.0).abs() < 1e-9);
+    assert!((hit.u - 0.5).abs() < 1e-3);
+    assert!((hit.n - Vec3 { x: 0.0, y: 0.0, z: -1.0 }).len() < 1e-9);
+    assert!((hit.tangent() - Vec3 { x: 1.0, y: 0.0, z: 0.0 }).len() < 1e-9);
+
+    // Off centre, the normal leans out like a tube's
+    let edge = Ray::new(Vec3 { x: 0.0, y: 0.05, z: -5.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    let hit = curve.intersects(&edge, 0.0, 10.0).unwrap();
+    assert!((hit.n.y - 0.5).abs() < 1e-9);
+
+    let past = Ray::new(Vec3 { x: 0.0, y: 0.15, z: -5.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    assert!(curve.intersects(&past, 0.0, 10.0).is_none());
+}
+
+#[test]
+fn it_gives_the_curve_tangent_as_dpdu() {
+    let material = Box::new(FlatMaterial { color: Vec3::one() });
+    let points = [Vec3 { x: -1.0, y: -1.0, z: 0.0 }, Vec3 { x: -0.5, y: 1.0, z: 0.0 },
+                  Vec3 { x: 0.5, y: 1.0, z: 0.0 }, Vec3 { x: 1.0, y: -1.0, z: 0.0 }];
+    let curve = Curve::new(points, (0.1, 0.1), material);
+
+    for &u in [0.2, 0.45, 0.7].iter() {
+        let on_curve = bezier(&points, u);
+        let ray = Ray::new(on_curve - Vec3 { x: 0.0, y: 0.0, z: 5.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+        let hit = curve.intersects(&ray, 0.0, 10.0).unwrap();
+        let tangent = bezier_derivative(&points, hit.u).unit();
+        assert!((hit.u - u).abs() < 1e-2);
+
+        assert!((hit.dpdu.unit() - tangent).len() < 1e-9);
+        assert!((hit.tangent() - tangent).len() < 1e-9);
+    }
+}
+
+#[test]
+fn it_cuts_curves_into_pieces() {
+    let material = SharedMaterial::new(FlatMaterial { color: Vec3::one() });
+    let points = [Vec3::zero(), Vec3 { x: 0.0, y: 1.0, z: 0.0 }, Vec3 { x: 1.0, y: 2.0, z: 0.0 }, Vec3 { x: 2.0, y: 2.0, z: 0.0 }];
+    let pieces = Curve::pieces(points, (0.4, 0.0), 4, &material);
+
+    assert_eq!(pieces.len(), 4);
+    for (i, piece) in pieces.iter().enumerate() {
+        let u = (i as f64 + 0.5) / 4.0;
+        assert!((bezier(&piece.points, 0.5) - bezier(&points, u)).len() < 1e-9);
+    }
+    assert!((pieces[1].widths.0 - 0.3).abs() < 1e-9);
+    assert!((pieces[3].points[3] - points[3]).len() < 1e-9);
+}
@@ -0,0 +1,157 @@
+use std::f64::consts::PI;
+use rand::{thread_rng, Rng};
+use geometry::bbox::{BBox, PartialBoundingBox};
+use geometry::emitter::Emitter;
+use geometry::prim::{self, Prim};
+use geometry::roots;
+use material::Material;
+use mat4::{Mat4, Transform};
+use raytracer::{Ray, Intersection};
+use vec3::Vec3;
+
+#[cfg(test)]
+use material::materials::FlatMaterial;
+
+/// A capped cylinder standing on the origin along +y in object space
+#[allow(dead_code)]
+pub struct Cylinder {
+    pub radius: f64,
+    pub height: f64,
+    pub material: Box<Material+Send+Sync>,
+    transform: Transform
+}
+
+impl Cylinder {
+    pub fn new(base: Vec3, top: Vec3, radius: f64, material: Box<Material+Send+Sync>) -> Cylinder {
+        let axis = top - base;
+
+        Cylinder {
+            radius: radius,
+            height: axis.len(),
+            material: material,
+            transform: Transform::new(Mat4::mult_m(&Mat4::translate_matrix(&base), &Mat4::align_y_matrix(&axis)))
+        }
+    }
+
+    fn intersects_object<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        let (o, d) = (ray.origin, ray.direction);
+        let mut nearest: Option<(f64, Vec3)> = None;
+        let mut consider = |t: f64, n: Vec3| {
+            if t >= t_min && t <= t_max && nearest.map_or(true, |(nearest_t, _)| t < nearest_t) {
+                nearest = Some((t, n));
+            }
+        };
+
+        // Side
+        let a = d.x * d.x + d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.z * d.z);
+        let c = o.x * o.x + o.z * o.z - self.radius * self.radius;
+        if a > 0.0 {
+            if let Some((t0, t1)) = roots::quadratic(a, b, c) {
+                for &t in [t0, t1].iter() {
+                    let y = o.y + t * d.y;
+                    if y >= 0.0 && y <= self.height {
+                        let p = o + d.scale(t);
+                        consider(t, Vec3 { x: p.x, y: 0.0, z: p.z }.unit());
+                    }
+                }
+            }
+        }
+
+        // Caps
+        for &(y, n_y) in [(0.0, -1.0), (self.height, 1.0)].iter() {
+            let t = (y - o.y) / d.y;
+            let p = o + d.scale(t);
+            if p.x * p.x + p.z * p.z <= self.radius * self.radius {
+                consider(t, Vec3 { x: 0.0, y: n_y, z: 0.0 });
+            }
+        }
+
+        nearest.map(|(t, n)| {
+            let position = o + d.scale(t);
+            let (v, dpdv) = if n.y == 0.0 {
+                (position.y / self.height, Vec3 { x: 0.0, y: self.height, z: 0.0 })
+            } else {
+                ((position.x * position.x + position.z * position.z).sqrt() / self.radius, prim::away_from_y(&position, self.radius))
+            };
+
+            Intersection {
+                n: n,
+                ng: n,
+                t: t,
+                u: 0.5 + position.z.atan2(position.x) / (2.0 * PI),
+                v: v,
+                dpdu: prim::dpdu_around_y(&position),
+                dpdv: dpdv,
+                position: position,
+                shading_position: position,
+                material: &self.material
+            }
+        })
+    }
+}
+
+impl PartialBoundingBox for Cylinder {
+    fn partial_bounding_box(&self) -> Option<BBox> {
+        let bbox = BBox {
+            min: Vec3 { x: -self.radius, y: 0.0, z: -self.radius },
+            max: Vec3 { x: self.radius, y: self.height, z: self.radius }
+        };
+        Some(Mat4::transform_bbox(&bbox, &self.transform.m))
+    }
+}
+
+impl Prim for Cylinder {
+    fn intersects<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        prim::intersects_in_object_space(&self.transform, ray, t_min, t_max, |object_ray, t_min, t_max| {
+            self.intersects_object(object_ray, t_min, t_max)
+        })
+    }
+
+    fn mut_transform(&mut self, transform: &Transform) {
+        self.transform = Transform::compose(transform, &self.transform);
+    }
+}
+
+impl Emitter for Cylinder {
+    fn sample_surface(&self) -> Vec3 {
+        let mut rng = thread_rng();
+        let theta = 2.0 * PI * rng.gen::<f64>();
+        let side_area = 2.0 * PI * self.radius * self.height;
+        let cap_area = PI * self.radius * self.radius;
+
+        let point = if rng.gen::<f64>() * (side_area + 2.0 * cap_area) < side_area {
+            Vec3 { x: self.radius * theta.cos(), y: self.height * rng.gen::<f64>(), z: self.radius * theta.sin() }
+        } else {
+            let r = self.radius * rng.gen::<f64>().sqrt();
+            let y = if rng.gen::<bool>() { self.height } else { 0.0 };
+            Vec3 { x: r * theta.cos(), y: y, z: r * theta.sin() }
+        };
+
+        Mat4::mult_p(&self.transform.m, &point)
+    }
+
+    fn center(&self) -> Vec3 {
+        Mat4::mult_p(&self.transform.m, &Vec3 { x: 0.0, y: self.height * 0.5, z: 0.0 })
+    }
+}
+
+#[test]
+fn it_intersects_the_side_and_caps() {
+    let cylinder = Cylinder::new(Vec3::zero(), Vec3 { x: 4.0, y: 0.0, z: 0.0 }, 1.0,
+                                 Box::new(FlatMaterial { color: Vec3::one() }));
+
+    let side = Ray::new(Vec3 { x: 2.0, y: 5.0, z: 0.0 }, Vec3 { x: 0.0, y: -1.0, z: 0.0 });
+    let hit = cylinder.intersects(&side, 0.0, 100.0).unwrap();
+    assert!((hit.t - 4.0).abs() < 1e-9);
+    assert!((hit.n - Vec3 { x: 0.0, y: 1.0, z: 0.0 }).len() < 1e-9);
+    assert!((hit.v - 0.5).abs() < 1e-9);
+
+    let cap = Ray::new(Vec3 { x: 10.0, y: 0.5, z: 0.0 }, Vec3 { x: -1.0, y: 0.0, z: 0.0 });
+    let hit = cylinder.intersects(&cap, 0.0, 100.0).unwrap();
+    assert!((hit.t - 6.0).abs() < 1e-9);
+    assert!((hit.n - Vec3 { x: 1.0, y: 0.0, z: 0.0 }).len() < 1e-9);
+
+    let past = Ray::new(Vec3 { x: 5.0, y: 5.0, z: 0.0 }, Vec3 { x: 0.0, y: -1.0, z: 0.0 });
+    assert!(cylinder.intersects(&past, 0.0, 100.0).is_none());
+}
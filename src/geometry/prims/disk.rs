@@ -0,0 +1,111 @@
+use std::f64::consts::PI;
+use rand::{thread_rng, Rng};
+use geometry::bbox::{BBox, PartialBoundingBox};
+use geometry::emitter::Emitter;
+use geometry::prim::{self, Prim};
+use material::Material;
+use mat4::{Mat4, Transform};
+use raytracer::{Ray, Intersection};
+use vec3::Vec3;
+
+#[cfg(test)]
+use material::materials::FlatMaterial;
+
+/// A flat disk, centred on the origin facing +y in object space
+#[allow(dead_code)]
+pub struct Disk {
+    pub radius: f64,
+    pub material: Box<Material+Send+Sync>,
+    transform: Transform
+}
+
+impl Disk {
+    pub fn new(center: Vec3, normal: Vec3, radius: f64, material: Box<Material+Send+Sync>) -> Disk {
+        Disk {
+            radius: radius,
+            material: material,
+            transform: Transform::new(Mat4::mult_m(&Mat4::translate_matrix(&center), &Mat4::align_y_matrix(&normal)))
+        }
+    }
+
+    fn intersects_object<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        let t = -ray.origin.y / ray.direction.y;
+        if !(t >= t_min && t <= t_max) {
+            return None;
+        }
+
+        let position = ray.origin + ray.direction.scale(t);
+        let distance = (position.x * position.x + position.z * position.z).sqrt();
+        if distance > self.radius {
+            return None;
+        }
+
+        Some(Intersection {
+            n: Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+            ng: Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+            t: t,
+            u: 0.5 + position.z.atan2(position.x) / (2.0 * PI),
+            v: distance / self.radius,
+            dpdu: prim::dpdu_around_y(&position),
+            dpdv: prim::away_from_y(&position, self.radius),
+            position: position,
+            shading_position: position,
+            material: &self.material
+        })
+    }
+}
+
+impl PartialBoundingBox for Disk {
+    fn partial_bounding_box(&self) -> Option<BBox> {
+        let bbox = BBox {
+            min: Vec3 { x: -self.radius, y: 0.0, z: -self.radius },
+            max: Vec3 { x: self.radius, y: 0.0, z: self.radius }
+        };
+        Some(Mat4::transform_bbox(&bbox, &self.transform.m))
+    }
+}
+
+impl Prim for Disk {
+    fn intersects<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        prim::intersects_in_object_space(&self.transform, ray, t_min, t_max, |object_ray, t_min, t_max| {
+            self.intersects_object(object_ray, t_min, t_max)
+        })
+    }
+
+    fn mut_transform(&mut self, transform: &Transform) {
+        self.transform = Transform::compose(transform, &self.transform);
+    }
+}
+
+impl Emitter for Disk {
+    fn sample_surface(&self) -> Vec3 {
+        let mut rng = thread_rng();
+        let r = self.radius * rng.gen::<f64>().sqrt();
+        let theta = 2.0 * PI * rng.gen::<f64>();
+
+        Mat4::mult_p(&self.transform.m, &Vec3 { x: r * theta.cos(), y: 0.0, z: r * theta.sin() })
+    }
+
+    fn center(&self) -> Vec3 {
+        Mat4::mult_p(&self.transform.m, &Vec3::zero())
+    }
+}
+
+#[test]
+fn it_intersects() {
+    let disk = Disk::new(Vec3 { x: 0.0, y: 0.0, z: 5.0 }, Vec3 { x: 0.0, y: 0.0, z: -1.0 }, 2.0,
+                         Box::new(FlatMaterial { color: Vec3::one() }));
+
+    let ray = Ray::new(Vec3 { x: 1.5, y: 0.0, z: 0.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    let hit = disk.intersects(&ray, 0.0, 100.0).unwrap();
+    assert!((hit.t - 5.0).abs() < 1e-9);
+    assert!((hit.n - Vec3 { x: 0.0, y: 0.0, z: -1.0 }).len() < 1e-9);
+    assert!((hit.v - 0.75).abs() < 1e-9);
+
+    let outside = Ray::new(Vec3 { x: 2.5, y: 0.0, z: 0.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    assert!(disk.intersects(&outside, 0.0, 100.0).is_none());
+
+    let bbox = disk.partial_bounding_box().unwrap();
+    assert!((bbox.min - Vec3 { x: -2.0, y: -2.0, z: 5.0 }).len() < 1e-9);
+    assert!((disk.center() - Vec3 { x: 0.0, y: 0.0, z: 5.0 }).len() < 1e-9);
+}
@@ -0,0 +1,257 @@
+use geometry::bbox::{BBox, PartialBoundingBox};
+use geometry::prim::{self, Prim};
+use geometry::prims::triangle::intersects_watertight;
+use material::Material;
+use mat4::{Mat4, Transform};
+use raytracer::{Ray, Intersection};
+use raytracer::compositor::Surface;
+use vec3::Vec3;
+
+#[cfg(test)]
+use material::materials::FlatMaterial;
+#[cfg(test)]
+use raytracer::compositor::ColorRGBA;
+
+/// Terrain from a grid of heights, one per pixel of a grayscale image. Spans
+/// `size.x` by `size.z` from the origin, with white `size.y` high. Rays walk
+/// the grid cell by cell, so no triangles are stored.
+pub struct Heightfield {
+    pub material: Box<Material+Send+Sync>,
+    columns: usize,
+    rows: usize,
+    heights: Vec<f32>,
+    size: Vec3,
+    bounds: BBox,
+    transform: Transform
+}
+
+impl Heightfield {
+    /// Heights are the brightness of each pixel. Pixel columns run along x
+    /// and rows along z.
+    pub fn new(image: &Surface, size: Vec3, material: Box<Material+Send+Sync>) -> Heightfield {
+        let mut heights = Vec::with_capacity(image.width * image.height);
+        for row in 0..image.height {
+            for column in 0..image.width {
+                let color = image[(column, row)].channel_f64();
+                heights.push(((color.r + color.g + color.b) / 3.0) as f32);
+            }
+        }
+
+        Heightfield::from_heights(image.width, image.height, heights, size, material)
+    }
+
+    /// `heights` from 0 to 1, row by row
+    pub fn from_heights(columns: usize, rows: usize, heights: Vec<f32>, size: Vec3, material: Box<Material+Send+Sync>) -> Heightfield {
+        assert!(columns >= 2 && rows >= 2, "a heightfield needs at least 2x2 samples");
+        assert_eq!(heights.len(), columns * rows);
+
+        let lowest = heights.iter().fold(::std::f32::INFINITY, |low, &h| low.min(h)) as f64;
+        let highest = heights.iter().fold(::std::f32::NEG_INFINITY, |high, &h| high.max(h)) as f64;
+
+        Heightfield {
+            material: material,
+            columns: columns,
+            rows: rows,
+            heights: heights,
+            size: size,
+            bounds: BBox {
+                min: Vec3 { x: 0.0, y: lowest * size.y, z: 0.0 },
+                max: Vec3 { x: size.x, y: highest * size.y, z: size.z }
+            },
+            transform: Transform::identity()
+        }
+    }
+
+    fn cell_size(&self) -> (f64, f64) {
+        (self.size.x / (self.columns - 1) as f64, self.size.z / (self.rows - 1) as f64)
+    }
+
+    fn height(&self, column: usize, row: usize) -> f64 {
+        self.heights[row * self.columns + column] as f64 * self.size.y
+    }
+
+    fn vertex(&self, column: usize, row: usize) -> Vec3 {
+        let (dx, dz) = self.cell_size();
+        Vec3 { x: column as f64 * dx, y: self.height(column, row), z: row as f64 * dz }
+    }
+
+    /// Normal at a sample from the slope to its neighbours
+    fn vertex_normal(&self, column: usize, row: usize) -> Vec3 {
+        let (dx, dz) = self.cell_size();
+        let (left, right) = (column.saturating_sub(1), (column + 1).min(self.columns - 1));
+        let (back, front) = (row.saturating_sub(1), (row + 1).min(self.rows - 1));
+
+        let slope_x = (self.height(right, row) - self.height(left, row)) / ((right - left) as f64 * dx);
+        let slope_z = (self.height(column, front) - self.height(column, back)) / ((front - back) as f64 * dz);
+        Vec3 { x: -slope_x, y: 1.0, z: -slope_z }.unit()
+    }
+
+    /// The nearer of the cell's two triangles, with its upward face normal
+    fn intersects_cell(&self, ray: &Ray, column: usize, row: usize, t_min: f64, t_max: f64) -> Option<(f64, Vec3)> {
+        let p00 = self.vertex(column, row);
+        let p10 = self.vertex(column + 1, row);
+        let p01 = self.vertex(column, row + 1);
+        let p11 = self.vertex(column + 1, row + 1);
+
+        let first = [p00, p11, p10];
+        let second = [p00, p01, p11];
+        let face = |v: &[Vec3; 3]| (v[1] - v[0]).cross(&(v[2] - v[0])).unit();
+
+        let hit = intersects_watertight(ray, &first, t_min, t_max).map(|hit| (hit.0, face(&first)));
+        let t_max = hit.map_or(t_max, |hit| hit.0);
+        intersects_watertight(ray, &second, t_min, t_max).map(|hit| (hit.0, face(&second))).or(hit)
+    }
+
+    fn intersects_object<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        let (t_enter, t_exit) = match self.bounds.expand(1e-9).intersect_range(ray) {
+            Some(range) => range,
+            None => return None
+        };
+        let t_start = t_enter.max(t_min);
+        let t_end = t_exit.min(t_max);
+        if t_start > t_end {
+            return None;
+        }
+
+        // Walk the cells under the ray in order, so the first hit is nearest
+        let (dx, dz) = self.cell_size();
+        let start = ray.origin + ray.direction.scale(t_start);
+        let clamp_cell = |p: f64, size: f64, count: usize| ((p / size).floor().max(0.0) as usize).min(count - 2);
+        let mut column = clamp_cell(start.x, dx, self.columns);
+        let mut row = clamp_cell(start.z, dz, self.rows);
+
+        let step = |d: f64| if d > 0.0 { 1 } else { -1 };
+        let next_crossing = |cell: usize, size: f64, o: f64, d: f64| {
+            if d > 0.0 {
+                ((cell + 1) as f64 * size - o) / d
+            } else if d < 0.0 {
+                (cell as f64 * size - o) / d
+            } else {
+                ::std::f64::INFINITY
+            }
+        };
+        let (step_x, step_z) = (step(ray.direction.x), step(ray.direction.z));
+        let delta_x = (dx / ray.direction.x).abs();
+        let delta_z = (dz / ray.direction.z).abs();
+        let mut next_x = next_crossing(column, dx, ray.origin.x, ray.direction.x);
+        let mut next_z = next_crossing(row, dz, ray.origin.z, ray.direction.z);
+
+        loop {
+            if let Some((t, ng)) = self.intersects_cell(ray, column, row, t_min, t_max) {
+                return Some(self.hit_at(ray, t, ng));
+            }
+
+            if next_x < next_z {
+                if next_x > t_end || (step_x < 0 && column == 0) || (step_x > 0 && column + 2 == self.columns) {
+                    return None;
+                }
+                column = (column as isize + step_x) as usize;
+                next_x += delta_x;
+            } else {
+                if next_z > t_end || (step_z < 0 && row == 0) || (step_z > 0 && row + 2 == self.rows) {
+                    return None;
+                }
+                row = (row as isize + step_z) as usize;
+                next_z += delta_z;
+            }
+        }
+    }
+
+    fn hit_at<'a>(&'a self, ray: &Ray, t: f64, ng: Vec3) -> Intersection<'a> {
+        let position = ray.origin + ray.direction.scale(t);
+        let (dx, dz) = self.cell_size();
+
+        // Blend the corner normals across the cell so shading is smooth
+        let x = (position.x / dx).max(0.0).min((self.columns - 1) as f64);
+        let z = (position.z / dz).max(0.0).min((self.rows - 1) as f64);
+        let column = (x.floor() as usize).min(self.columns - 2);
+        let row = (z.floor() as usize).min(self.rows - 2);
+        let (fx, fz) = (x - column as f64, z - row as f64);
+
+        let n = self.vertex_normal(column, row).scale((1.0 - fx) * (1.0 - fz)) +
+                self.vertex_normal(column + 1, row).scale(fx * (1.0 - fz)) +
+                self.vertex_normal(column, row + 1).scale((1.0 - fx) * fz) +
+                self.vertex_normal(column + 1, row + 1).scale(fx * fz);
+
+        Intersection {
+            n: n.unit(),
+            ng: ng,
+            t: t,
+            u: position.x / self.size.x,
+            v: position.z / self.size.z,
+            // Along the face of the cell, which slopes by -ng.x / ng.y in x
+            dpdu: Vec3 { x: 1.0, y: -ng.x / ng.y, z: 0.0 }.scale(self.size.x),
+            dpdv: Vec3 { x: 0.0, y: -ng.z / ng.y, z: 1.0 }.scale(self.size.z),
+            position: position,
+            shading_position: position,
+            material: &self.material
+        }
+    }
+}
+
+impl PartialBoundingBox for Heightfield {
+    fn partial_bounding_box(&self) -> Option<BBox> {
+        Some(Mat4::transform_bbox(&self.bounds, &self.transform.m))
+    }
+}
+
+impl Prim for Heightfield {
+    fn intersects<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        prim::intersects_in_object_space(&self.transform, ray, t_min, t_max, |object_ray, t_min, t_max| {
+            self.intersects_object(object_ray, t_min, t_max)
+        })
+    }
+
+    fn mut_transform(&mut self, transform: &Transform) {
+        self.transform = Transform::compose(transform, &self.transform);
+    }
+}
+
+#[test]
+fn it_walks_the_grid() {
+    // A ridge along z at the middle column
+    let heights = vec![0.0, 1.0, 0.0,
+                       0.0, 1.0, 0.0,
+                       0.0, 1.0, 0.0];
+    let ridge = Heightfield::from_heights(3, 3, heights, Vec3 { x: 2.0, y: 1.0, z: 2.0 }, Box::new(FlatMaterial { color: Vec3::one() }));
+
+    // Straight down onto the peak, where the smooth normal points up
+    let down = Ray::new(Vec3 { x: 1.0, y: 5.0, z: 0.5 }, Vec3 { x: 0.0, y: -1.0, z: 0.0 });
+    let hit = ridge.intersects(&down, 0.0, 100.0).unwrap();
+    assert!((hit.t - 4.0).abs() < 1e-9);
+    assert!((hit.n - Vec3 { x: 0.0, y: 1.0, z: 0.0 }).len() < 1e-9);
+    assert!((hit.u - 0.5).abs() < 1e-9 && (hit.v - 0.25).abs() < 1e-9);
+
+    // Low across the grid, meeting the slope that faces the ray
+    let across = Ray::new(Vec3 { x: -1.0, y: 0.5, z: 1.5 }, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+    let hit = ridge.intersects(&across, 0.0, 100.0).unwrap();
+    assert!((hit.t - 1.5).abs() < 1e-9);
+    assert!(hit.n.x < 0.0 && hit.n.y > 0.0);
+
+    let back = Ray::new(Vec3 { x: 3.0, y: 0.5, z: 1.5 }, Vec3 { x: -1.0, y: 0.0, z: 0.0 });
+    let hit = ridge.intersects(&back, 0.0, 100.0).unwrap();
+    assert!((hit.t - 1.5).abs() < 1e-9);
+    assert!(hit.n.x > 0.0 && hit.n.y > 0.0);
+
+    // Past the ridge, from a hit on its near slope
+    let beyond = Ray::new(Vec3 { x: -1.0, y: 0.5, z: 0.5 }, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+    let hit = ridge.intersects(&beyond, 2.0, 100.0).unwrap();
+    assert!((hit.t - 2.5).abs() < 1e-9);
+
+    let over = Ray::new(Vec3 { x: -1.0, y: 1.5, z: 1.5 }, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+    assert!(ridge.intersects(&over, 0.0, 100.0).is_none());
+}
+
+#[test]
+fn it_reads_heights_from_images() {
+    let mut image = Surface::new(2, 2, ColorRGBA::black());
+    image[(1, 1)] = ColorRGBA::white();
+    let field = Heightfield::new(&image, Vec3 { x: 1.0, y: 3.0, z: 1.0 }, Box::new(FlatMaterial { color: Vec3::one() }));
+
+    let bounds = field.partial_bounding_box().unwrap();
+    assert!((bounds.max.y - 3.0).abs() < 1e-9);
+
+    let down = Ray::new(Vec3 { x: 0.9, y: 5.0, z: 0.5 }, Vec3 { x: 0.0, y: -1.0, z: 0.0 });
+    let hit = field.intersects(&down, 0.0, 100.0).unwrap();
+    assert!((hit.position.y - 1.5).abs() < 1e-9);
+}
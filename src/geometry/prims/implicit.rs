@@ -0,0 +1,159 @@
+use std::f64::consts::PI;
+use geometry::bbox::{BBox, PartialBoundingBox};
+use geometry::prim::{self, Prim};
+use geometry::sdf::Sdf;
+use material::Material;
+use mat4::{Mat4, Transform};
+use raytracer::{Ray, Intersection};
+use vec3::Vec3;
+
+#[cfg(test)]
+use geometry::sdf::{SdfBox, SdfSphere};
+#[cfg(test)]
+use material::materials::FlatMaterial;
+
+/// Sphere tracing gives up after this many steps, which only grazing rays reach
+const MAX_STEPS: u32 = 512;
+
+/// Hit tolerance relative to the size of the bounds
+const RELATIVE_EPSILON: f64 = 1e-6;
+
+/// A surface where a signed distance function is zero, found by sphere
+/// tracing through its bounds
+pub struct Implicit {
+    pub sdf: Box<Sdf+Send+Sync>,
+    pub material: Box<Material+Send+Sync>,
+    bounds: BBox,
+    epsilon: f64,
+    transform: Transform
+}
+
+impl Implicit {
+    pub fn new(sdf: Box<Sdf+Send+Sync>, material: Box<Material+Send+Sync>) -> Implicit {
+        let bounds = sdf.bounds();
+
+        Implicit {
+            sdf: sdf,
+            material: material,
+            bounds: bounds,
+            epsilon: bounds.len().len() * RELATIVE_EPSILON,
+            transform: Transform::identity()
+        }
+    }
+
+    /// Central differences of the distance, which point out of the surface
+    fn normal(&self, p: &Vec3) -> Vec3 {
+        let h = self.epsilon;
+        let dx = Vec3 { x: h, y: 0.0, z: 0.0 };
+        let dy = Vec3 { x: 0.0, y: h, z: 0.0 };
+        let dz = Vec3 { x: 0.0, y: 0.0, z: h };
+
+        Vec3 {
+            x: self.sdf.distance(&(*p + dx)) - self.sdf.distance(&(*p - dx)),
+            y: self.sdf.distance(&(*p + dy)) - self.sdf.distance(&(*p - dy)),
+            z: self.sdf.distance(&(*p + dz)) - self.sdf.distance(&(*p - dz))
+        }.unit()
+    }
+
+    fn intersects_object<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        let (t_enter, t_exit) = match self.bounds.expand(2.0 * self.epsilon).intersect_range(ray) {
+            Some(range) => range,
+            None => return None
+        };
+        let mut t = t_enter.max(t_min);
+        let t_end = t_exit.min(t_max);
+
+        // Rays leaving the surface start within the tolerance of it, so move
+        // off before looking for the next crossing. Rays from outside the
+        // bounds start at their padded edge, which is clear of the surface.
+        let mut leaving = t_min > t_enter;
+
+        for _ in 0..MAX_STEPS {
+            if t > t_end {
+                return None;
+            }
+
+            // Inside, the distance is negative but still safe to step by
+            let distance = self.sdf.distance(&(ray.origin + ray.direction.scale(t))).abs();
+            if distance < self.epsilon {
+                if !leaving {
+                    break;
+                }
+                t += self.epsilon;
+            } else {
+                leaving = false;
+                t += distance;
+            }
+        }
+
+        if leaving || t > t_end {
+            return None;
+        }
+
+        let position = ray.origin + ray.direction.scale(t);
+        let n = self.normal(&position);
+        let (dpdu, dpdv) = prim::spherical_derivatives(&n);
+
+        Some(Intersection {
+            n: n,
+            ng: n,
+            t: t,
+            u: 0.5 + n.z.atan2(n.x) / (2.0 * PI),
+            v: 0.5 - n.y.asin() / PI,
+            dpdu: dpdu,
+            dpdv: dpdv,
+            position: position,
+            shading_position: position,
+            material: &self.material
+        })
+    }
+}
+
+impl PartialBoundingBox for Implicit {
+    fn partial_bounding_box(&self) -> Option<BBox> {
+        Some(Mat4::transform_bbox(&self.bounds, &self.transform.m))
+    }
+}
+
+impl Prim for Implicit {
+    fn intersects<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        prim::intersects_in_object_space(&self.transform, ray, t_min, t_max, |object_ray, t_min, t_max| {
+            self.intersects_object(object_ray, t_min, t_max)
+        })
+    }
+
+    fn mut_transform(&mut self, transform: &Transform) {
+        self.transform = Transform::compose(transform, &self.transform);
+    }
+}
+
+#[test]
+fn it_sphere_traces() {
+    let sphere = Implicit::new(Box::new(SdfSphere { center: Vec3 { x: 0.0, y: 0.0, z: 5.0 }, radius: 1.0 }),
+                               Box::new(FlatMaterial { color: Vec3::one() }));
+
+    let ray = Ray::new(Vec3 { x: 0.0, y: 0.5, z: 0.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    let hit = sphere.intersects(&ray, 0.0, 100.0).unwrap();
+    let expected_z = 5.0 - 0.75f64.sqrt();
+    assert!((hit.t - expected_z).abs() < 1e-4);
+    assert!((hit.n - Vec3 { x: 0.0, y: 0.5, z: -0.75f64.sqrt() }).len() < 1e-4);
+
+    // Stepping past the first hit finds the way out
+    let hits = sphere.all_hits(&ray, 0.0, 100.0);
+    assert_eq!(hits.len(), 2);
+    assert!((hits[1].t - (5.0 + 0.75f64.sqrt())).abs() < 1e-4);
+
+    let miss = Ray::new(Vec3 { x: 0.0, y: 1.5, z: 0.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    assert!(sphere.intersects(&miss, 0.0, 100.0).is_none());
+}
+
+#[test]
+fn it_hits_surfaces_on_the_bounds() {
+    let cube = Implicit::new(Box::new(SdfBox { center: Vec3::zero(), half_extents: Vec3::one(), rounding: 0.0 }),
+                             Box::new(FlatMaterial { color: Vec3::one() }));
+
+    let ray = Ray::new(Vec3 { x: 0.2, y: 0.3, z: -5.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    let hit = cube.intersects(&ray, 0.0, 100.0).unwrap();
+    assert!((hit.t - 4.0).abs() < 1e-4);
+    assert!((hit.n - Vec3 { x: 0.0, y: 0.0, z: -1.0 }).len() < 1e-4);
+}
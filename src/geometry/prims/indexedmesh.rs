@@ -0,0 +1,313 @@
+use geometry::bbox::{BBox, PartialBoundingBox};
+use geometry::prim::{self, Prim};
+use geometry::prims::triangle::{get_shading_position, get_uv_derivatives, intersects_watertight};
+use material::Material;
+use material::bump::Bump;
+use mat4::{Mat4, Transform};
+use raytracer::{cache, Bvh, Ray, Intersection};
+use vec3::Vec3;
+
+#[cfg(test)]
+use material::materials::FlatMaterial;
+
+fn to_vec3(v: &[f32; 3]) -> Vec3 {
+    Vec3 { x: v[0] as f64, y: v[1] as f64, z: v[2] as f64 }
+}
+
+fn to_f32(v: &Vec3) -> [f32; 3] {
+    [v.x as f32, v.y as f32, v.z as f32]
+}
+
+/// One triangle in 40 bytes: indices into the mesh's vertex buffers, the
+/// index of its material and its bounds, kept so the hierarchy can be built
+/// without the buffers
+#[derive(Clone, Copy)]
+pub struct MeshFace {
+    pub vertices: [u32; 3],
+    pub material: u16,
+    bounds: [f32; 6]
+}
+
+impl PartialBoundingBox for MeshFace {
+    fn partial_bounding_box(&self) -> Option<BBox> {
+        Some(BBox {
+            min: to_vec3(&[self.bounds[0], self.bounds[1], self.bounds[2]]),
+            max: to_vec3(&[self.bounds[3], self.bounds[4], self.bounds[5]])
+        })
+    }
+}
+
+/// Collects the vertices, faces and materials of an `IndexedMesh`. Normals
+/// and UVs are optional, but each vertex given one is stored alongside all
+/// the others.
+pub struct IndexedMeshBuilder {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    faces: Vec<MeshFace>,
+    materials: Vec<Box<Material+Send+Sync>>,
+    bumps: Vec<Option<Bump>>
+}
+
+impl IndexedMeshBuilder {
+    pub fn new() -> IndexedMeshBuilder {
+        IndexedMeshBuilder {
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            faces: Vec::new(),
+            materials: Vec::new(),
+            bumps: Vec::new()
+        }
+    }
+
+    /// Returns the index faces refer to the vertex by
+    pub fn add_vertex(&mut self, position: Vec3, normal: Option<Vec3>, uv: Option<(f64, f64)>) -> u32 {
+        let index = self.positions.len();
+        self.positions.push(to_f32(&position));
+
+        // Vertices before the first with a normal or UV get zeros
+        if let Some(normal) = normal {
+            self.normals.resize(index, [0.0; 3]);
+            self.normals.push(to_f32(&normal));
+        }
+        if let Some((u, v)) = uv {
+            self.uvs.resize(index, [0.0; 2]);
+            self.uvs.push([u as f32, v as f32]);
+        }
+
+        index as u32
+    }
+
+    /// Returns the index faces refer to the material by, or an error once
+    /// there are more materials than such an index can tell apart
+    pub fn add_material(&mut self, material: Box<Material+Send+Sync>) -> Result<u16, String> {
+        if self.materials.len() >= ::std::u16::MAX as usize {
+            return Err(format!("More than {} materials in one mesh", ::std::u16::MAX));
+        }
+
+        self.materials.push(material);
+        self.bumps.push(None);
+        Ok((self.materials.len() - 1) as u16)
+    }
+
+    /// Bumps every face using `material`. Oriented by the mesh's UVs.
+    pub fn bump(&mut self, material: u16, bump: Bump) {
+        self.bumps[material as usize] = Some(bump);
+    }
+
+    /// Counter-clockwise vertices face the side the face normal points to
+    pub fn add_face(&mut self, vertices: [u32; 3], material: u16) {
+        let corners: Vec<[f32; 3]> = vertices.iter().map(|&i| self.positions[i as usize]).collect();
+        let mut bounds = [::std::f32::INFINITY, ::std::f32::INFINITY, ::std::f32::INFINITY,
+                          ::std::f32::NEG_INFINITY, ::std::f32::NEG_INFINITY, ::std::f32::NEG_INFINITY];
+        for corner in corners.iter() {
+            for axis in 0..3 {
+                bounds[axis] = bounds[axis].min(corner[axis]);
+                bounds[axis + 3] = bounds[axis + 3].max(corner[axis]);
+            }
+        }
+
+        self.faces.push(MeshFace { vertices: vertices, material: material, bounds: bounds });
+    }
+
+    #[allow(dead_code)]
+    pub fn face_count(&self) -> usize {
+        self.faces.len()
+    }
+
+    pub fn build(self) -> IndexedMesh {
+        self.build_with(|faces| faces.into_iter().collect())
+    }
+
+    /// Like `build`, but loads the hierarchy over the faces from `path`, or
+    /// builds it and saves it there
+    pub fn build_cached(self, path: &str) -> IndexedMesh {
+        self.build_with(|faces| cache::load_or_build::<_, Bvh<_>>(faces, path))
+    }
+
+    fn build_with<F>(mut self, build_faces: F) -> IndexedMesh where F: FnOnce(Vec<MeshFace>) -> Bvh<MeshFace> {
+        assert!(self.faces.iter().all(|face| (face.material as usize) < self.materials.len()),
+                "a face of the mesh has no material");
+
+        let vertex_count = self.positions.len();
+        if !self.normals.is_empty() {
+            self.normals.resize(vertex_count, [0.0; 3]);
+        }
+        if !self.uvs.is_empty() {
+            self.uvs.resize(vertex_count, [0.0; 2]);
+        }
+
+        IndexedMesh {
+            materials: self.materials,
+            bumps: self.bumps,
+            positions: self.positions,
+            normals: self.normals,
+            uvs: self.uvs,
+            faces: build_faces(self.faces),
+            transform: Transform::identity()
+        }
+    }
+}
+
+/// A triangle mesh as a single prim. Vertices are stored once in f32 and
+/// shared by the faces around them, which are found through a hierarchy of
+/// the mesh's own, so models of millions of triangles stay small.
+pub struct IndexedMesh {
+    pub materials: Vec<Box<Material+Send+Sync>>,
+    bumps: Vec<Option<Bump>>, // One for each material
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>, // Empty for flat shaded meshes
+    uvs: Vec<[f32; 2]>,     // Empty if the mesh has no texture coordinates
+    faces: Bvh<MeshFace>,
+    transform: Transform
+}
+
+impl IndexedMesh {
+    fn intersects_object<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        let mut nearest: Option<(&MeshFace, (f64, f64, f64, f64))> = None;
+        self.faces.traverse(ray, t_max, |face, t_max| {
+            let vertices = self.face_positions(face);
+            let hit = intersects_watertight(ray, &vertices, t_min, t_max);
+            if let Some(hit) = hit {
+                nearest = Some((face, hit));
+            }
+            hit.map(|hit| hit.0)
+        });
+
+        nearest.map(|(face, (t, alpha, beta, gamma))| self.hit_at(ray, face, t, [alpha, beta, gamma]))
+    }
+
+    fn face_positions(&self, face: &MeshFace) -> [Vec3; 3] {
+        [to_vec3(&self.positions[face.vertices[0] as usize]),
+         to_vec3(&self.positions[face.vertices[1] as usize]),
+         to_vec3(&self.positions[face.vertices[2] as usize])]
+    }
+
+    fn hit_at<'a>(&'a self, ray: &Ray, face: &MeshFace, t: f64, weights: [f64; 3]) -> Intersection<'a> {
+        let position = ray.origin + ray.direction.scale(t);
+        let vertices = self.face_positions(face);
+        let face_n = (vertices[1] - vertices[0]).cross(&(vertices[2] - vertices[0])).unit();
+
+        // Vertices without a normal of their own make the face flat
+        let normals = if self.normals.is_empty() {
+            [face_n, face_n, face_n]
+        } else {
+            let normal = |i: usize| to_vec3(&self.normals[face.vertices[i] as usize]);
+            let normals = [normal(0), normal(1), normal(2)];
+            if normals.iter().any(|n| n.len() == 0.0) { [face_n, face_n, face_n] } else { normals }
+        };
+        let n = normals[0].scale(weights[0]) + normals[1].scale(weights[1]) + normals[2].scale(weights[2]);
+        let ng = if face_n.dot(&n) < 0.0 { -face_n } else { face_n };
+
+        // Without UVs the face is parametrised along its edges
+        let uvs = if self.uvs.is_empty() {
+            [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]
+        } else {
+            let uv = |i: usize| {
+                let uv = self.uvs[face.vertices[i] as usize];
+                (uv[0] as f64, uv[1] as f64)
+            };
+            [uv(0), uv(1), uv(2)]
+        };
+        let u = uvs[0].0 * weights[0] + uvs[1].0 * weights[1] + uvs[2].0 * weights[2];
+        let v = uvs[0].1 * weights[0] + uvs[1].1 * weights[1] + uvs[2].1 * weights[2];
+        let (dpdu, dpdv) = get_uv_derivatives(&vertices, &uvs);
+        let shading_n = match self.bumps[face.material as usize] {
+            Some(ref bump) => bump.perturb(&n, &dpdu, &dpdv, u, v),
+            None => n
+        };
+
+        Intersection {
+            n: shading_n,
+            ng: ng,
+            t: t,
+            u: u,
+            v: v,
+            dpdu: dpdu,
+            dpdv: dpdv,
+            position: position,
+            shading_position: get_shading_position(position, &vertices, &normals, weights),
+            material: &self.materials[face.material as usize]
+        }
+    }
+}
+
+impl PartialBoundingBox for IndexedMesh {
+    fn partial_bounding_box(&self) -> Option<BBox> {
+        self.faces.bounds().map(|bounds| Mat4::transform_bbox(&bounds, &self.transform.m))
+    }
+}
+
+impl Prim for IndexedMesh {
+    fn intersects<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        prim::intersects_in_object_space(&self.transform, ray, t_min, t_max, |object_ray, t_min, t_max| {
+            self.intersects_object(object_ray, t_min, t_max)
+        })
+    }
+
+    fn mut_transform(&mut self, transform: &Transform) {
+        self.transform = Transform::compose(transform, &self.transform);
+    }
+}
+
+#[test]
+fn it_shares_vertices_between_faces() {
+    // A unit square of two faces in z = 0, red below the diagonal and blue above
+    let mut builder = IndexedMeshBuilder::new();
+    let up = Some(Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    let corners: Vec<u32> = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)].iter().map(|&(x, y)| {
+        builder.add_vertex(Vec3 { x: x, y: y, z: 0.0 }, up, Some((x, y)))
+    }).collect();
+    let red = builder.add_material(Box::new(FlatMaterial { color: Vec3 { x: 1.0, y: 0.0, z: 0.0 } })).unwrap();
+    let blue = builder.add_material(Box::new(FlatMaterial { color: Vec3 { x: 0.0, y: 0.0, z: 1.0 } })).unwrap();
+    builder.add_face([corners[0], corners[1], corners[2]], red);
+    builder.add_face([corners[0], corners[2], corners[3]], blue);
+    assert_eq!(builder.face_count(), 2);
+    let square = builder.build();
+
+    let ray = Ray::new(Vec3 { x: 0.25, y: 0.75, z: 2.0 }, Vec3 { x: 0.0, y: 0.0, z: -1.0 });
+    let hit = square.intersects(&ray, 0.0, 10.0).unwrap();
+    assert!((hit.t - 2.0).abs() < 1e-6);
+    assert!((hit.u - 0.25).abs() < 1e-6 && (hit.v - 0.75).abs() < 1e-6);
+    assert!((hit.n - Vec3 { x: 0.0, y: 0.0, z: 1.0 }).len() < 1e-6);
+    assert!((hit.dpdu - Vec3 { x: 1.0, y: 0.0, z: 0.0 }).len() < 1e-6);
+    assert!((hit.material.sample(Vec3::zero(), Vec3::zero(), Vec3::zero(), 0.0, 0.0) - Vec3 { x: 0.0, y: 0.0, z: 1.0 }).len() < 1e-9);
+
+    let below = Ray::new(Vec3 { x: 0.75, y: 0.25, z: -2.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    let hit = square.intersects(&below, 0.0, 10.0).unwrap();
+    assert!(hit.ng.z > 0.0);
+    assert!((hit.material.sample(Vec3::zero(), Vec3::zero(), Vec3::zero(), 0.0, 0.0) - Vec3 { x: 1.0, y: 0.0, z: 0.0 }).len() < 1e-9);
+
+    let past = Ray::new(Vec3 { x: 1.5, y: 0.5, z: 2.0 }, Vec3 { x: 0.0, y: 0.0, z: -1.0 });
+    assert!(square.intersects(&past, 0.0, 10.0).is_none());
+}
+
+#[test]
+fn it_moves_with_its_transform() {
+    let mut builder = IndexedMeshBuilder::new();
+    let a = builder.add_vertex(Vec3 { x: 0.0, y: 0.0, z: 0.0 }, None, None);
+    let b = builder.add_vertex(Vec3 { x: 1.0, y: 0.0, z: 0.0 }, None, None);
+    let c = builder.add_vertex(Vec3 { x: 0.0, y: 1.0, z: 0.0 }, None, None);
+    let material = builder.add_material(Box::new(FlatMaterial { color: Vec3::one() })).unwrap();
+    builder.add_face([a, b, c], material);
+    let mut triangle = builder.build();
+    triangle.mut_transform(&Transform::new(Mat4::translate_matrix(&Vec3 { x: 0.0, y: 0.0, z: 5.0 })));
+
+    let ray = Ray::new(Vec3 { x: 0.2, y: 0.2, z: 0.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    let hit = triangle.intersects(&ray, 0.0, 10.0).unwrap();
+    assert!((hit.t - 5.0).abs() < 1e-6);
+    assert!((hit.u - 0.2).abs() < 1e-6 && (hit.v - 0.2).abs() < 1e-6);
+
+    let bounds = triangle.partial_bounding_box().unwrap();
+    assert!((bounds.min.z - 5.0).abs() < 1e-6);
+}
+
+#[test]
+fn it_refuses_more_materials_than_faces_can_index() {
+    let mut builder = IndexedMeshBuilder::new();
+    for _ in 0..::std::u16::MAX {
+        builder.add_material(Box::new(FlatMaterial { color: Vec3::one() })).unwrap();
+    }
+    assert!(builder.add_material(Box::new(FlatMaterial { color: Vec3::one() })).is_err());
+}
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+use geometry::bbox::{union_bbox, BBox, PartialBoundingBox};
+use geometry::prim::{self, Prim};
+use mat4::{Mat4, Transform};
+use raytracer::{Accelerator, Intersection, Ray};
+
+/// A placement of a shared object, such as a mesh, with its own transform.
+/// Any number of instances can reference the same object and its acceleration
+/// structure; rays are moved into object space rather than the object into
+/// world space.
+pub struct Instance {
+    pub object: Arc<Accelerator+Send+Sync>,
+    pub transform: Transform,
+    /// Set for instances that move while the shutter is open
+    pub motion: Option<Motion>
+}
+
+/// A move from the instance's `transform` at `start_time` to `end` at
+/// `end_time`. Rays see the instance where it is at `ray.time`, which gives
+/// motion blur; before and after the move it stays at either end.
+#[derive(Clone, Copy)]
+pub struct Motion {
+    pub end: Transform,
+    pub start_time: f64,
+    pub end_time: f64
+}
+
+impl Instance {
+    pub fn new(object: Arc<Accelerator+Send+Sync>, transform: Transform) -> Instance {
+        Instance {
+            object: object,
+            transform: transform,
+            motion: None
+        }
+    }
+
+    /// An instance moving from `start` to `end` between the given times.
+    /// The matrices are blended element by element, which suits moves and
+    /// scaling; large rotations shrink the object part way through.
+    pub fn moving(object: Arc<Accelerator+Send+Sync>, (start_time, start): (f64, Transform), (end_time, end): (f64, Transform)) -> Instance {
+        Instance {
+            object: object,
+            transform: start,
+            motion: Some(Motion { end: end, start_time: start_time, end_time: end_time })
+        }
+    }
+
+    fn transform_at(&self, time: f64) -> Transform {
+        match self.motion {
+            Some(ref motion) if motion.end_time > motion.start_time => {
+                let alpha = ((time - motion.start_time) / (motion.end_time - motion.start_time)).max(0.0).min(1.0);
+                Transform::new(Mat4::lerp(&self.transform.m, &motion.end.m, alpha))
+            },
+            _ => self.transform
+        }
+    }
+}
+
+impl PartialBoundingBox for Instance {
+    /// Every point moves in a straight line, so the boxes at either end of
+    /// the move bound it all the way
+    fn partial_bounding_box(&self) -> Option<BBox> {
+        self.object.bounds().map(|bbox| {
+            let start = Mat4::transform_bbox(&bbox, &self.transform.m);
+            match self.motion {
+                Some(ref motion) => union_bbox(&start, &Mat4::transform_bbox(&bbox, &motion.end.m)),
+                None => start
+            }
+        })
+    }
+}
+
+impl Prim for Instance {
+    fn intersects<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        let object = &self.object;
+        prim::intersects_in_object_space(&self.transform_at(ray.time), ray, t_min, t_max, |object_ray, t_min, t_max| {
+            object.nearest_hit(object_ray, t_min, t_max)
+        })
+    }
+
+    fn mut_transform(&mut self, transform: &Transform) {
+        self.transform = Transform::compose(transform, &self.transform);
+        if let Some(ref mut motion) = self.motion {
+            motion.end = Transform::compose(transform, &motion.end);
+        }
+    }
+}
+
+#[cfg(test)]
+use geometry::prims::Sphere;
+#[cfg(test)]
+use material::materials::FlatMaterial;
+#[cfg(test)]
+use raytracer::AcceleratorKind;
+#[cfg(test)]
+use vec3::Vec3;
+
+#[cfg(test)]
+fn get_unit_sphere() -> Arc<Accelerator+Send+Sync> {
+    let prims: Vec<Box<Prim+Send+Sync>> = vec![Box::new(Sphere::new(
+        Vec3::zero(),
+        1.0,
+        Box::new(FlatMaterial { color: Vec3::one() })
+    ))];
+    Arc::from(AcceleratorKind::Bvh.build(prims))
+}
+
+#[test]
+fn it_intersects_in_object_space() {
+    let sphere = get_unit_sphere();
+    let translate = Mat4::translate_matrix(&Vec3 { x: 5.0, y: 0.0, z: 0.0 });
+    let scale = Mat4::scale_matrix(&Vec3 { x: 2.0, y: 1.0, z: 1.0 });
+    let instance = Instance::new(sphere.clone(), Transform::new(Mat4::mult_m(&translate, &scale)));
+
+    let bbox = instance.partial_bounding_box().unwrap();
+    assert_eq!(bbox.min, Vec3 { x: 3.0, y: -1.0, z: -1.0 });
+    assert_eq!(bbox.max, Vec3 { x: 7.0, y: 1.0, z: 1.0 });
+
+    // The stretched side of the ellipsoid
+    let ray = Ray::new(Vec3 { x: 0.0, y: 0.0, z: 0.0 }, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+    let hit = instance.intersects(&ray, 0.0, 100.0).unwrap();
+    assert!((hit.t - 3.0).abs() < 1e-9);
+    assert!((hit.position.x - 3.0).abs() < 1e-9);
+    assert!((hit.n.x + 1.0).abs() < 1e-9);
+
+    // The unscaled side
+    let ray = Ray::new(Vec3 { x: 5.0, y: 10.0, z: 0.0 }, Vec3 { x: 0.0, y: -1.0, z: 0.0 });
+    let hit = instance.intersects(&ray, 0.0, 100.0).unwrap();
+    assert!((hit.t - 9.0).abs() < 1e-9);
+    assert!((hit.n.y - 1.0).abs() < 1e-9);
+
+    assert!(instance.intersects(&ray, 0.0, 8.0).is_none());
+}
+
+#[test]
+fn it_shares_the_object_between_instances() {
+    let sphere = get_unit_sphere();
+    let instances: Vec<Instance> = (0..10).map(|i| {
+        Instance::new(sphere.clone(), Transform::new(Mat4::translate_matrix(&Vec3 { x: 0.0, y: 0.0, z: i as f64 * 3.0 })))
+    }).collect();
+
+    assert_eq!(Arc::strong_count(&sphere), 11);
+    let ray = Ray::new(Vec3 { x: 0.0, y: 0.0, z: 40.0 }, Vec3 { x: 0.0, y: 0.0, z: -1.0 });
+    assert!((instances[9].intersects(&ray, 0.0, 100.0).unwrap().t - 12.0).abs() < 1e-9);
+    assert!((instances[7].intersects(&ray, 0.0, 100.0).unwrap().t - 18.0).abs() < 1e-9);
+}
+
+#[test]
+fn it_moves_with_the_ray_time() {
+    let sphere = get_unit_sphere();
+    let start = Transform::new(Mat4::translate_matrix(&Vec3 { x: -2.0, y: 0.0, z: 0.0 }));
+    let end = Transform::new(Mat4::translate_matrix(&Vec3 { x: 2.0, y: 0.0, z: 0.0 }));
+    let instance = Instance::moving(sphere, (2.0, start), (3.0, end));
+
+    let bbox = instance.partial_bounding_box().unwrap();
+    assert_eq!(bbox.min, Vec3 { x: -3.0, y: -1.0, z: -1.0 });
+    assert_eq!(bbox.max, Vec3 { x: 3.0, y: 1.0, z: 1.0 });
+
+    // Straight down through x = -2 finds the sphere only at the start, and
+    // through x = 2 only at the end. It waits at either end outside the move.
+    let down = Vec3 { x: 0.0, y: -1.0, z: 0.0 };
+    let at_start = Vec3 { x: -2.0, y: 10.0, z: 0.0 };
+    let at_end = Vec3 { x: 2.0, y: 10.0, z: 0.0 };
+    let hit = instance.intersects(&Ray::new_at_time(at_start, down, 2.0), 0.0, 100.0).unwrap();
+    assert!((hit.t - 9.0).abs() < 1e-9);
+    assert!((hit.position.x + 2.0).abs() < 1e-9);
+    assert!(instance.intersects(&Ray::new_at_time(at_end, down, 0.0), 0.0, 100.0).is_none());
+
+    let hit = instance.intersects(&Ray::new_at_time(at_end, down, 3.0), 0.0, 100.0).unwrap();
+    assert!((hit.position.x - 2.0).abs() < 1e-9);
+    assert!(instance.intersects(&Ray::new_at_time(at_start, down, 9.0), 0.0, 100.0).is_none());
+
+    // Half way through the move it is at the origin
+    let middle = Ray::new_at_time(Vec3 { x: 0.0, y: 10.0, z: 0.0 }, down, 2.5);
+    assert!((instance.intersects(&middle, 0.0, 100.0).unwrap().t - 9.0).abs() < 1e-9);
+}
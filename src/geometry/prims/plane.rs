@@ -40,35 +40,31 @@ impl Prim for Plane {
 
             Some(Intersection {
                 n: n,
+                ng: n,
                 t: t,
                 u: u,
                 v: v,
+                dpdu: u_axis - n.scale(n.dot(&u_axis) / n.dot(&n)),
+                dpdv: v_axis,
                 position: intersection_point,
+                shading_position: intersection_point,
                 material: &self.material
             })
         }
     }
 
-    /// This transformation is entirely ad-hoc, do not trust this
+    /// Planes stay planes under any affine transform, so the transform is baked in
     fn mut_transform(&mut self, transform: &Transform) {
-        let new_v = Mat4::mult_v(&transform.m, &Vec3 { x: self.a, y: self.b, z: self.c });
-
-        self.a = new_v.x;
-        self.b = new_v.y;
-        self.c = new_v.z;
-
-        let trans = Vec3 {
-            x: transform.m.m[0][3],
-            y: transform.m.m[1][3],
-            z: transform.m.m[2][3]
-        };
+        let n = Vec3 { x: self.a, y: self.b, z: self.c };
+        let point_on_plane = n.scale(-self.d / n.dot(&n));
 
-        let t_x = transform.m.m[0][3].powf(2.0) * if trans.x < 0.0 { -1.0 } else { 1.0 };
-        let t_y = transform.m.m[1][3].powf(2.0) * if trans.y < 0.0 { -1.0 } else { 1.0 };
-        let t_z = transform.m.m[2][3].powf(2.0) * if trans.z < 0.0 { -1.0 } else { 1.0 };
-        let add_sub = if t_x + t_y + t_z < 0.0 { -1.0 } else { 1.0 };
+        let new_n = Mat4::transform_normal_inv(&n, &transform.inv);
+        let new_point = Mat4::mult_p(&transform.m, &point_on_plane);
 
-        self.d += trans.len() * add_sub;
+        self.a = new_n.x;
+        self.b = new_n.y;
+        self.c = new_n.z;
+        self.d = -new_n.dot(&new_point);
     }
 }
 
@@ -110,3 +106,23 @@ fn it_intersects_only_in_tmin_tmax() {
     non_intersection = plane.intersects(&intersecting_ray, 0.0, 0.0001);
     assert!(non_intersection.is_none());
 }
+
+#[test]
+fn it_transforms() {
+    let mut plane = Plane { a: 0.0, b: 1.0, c: 0.0, d: -1.0, material: Box::new(FlatMaterial { color: Vec3::one() }) };
+
+    // y = 1 raised to y = 3, then turned so it faces along x: x = -3
+    plane.mut_transform(&Transform::new(Mat4::translate_matrix(&Vec3 { x: 5.0, y: 2.0, z: 0.0 })));
+    plane.mut_transform(&Transform::new(Mat4::rotate_z_deg_matrix(90.0)));
+
+    let ray = Ray::new(Vec3 { x: 0.0, y: 0.0, z: 0.0 }, Vec3 { x: -1.0, y: 0.0, z: 0.0 });
+    let hit = plane.intersects(&ray, 0.0, 10.0).unwrap();
+    assert!((hit.t - 3.0).abs() < 1e-9);
+    assert!((hit.n - Vec3 { x: -1.0, y: 0.0, z: 0.0 }).len() < 1e-9);
+
+    // Scaling moves the plane but keeps its normal unit length
+    plane.mut_transform(&Transform::new(Mat4::scale_matrix(&Vec3 { x: 2.0, y: 1.0, z: 1.0 })));
+    let hit = plane.intersects(&ray, 0.0, 10.0).unwrap();
+    assert!((hit.t - 6.0).abs() < 1e-9);
+    assert!((hit.n.len() - 1.0).abs() < 1e-9);
+}
@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use geometry::bbox::{BBox, PartialBoundingBox};
+use geometry::prim::{self, Prim};
+use material::Material;
+use mat4::{Mat4, Transform};
+use raytracer::{Bvh, Ray, Intersection};
+use vec3::Vec3;
+
+#[cfg(test)]
+use material::materials::FlatMaterial;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParticleShape {
+    Sphere,
+    /// Flat round splats, each turned to face the given point (usually the
+    /// camera) once and for all, so rays leaving a disc see the same disc
+    Disc(Vec3)
+}
+
+/// One particle in 20 bytes. Its material is an index into the cloud's
+/// palette rather than a material of its own.
+#[derive(Clone, Copy)]
+pub struct Particle {
+    pub position: [f32; 3],
+    pub radius: f32,
+    pub material: u16
+}
+
+impl Particle {
+    fn center(&self) -> Vec3 {
+        Vec3 { x: self.position[0] as f64, y: self.position[1] as f64, z: self.position[2] as f64 }
+    }
+
+    fn intersects(&self, shape: ParticleShape, ray: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
+        let to_center = self.center() - ray.origin;
+        let radius = self.radius as f64;
+
+        let t = match shape {
+            ParticleShape::Disc(facing) => {
+                let n = self.disc_normal(facing);
+                let denom = ray.direction.dot(&n);
+                if denom == 0.0 {
+                    return None;
+                }
+
+                let t = to_center.dot(&n) / denom;
+                let from_center = ray.origin + ray.direction.scale(t) - self.center();
+                if from_center.dot(&from_center) > radius * radius {
+                    return None;
+                }
+                t
+            },
+            ParticleShape::Sphere => {
+                let along = to_center.dot(&ray.direction);
+                let miss_squared = to_center.dot(&to_center) - along * along;
+                if miss_squared > radius * radius {
+                    return None;
+                }
+
+                let half_chord = (radius * radius - miss_squared).sqrt();
+                if along - half_chord >= t_min { along - half_chord } else { along + half_chord }
+            }
+        };
+
+        if t >= t_min && t <= t_max { Some(t) } else { None }
+    }
+
+    fn disc_normal(&self, facing: Vec3) -> Vec3 {
+        let n = (facing - self.center()).unit();
+        if n.x.is_nan() { Vec3 { x: 0.0, y: 1.0, z: 0.0 } } else { n }
+    }
+}
+
+impl PartialBoundingBox for Particle {
+    fn partial_bounding_box(&self) -> Option<BBox> {
+        let r = self.radius as f64;
+        let extent = Vec3 { x: r, y: r, z: r };
+        Some(BBox { min: self.center() - extent, max: self.center() + extent })
+    }
+}
+
+/// Collects particles for a `PointCloud`. Colours are rounded to 5 bits a
+/// channel so the whole cloud shares at most 32768 materials.
+pub struct ParticleSet {
+    particles: Vec<Particle>,
+    palette: Vec<Vec3>,
+    palette_index: HashMap<u16, u16>
+}
+
+impl ParticleSet {
+    pub fn new() -> ParticleSet {
+        ParticleSet {
+            particles: Vec::new(),
+            palette: Vec::new(),
+            palette_index: HashMap::new()
+        }
+    }
+
+    pub fn add(&mut self, position: Vec3, radius: f64, color: Vec3) {
+        let quantize = |c: f64| (c.max(0.0).min(1.0) * 31.0).round() as u16;
+        let key = quantize(color.x) << 10 | quantize(color.y) << 5 | quantize(color.z);
+
+        let palette = &mut self.palette;
+        let material = *self.palette_index.entry(key).or_insert_with(|| {
+            palette.push(Vec3 {
+                x: (key >> 10) as f64 / 31.0,
+                y: (key >> 5 & 31) as f64 / 31.0,
+                z: (key & 31) as f64 / 31.0
+            });
+            (palette.len() - 1) as u16
+        });
+
+        self.particles.push(Particle {
+            position: [position.x as f32, position.y as f32, position.z as f32],
+            radius: radius as f32,
+            material: material
+        });
+    }
+
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    /// `material` makes the material shared by every particle of a colour
+    pub fn build<F>(self, shape: ParticleShape, material: F) -> PointCloud
+            where F: Fn(Vec3) -> Box<Material+Send+Sync> {
+        PointCloud {
+            shape: shape,
+            materials: self.palette.into_iter().map(material).collect(),
+            particles: self.particles.into_iter().collect(),
+            transform: Transform::identity()
+        }
+    }
+}
+
+/// Many small spheres or discs as a single prim, with a hierarchy of its own
+/// over the particles
+pub struct PointCloud {
+    pub shape: ParticleShape,
+    pub materials: Vec<Box<Material+Send+Sync>>,
+    particles: Bvh<Particle>,
+    transform: Transform
+}
+
+impl PointCloud {
+    fn intersects_object<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        let mut nearest: Option<(&Particle, f64)> = None;
+        self.particles.traverse(ray, t_max, |particle, t_max| {
+            let hit = particle.intersects(self.shape, ray, t_min, t_max);
+            if let Some(t) = hit {
+                nearest = Some((particle, t));
+            }
+            hit
+        });
+
+        nearest.map(|(particle, t)| {
+            let position = ray.origin + ray.direction.scale(t);
+            let n = match self.shape {
+                ParticleShape::Sphere => (position - particle.center()).unit(),
+                ParticleShape::Disc(facing) => particle.disc_normal(facing)
+            };
+            let (dpdu, dpdv) = prim::spherical_derivatives(&n.scale(particle.radius as f64));
+
+            Intersection {
+                n: n,
+                ng: n,
+                t: t,
+                u: 0.5 + n.z.atan2(n.x) / (2.0 * PI),
+                v: 0.5 - n.y.asin() / PI,
+                dpdu: dpdu,
+                dpdv: dpdv,
+                position: position,
+                shading_position: position,
+                material: &self.materials[particle.material as usize]
+            }
+        })
+    }
+}
+
+impl PartialBoundingBox for PointCloud {
+    fn partial_bounding_box(&self) -> Option<BBox> {
+        self.particles.bounds().map(|bounds| Mat4::transform_bbox(&bounds, &self.transform.m))
+    }
+}
+
+impl Prim for PointCloud {
+    fn intersects<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        prim::intersects_in_object_space(&self.transform, ray, t_min, t_max, |object_ray, t_min, t_max| {
+            self.intersects_object(object_ray, t_min, t_max)
+        })
+    }
+
+    fn mut_transform(&mut self, transform: &Transform) {
+        self.transform = Transform::compose(transform, &self.transform);
+    }
+}
+
+#[test]
+fn it_intersects_the_nearest_particle() {
+    let mut set = ParticleSet::new();
+    for i in 0..100 {
+        set.add(Vec3 { x: 0.0, y: 0.0, z: i as f64 }, 0.25, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+    }
+    set.add(Vec3 { x: 0.0, y: 0.0, z: -5.0 }, 0.5, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    assert_eq!(set.len(), 101);
+
+    let spheres = set.build(ParticleShape::Sphere, |color| Box::new(FlatMaterial { color: color }));
+    assert_eq!(spheres.materials.len(), 2);
+
+    let ray = Ray::new(Vec3 { x: 0.0, y: 0.0, z: -10.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    let hit = spheres.intersects(&ray, 0.0, 100.0).unwrap();
+    assert!((hit.t - 4.5).abs() < 1e-6);
+    assert!((hit.n - Vec3 { x: 0.0, y: 0.0, z: -1.0 }).len() < 1e-6);
+
+    let hit = spheres.intersects(&ray, 6.0, 100.0).unwrap();
+    assert!((hit.t - 9.75).abs() < 1e-6);
+
+    let bbox = spheres.partial_bounding_box().unwrap();
+    assert!((bbox.max.z - 99.25).abs() < 1e-6);
+}
+
+#[test]
+fn it_faces_discs_to_the_given_point() {
+    let mut set = ParticleSet::new();
+    set.add(Vec3 { x: 1.0, y: 0.0, z: 0.0 }, 0.5, Vec3::one());
+    let discs = set.build(ParticleShape::Disc(Vec3 { x: 1.0, y: 5.0, z: 0.0 }), |color| Box::new(FlatMaterial { color: color }));
+
+    let ray = Ray::new(Vec3 { x: 1.3, y: 5.0, z: 0.0 }, Vec3 { x: 0.0, y: -1.0, z: 0.0 });
+    let hit = discs.intersects(&ray, 0.0, 100.0).unwrap();
+    assert!((hit.t - 5.0).abs() < 1e-6);
+    assert!((hit.n - Vec3 { x: 0.0, y: 1.0, z: 0.0 }).len() < 1e-9);
+
+    let past = Ray::new(Vec3 { x: 1.6, y: 5.0, z: 0.0 }, Vec3 { x: 0.0, y: -1.0, z: 0.0 });
+    assert!(discs.intersects(&past, 0.0, 100.0).is_none());
+
+    // Seen from the side, the disc stays flat rather than turning
+    let side = Ray::new(Vec3 { x: -5.0, y: 0.0, z: 0.2 }, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+    assert!(discs.intersects(&side, 0.0, 100.0).is_none());
+}
+
+#[test]
+fn it_does_not_shadow_discs_with_themselves() {
+    let mut set = ParticleSet::new();
+    set.add(Vec3::zero(), 0.5, Vec3::one());
+    let discs = set.build(ParticleShape::Disc(Vec3 { x: 0.0, y: 0.0, z: 10.0 }), |color| Box::new(FlatMaterial { color: color }));
+
+    // Hit off centre, on the half of the disc away from the light
+    let ray = Ray::new(Vec3 { x: -0.3, y: 0.1, z: 10.0 }, Vec3 { x: 0.0, y: 0.0, z: -1.0 });
+    let hit = discs.intersects(&ray, 0.0, 100.0).unwrap();
+
+    for &towards in [Vec3 { x: 1.0, y: 0.2, z: 0.3 }, Vec3 { x: 1.0, y: -0.1, z: -0.3 }].iter() {
+        let direction = towards.unit();
+        let secondary = Ray::new(hit.offset_origin(&direction), direction);
+        assert!(discs.intersects(&secondary, 0.0, 100.0).is_none());
+    }
+}
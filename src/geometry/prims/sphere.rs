@@ -1,5 +1,6 @@
 use geometry::bbox::{BBox, PartialBoundingBox};
-use geometry::prim::Prim;
+use geometry::prim::{self, Prim};
+use geometry::roots;
 use material::Material;
 use mat4::{Mat4, Transform};
 use raytracer::{Ray, Intersection};
@@ -12,12 +13,24 @@ use material::materials::FlatMaterial;
 pub struct Sphere {
     pub center: Vec3,
     pub radius: f64,
-    pub material: Box<Material+Send+Sync>
+    pub material: Box<Material+Send+Sync>,
+    // Set once a transform that stretches or shears has turned the sphere
+    // into an ellipsoid. The center and radius are then in object space.
+    transform: Option<Transform>
 }
 
-impl PartialBoundingBox for Sphere {
-    fn partial_bounding_box(&self) -> Option<BBox> {
-        Some(BBox {
+impl Sphere {
+    pub fn new(center: Vec3, radius: f64, material: Box<Material+Send+Sync>) -> Sphere {
+        Sphere {
+            center: center,
+            radius: radius,
+            material: material,
+            transform: None
+        }
+    }
+
+    fn object_bounding_box(&self) -> BBox {
+        BBox {
             min: Vec3 {
                 x: self.center.x - self.radius,
                 y: self.center.y - self.radius,
@@ -28,71 +41,118 @@ impl PartialBoundingBox for Sphere {
                 y: self.center.y + self.radius,
                 z: self.center.z + self.radius
             }
+        }
+    }
+
+    /// Where the ray's line crosses the sphere, nearest first
+    fn roots(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let i = ray.origin - self.center;
+        roots::quadratic(1.0, 2.0 * ray.direction.dot(&i), i.dot(&i) - self.radius * self.radius)
+    }
+
+    fn hit_at<'a>(&'a self, ray: &Ray, t: f64) -> Intersection<'a> {
+        let intersection_point = ray.origin + ray.direction.scale(t);
+        let n = (intersection_point - self.center).unit();
+
+        let u = 0.5 + n.z.atan2(n.x) / (::std::f64::consts::PI * 2.0);
+        let v = 0.5 - n.y.asin() / ::std::f64::consts::PI;
+        let (dpdu, dpdv) = prim::spherical_derivatives(&(intersection_point - self.center));
+
+        Intersection {
+            n: n,
+            ng: n,
+            t: t,
+            u: u,
+            v: v,
+            dpdu: dpdu,
+            dpdv: dpdv,
+            position: intersection_point,
+            shading_position: intersection_point,
+            material: &self.material
+        }
+    }
+
+    fn intersects_object<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        // Up to two intersections: take the nearer one in range
+        self.roots(ray).and_then(|(t_near, t_far)| {
+            if t_near >= t_min && t_near <= t_max {
+                Some(self.hit_at(ray, t_near))
+            } else if t_far >= t_min && t_far <= t_max {
+                Some(self.hit_at(ray, t_far))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn all_hits_object<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<Intersection<'a>> {
+        match self.roots(ray) {
+            Some((t_near, t_far)) => {
+                [t_near, t_far].iter()
+                    .filter(|&&t| t >= t_min && t <= t_max)
+                    .map(|&t| self.hit_at(ray, t))
+                    .collect()
+            },
+            None => Vec::new()
+        }
+    }
+}
+
+impl PartialBoundingBox for Sphere {
+    fn partial_bounding_box(&self) -> Option<BBox> {
+        let bbox = self.object_bounding_box();
+
+        Some(match self.transform {
+            Some(ref transform) => Mat4::transform_bbox(&bbox, &transform.m),
+            None => bbox
         })
     }
 }
 
 impl Prim for Sphere {
     fn intersects<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
-        let i = ray.origin - self.center;
-        let a = 1.0;
-        let b = 2.0 * ray.direction.dot(&i);
-        let c = i.dot(&i) - self.radius * self.radius;
-        let discriminant = b * b - 4.0 * a * c;
-
-        if discriminant <= 0.0 {
-            None
-        } else {
-            // Up to two intersections
-            let disc_sqrt = discriminant.sqrt();
-            let t1 = (-b + disc_sqrt) / 2.0 * a;
-            let t2 = (-b - disc_sqrt) / 2.0 * a;
-
-            if t1 >= t_min && t1 <= t_max ||
-               t2 >= t_min && t2 <= t_max {
-                // Valid intersection(s): get nearer intersection
-                let t = if t1.abs() < t2.abs() { t1 } else { t2 };
-                let intersection_point = ray.origin + ray.direction.scale(t);
-                let n = (intersection_point - self.center).unit();
-
-                let u = 0.5 + n.z.atan2(n.x) / (::std::f64::consts::PI * 2.0);
-                let v = 0.5 - n.y.asin() / ::std::f64::consts::PI;
-
-                Some(Intersection {
-                    n: n,
-                    t: t,
-                    u: u,
-                    v: v,
-                    position: intersection_point,
-                    material: &self.material
+        match self.transform {
+            Some(ref transform) => {
+                prim::intersects_in_object_space(transform, ray, t_min, t_max, |object_ray, t_min, t_max| {
+                    self.intersects_object(object_ray, t_min, t_max)
                 })
-            } else {
-                None
-            }
+            },
+            None => self.intersects_object(ray, t_min, t_max)
         }
     }
 
-    fn mut_transform(&mut self, transform: &Transform) {
-        let new_center = Mat4::mult_p(&transform.m, &self.center);
+    fn all_hits<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<Intersection<'a>> {
+        match self.transform {
+            Some(ref transform) => {
+                prim::all_hits_in_object_space(transform, ray, t_min, t_max, |object_ray, t_min, t_max| {
+                    self.all_hits_object(object_ray, t_min, t_max)
+                })
+            },
+            None => self.all_hits_object(ray, t_min, t_max)
+        }
+    }
 
-        let new_radius = if transform.m.has_scale() {
-            self.radius * transform.m.scale()
-        } else {
-            self.radius
-        };
+    /// Rotations, translations and uniform scaling are baked into the center
+    /// and radius. Anything else leaves them in object space.
+    fn mut_transform(&mut self, transform: &Transform) {
+        if let Some(ref mut existing) = self.transform {
+            *existing = Transform::compose(transform, existing);
+            return;
+        }
 
-        self.center = new_center;
-        self.radius = new_radius;
+        match transform.m.similarity_scale() {
+            Some(scale) => {
+                self.center = Mat4::mult_p(&transform.m, &self.center);
+                self.radius *= scale;
+            },
+            None => self.transform = Some(*transform)
+        }
     }
 }
 
 #[test]
 fn it_intersects() {
-    let sphere = Sphere {
-        center: Vec3::zero(),
-        radius: 1.0,
-        material: Box::new(FlatMaterial { color: Vec3::one() })
-    };
+    let sphere = Sphere::new(Vec3::zero(), 1.0, Box::new(FlatMaterial { color: Vec3::one() }));
 
     // Tests actual intersection
     let intersecting_ray = Ray::new(Vec3 { x: 0.0, y: 0.0, z: -2.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
@@ -121,11 +181,7 @@ fn it_intersects() {
 
 #[test]
 fn it_intersects_only_in_tmin_tmax() {
-    let sphere = Sphere {
-        center: Vec3::zero(),
-        radius: 1.0,
-        material: Box::new(FlatMaterial { color: Vec3::one() })
-    };
+    let sphere = Sphere::new(Vec3::zero(), 1.0, Box::new(FlatMaterial { color: Vec3::one() }));
 
     // Tests tmin
     let intersecting_ray = Ray::new(Vec3 { x: 0.0, y: 0.0, z: -2.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
@@ -136,3 +192,44 @@ fn it_intersects_only_in_tmin_tmax() {
     non_intersection = sphere.intersects(&intersecting_ray, 0.0, 0.0001);
     assert!(non_intersection.is_none());
 }
+
+#[test]
+fn it_transforms() {
+    let mut sphere = Sphere::new(Vec3::zero(), 1.0, Box::new(FlatMaterial { color: Vec3::one() }));
+
+    // Rotation, translation and uniform scaling stay a sphere
+    sphere.mut_transform(&Transform::new(Mat4::rotate_y_deg_matrix(30.0)));
+    sphere.mut_transform(&Transform::new(Mat4::mult_m(&Mat4::translate_matrix(&Vec3 { x: 0.0, y: 0.0, z: 5.0 }), &Mat4::scale_matrix(&Vec3::one().scale(2.0)))));
+    assert!(sphere.transform.is_none());
+    assert!((sphere.center - Vec3 { x: 0.0, y: 0.0, z: 5.0 }).len() < 1e-9);
+    assert!((sphere.radius - 2.0).abs() < 1e-9);
+
+    let ray = Ray::new(Vec3 { x: 0.0, y: 0.0, z: 0.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    assert!((sphere.intersects(&ray, 0.0, 10.0).unwrap().t - 3.0).abs() < 1e-9);
+}
+
+#[test]
+fn it_becomes_an_ellipsoid_under_non_uniform_scale() {
+    let mut sphere = Sphere::new(Vec3::zero(), 1.0, Box::new(FlatMaterial { color: Vec3::one() }));
+    sphere.mut_transform(&Transform::new(Mat4::scale_matrix(&Vec3 { x: 3.0, y: 1.0, z: 1.0 })));
+    sphere.mut_transform(&Transform::new(Mat4::rotate_z_deg_matrix(90.0)));
+
+    // Now stretched along y
+    let bbox = sphere.partial_bounding_box().unwrap();
+    assert!((bbox.min - Vec3 { x: -1.0, y: -3.0, z: -1.0 }).len() < 1e-9);
+    assert!((bbox.max - Vec3 { x: 1.0, y: 3.0, z: 1.0 }).len() < 1e-9);
+
+    let down = Ray::new(Vec3 { x: 0.0, y: 10.0, z: 0.0 }, Vec3 { x: 0.0, y: -1.0, z: 0.0 });
+    let hit = sphere.intersects(&down, 0.0, 100.0).unwrap();
+    assert!((hit.t - 7.0).abs() < 1e-9);
+    assert!((hit.n - Vec3 { x: 0.0, y: 1.0, z: 0.0 }).len() < 1e-9);
+
+    let across = Ray::new(Vec3 { x: 10.0, y: 0.0, z: 0.0 }, Vec3 { x: -1.0, y: 0.0, z: 0.0 });
+    assert!((sphere.intersects(&across, 0.0, 100.0).unwrap().t - 9.0).abs() < 1e-9);
+
+    // Normals on the flank lean towards the short axis
+    let flank = Ray::new(Vec3 { x: 10.0, y: 1.5, z: 0.0 }, Vec3 { x: -1.0, y: 0.0, z: 0.0 });
+    let hit = sphere.intersects(&flank, 0.0, 100.0).unwrap();
+    assert!(hit.n.x > 0.9 && hit.n.y > 0.0);
+    assert!((hit.n.len() - 1.0).abs() < 1e-9);
+}
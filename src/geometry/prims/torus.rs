@@ -0,0 +1,160 @@
+use std::f64::consts::PI;
+use rand::{thread_rng, Rng};
+use geometry::bbox::{BBox, PartialBoundingBox};
+use geometry::emitter::Emitter;
+use geometry::prim::{self, Prim};
+use geometry::roots;
+use material::Material;
+use mat4::{Mat4, Transform};
+use raytracer::{Ray, Intersection};
+use vec3::Vec3;
+
+#[cfg(test)]
+use material::materials::FlatMaterial;
+
+/// A ring around the y axis in object space. `major_radius` is from the
+/// center to the middle of the tube, `minor_radius` the tube's own radius.
+#[allow(dead_code)]
+pub struct Torus {
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    pub material: Box<Material+Send+Sync>,
+    transform: Transform
+}
+
+impl Torus {
+    pub fn new(center: Vec3, axis: Vec3, major_radius: f64, minor_radius: f64, material: Box<Material+Send+Sync>) -> Torus {
+        Torus {
+            major_radius: major_radius,
+            minor_radius: minor_radius,
+            material: material,
+            transform: Transform::new(Mat4::mult_m(&Mat4::translate_matrix(&center), &Mat4::align_y_matrix(&axis)))
+        }
+    }
+
+    fn object_bounding_box(&self) -> BBox {
+        let outer = self.major_radius + self.minor_radius;
+        BBox {
+            min: Vec3 { x: -outer, y: -self.minor_radius, z: -outer },
+            max: Vec3 { x: outer, y: self.minor_radius, z: outer }
+        }
+    }
+
+    fn intersects_object<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        // Only search where the ray is inside the bounds
+        let (t_enter, t_exit) = match self.object_bounding_box().intersect_range(ray) {
+            Some(range) => range,
+            None => return None
+        };
+        let lo = t_enter.max(t_min);
+        let hi = t_exit.min(t_max);
+        if lo > hi {
+            return None;
+        }
+
+        // Measure t from the start of the range so the coefficients stay small
+        let o = ray.origin + ray.direction.scale(lo);
+        let d = ray.direction;
+        let r2 = self.major_radius * self.major_radius;
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2), for p = o + t d and |d| = 1
+        let e = o.dot(&o) + r2 - self.minor_radius * self.minor_radius;
+        let f = o.dot(&d);
+        let coeffs = [
+            1.0,
+            4.0 * f,
+            4.0 * f * f + 2.0 * e - 4.0 * r2 * (d.x * d.x + d.z * d.z),
+            4.0 * e * f - 8.0 * r2 * (o.x * d.x + o.z * d.z),
+            e * e - 4.0 * r2 * (o.x * o.x + o.z * o.z)
+        ];
+
+        let t = match roots::polynomial_in_range(&coeffs, 0.0, hi - lo).first() {
+            Some(&t) => t + lo,
+            None => return None
+        };
+
+        let p = ray.origin + ray.direction.scale(t);
+        let s = p.dot(&p) + r2 - self.minor_radius * self.minor_radius;
+        let n = Vec3 { x: p.x * (s - 2.0 * r2), y: p.y * s, z: p.z * (s - 2.0 * r2) }.unit();
+        let ring_distance = (p.x * p.x + p.z * p.z).sqrt() - self.major_radius;
+
+        Some(Intersection {
+            n: n,
+            ng: n,
+            t: t,
+            u: 0.5 + p.z.atan2(p.x) / (2.0 * PI),
+            v: 0.5 + p.y.atan2(ring_distance) / (2.0 * PI),
+            dpdu: prim::dpdu_around_y(&p),
+            dpdv: Vec3 { x: -p.y * p.x, y: ring_distance * (ring_distance + self.major_radius), z: -p.y * p.z }
+                .scale(2.0 * PI / (ring_distance + self.major_radius)),
+            position: p,
+            shading_position: p,
+            material: &self.material
+        })
+    }
+}
+
+impl PartialBoundingBox for Torus {
+    fn partial_bounding_box(&self) -> Option<BBox> {
+        Some(Mat4::transform_bbox(&self.object_bounding_box(), &self.transform.m))
+    }
+}
+
+impl Prim for Torus {
+    fn intersects<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        prim::intersects_in_object_space(&self.transform, ray, t_min, t_max, |object_ray, t_min, t_max| {
+            self.intersects_object(object_ray, t_min, t_max)
+        })
+    }
+
+    fn mut_transform(&mut self, transform: &Transform) {
+        self.transform = Transform::compose(transform, &self.transform);
+    }
+}
+
+impl Emitter for Torus {
+    fn sample_surface(&self) -> Vec3 {
+        let mut rng = thread_rng();
+        let outer = self.major_radius + self.minor_radius;
+
+        // The outside of the ring has more area than the inside, so reject
+        // samples in proportion to their distance from the axis
+        loop {
+            let theta = 2.0 * PI * rng.gen::<f64>();
+            let phi = 2.0 * PI * rng.gen::<f64>();
+            let distance = self.major_radius + self.minor_radius * phi.cos();
+
+            if rng.gen::<f64>() * outer <= distance {
+                let point = Vec3 { x: distance * theta.cos(), y: self.minor_radius * phi.sin(), z: distance * theta.sin() };
+                return Mat4::mult_p(&self.transform.m, &point);
+            }
+        }
+    }
+
+    fn center(&self) -> Vec3 {
+        Mat4::mult_p(&self.transform.m, &Vec3::zero())
+    }
+}
+
+#[test]
+fn it_intersects() {
+    let torus = Torus::new(Vec3 { x: 0.0, y: 0.0, z: 0.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 }, 3.0, 1.0,
+                           Box::new(FlatMaterial { color: Vec3::one() }));
+
+    // Through the hole without touching
+    let hole = Ray::new(Vec3 { x: 0.0, y: 0.0, z: -10.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    assert!(torus.intersects(&hole, 0.0, 100.0).is_none());
+
+    // Through the tube
+    let tube = Ray::new(Vec3 { x: 3.0, y: 0.0, z: -10.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    let hit = torus.intersects(&tube, 0.0, 100.0).unwrap();
+    assert!((hit.t - 9.0).abs() < 1e-9);
+    assert!((hit.n - Vec3 { x: 0.0, y: 0.0, z: -1.0 }).len() < 1e-9);
+
+    // Across the ring, hitting the outside first and then the inside of the tube
+    let across = Ray::new(Vec3 { x: -10.0, y: 0.0, z: 0.0 }, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+    assert!((torus.intersects(&across, 0.0, 100.0).unwrap().t - 6.0).abs() < 1e-9);
+    let hit = torus.intersects(&across, 7.0, 100.0).unwrap();
+    assert!((hit.t - 8.0).abs() < 1e-9);
+    assert!((hit.n - Vec3 { x: 1.0, y: 0.0, z: 0.0 }).len() < 1e-9);
+}
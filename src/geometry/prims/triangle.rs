@@ -1,15 +1,23 @@
 #![allow(dead_code)]
 
+use std::sync::Arc;
 use geometry::bbox::{union_point, union_points, BBox, PartialBoundingBox};
-use geometry::prim::Prim;
-use material::Material;
+use geometry::prim::{self, Prim};
+use material::{Material, Texture};
+use material::bump::{self, Bump};
 use mat4::{Mat4, Transform};
 use raytracer::{Ray, Intersection};
 use vec3::Vec3;
 
-use material::materials::FlatMaterial;
+use material::materials::{FlatMaterial, SharedMaterial};
 
+#[cfg(test)]
+use material::textures::CheckerTexture;
+#[cfg(test)]
+use raytracer::compositor::ColorRGBA;
 
+
+#[derive(Clone, Copy)]
 struct UvValue {
     u: f64,
     v: f64
@@ -29,11 +37,16 @@ impl UvValue {
     }
 }
 
+/// Levels of `TriangleOptions::displaced` past which triangles are split no
+/// further. 4^8 pieces each is already finer than most height textures.
+const MAX_DISPLACEMENT_LEVELS: u32 = 8;
+
 pub struct TriangleOptions {
     vertices: [Vec3; 3],
     normals: Option<[Vec3; 3]>,
     texinfo: Option<[UvValue; 3]>,
     material: Option<Box<Material+Send+Sync>>,
+    bump: Option<Arc<Bump>>,
 }
 
 fn get_auto_normals(v: [Vec3; 3]) -> [Vec3; 3] {
@@ -41,6 +54,39 @@ fn get_auto_normals(v: [Vec3; 3]) -> [Vec3; 3] {
     [n, n, n]
 }
 
+/// How position changes with u and v across the triangle, falling back to
+/// any frame around the normal if the UVs are degenerate
+pub fn get_uv_derivatives(v: &[Vec3; 3], uv: &[(f64, f64); 3]) -> (Vec3, Vec3) {
+    let e1 = v[1] - v[0];
+    let e2 = v[2] - v[0];
+    let (du1, dv1) = (uv[1].0 - uv[0].0, uv[1].1 - uv[0].1);
+    let (du2, dv2) = (uv[2].0 - uv[0].0, uv[2].1 - uv[0].1);
+    let det = du1 * dv2 - dv1 * du2;
+
+    if det.abs() < ::std::f64::EPSILON {
+        return prim::frame_around(&e1.cross(&e2));
+    }
+
+    let inv_det = 1.0 / det;
+    ((e1.scale(dv2) - e2.scale(dv1)).scale(inv_det),
+     (e2.scale(du1) - e1.scale(du2)).scale(inv_det))
+}
+
+/// Where the smooth surface would be (Hanika, "Hacking the shadow
+/// terminator"): lifts `p` out of the tangent plane of each vertex it lies
+/// below, weighted like the normals
+pub fn get_shading_position(p: Vec3, vertices: &[Vec3; 3], normals: &[Vec3; 3], weights: [f64; 3]) -> Vec3 {
+    (0..3).fold(p, |lifted, i| {
+        let vertex_n = normals[i].unit();
+        let below = (p - vertices[i]).dot(&vertex_n).min(0.0);
+        lifted - vertex_n.scale(below * weights[i])
+    })
+}
+
+fn uv_tuples(uv: &[UvValue; 3]) -> [(f64, f64); 3] {
+    [(uv[0].u, uv[0].v), (uv[1].u, uv[1].v), (uv[2].u, uv[2].v)]
+}
+
 impl TriangleOptions {   
     pub fn new(v0: Vec3, v1: Vec3, v2: Vec3) -> TriangleOptions {
         TriangleOptions {
@@ -48,6 +94,7 @@ impl TriangleOptions {
             normals: None,
             texinfo: None,
             material: None,
+            bump: None,
         }
     }
 
@@ -72,20 +119,124 @@ impl TriangleOptions {
         self
     }
 
+    /// Shared between the triangles of a mesh. Oriented by the texinfo UVs.
+    pub fn bump(&mut self, bump: Arc<Bump>) -> &mut Self {
+        self.bump = Some(bump);
+        self
+    }
+
+    /// Splits the triangle into 4^`levels` pieces and raises each new vertex
+    /// along its normal by `scale` times the height texture, with normals
+    /// tilted to match. The pieces share the material and bump of the triangle.
+    /// `levels` is clamped to `MAX_DISPLACEMENT_LEVELS`.
+    pub fn displaced(mut self, height: &Texture, scale: f64, levels: u32) -> Vec<TriangleOptions> {
+        let normals = self.normals.unwrap_or_else(|| get_auto_normals(self.vertices));
+        let texinfo = self.texinfo.unwrap_or_else(UvValue::default3);
+        let (dpdu, dpdv) = get_uv_derivatives(&self.vertices, &uv_tuples(&texinfo));
+        let segments = 1 << levels.min(MAX_DISPLACEMENT_LEVELS);
+        let material = self.material.take().map(SharedMaterial::from_box);
+
+        // Vertex i, j of the grid, i steps towards vertices[1] and j towards vertices[2]
+        let grid_vertex = |i: u32, j: u32| {
+            let beta = i as f64 / segments as f64;
+            let gamma = j as f64 / segments as f64;
+            let alpha = 1.0 - beta - gamma;
+
+            let p = self.vertices[0].scale(alpha) + self.vertices[1].scale(beta) + self.vertices[2].scale(gamma);
+            let n = (normals[0].scale(alpha) + normals[1].scale(beta) + normals[2].scale(gamma)).unit();
+            let u = texinfo[0].u * alpha + texinfo[1].u * beta + texinfo[2].u * gamma;
+            let v = texinfo[0].v * alpha + texinfo[1].v * beta + texinfo[2].v * gamma;
+
+            (p + n.scale(scale * bump::height(height, u, v)),
+             bump::height_normal(height, scale, &n, &dpdu, &dpdv, u, v),
+             (u, v))
+        };
+
+        let piece = |a: (Vec3, Vec3, (f64, f64)), b: (Vec3, Vec3, (f64, f64)), c: (Vec3, Vec3, (f64, f64))| {
+            let mut triopts = TriangleOptions::new(a.0, b.0, c.0);
+            triopts.normals([a.1, b.1, c.1]);
+            triopts.texinfo([a.2, b.2, c.2]);
+            triopts.material = material.clone().map(|material| Box::new(material) as Box<Material+Send+Sync>);
+            triopts.bump = self.bump.clone();
+            triopts
+        };
+
+        let mut pieces = Vec::new();
+        for j in 0..segments {
+            for i in 0..segments - j {
+                pieces.push(piece(grid_vertex(i, j), grid_vertex(i + 1, j), grid_vertex(i, j + 1)));
+                if i + j + 1 < segments {
+                    pieces.push(piece(grid_vertex(i + 1, j), grid_vertex(i + 1, j + 1), grid_vertex(i, j + 1)));
+                }
+            }
+        }
+
+        pieces
+    }
+
+    pub fn bounding_box(&self) -> BBox {
+        union_point(&union_points(&self.vertices[0], &self.vertices[1]), &self.vertices[2])
+    }
+
     pub fn build(self) -> Triangle {
         let normals = self.normals.unwrap_or_else(|| get_auto_normals(self.vertices));
         let texinfo = self.texinfo.unwrap_or_else(UvValue::default3);
         let material = self.material.unwrap_or_else(|| Box::new(FlatMaterial { color: Vec3::one() }));
+        let (dpdu, dpdv) = get_uv_derivatives(&self.vertices, &uv_tuples(&texinfo));
 
         Triangle {
             vertices: self.vertices,
             normals: normals,
             texinfo: texinfo,
+            dpdu: dpdu,
+            dpdv: dpdv,
             material: material,
+            bump: self.bump,
         }
     }
 }
 
+/// Watertight ray/triangle intersection (Woo, Benthin and Wald, 2013). The
+/// edge tests are done in a space where the ray runs along an axis, so a ray
+/// through an edge or vertex shared by several triangles always hits at
+/// least one of them. Gives t and the barycentric weights of the vertices.
+pub fn intersects_watertight(ray: &Ray, vertices: &[Vec3; 3], t_min: f64, t_max: f64) -> Option<(f64, f64, f64, f64)> {
+    // Make the largest direction component z, keeping the winding
+    let d = ray.direction;
+    let kz = if d.x.abs() > d.y.abs() { if d.x.abs() > d.z.abs() { 0 } else { 2 } } else if d.y.abs() > d.z.abs() { 1 } else { 2 };
+    let (kx, ky) = if d.axis(kz) < 0.0 { ((kz + 2) % 3, (kz + 1) % 3) } else { ((kz + 1) % 3, (kz + 2) % 3) };
+
+    // Shear so the ray runs up z from the origin
+    let shear_x = d.axis(kx) / d.axis(kz);
+    let shear_y = d.axis(ky) / d.axis(kz);
+    let shear_z = 1.0 / d.axis(kz);
+    let sheared = |v: &Vec3| {
+        let p = *v - ray.origin;
+        (p.axis(kx) - shear_x * p.axis(kz), p.axis(ky) - shear_y * p.axis(kz), shear_z * p.axis(kz))
+    };
+    let (a, b, c) = (sheared(&vertices[0]), sheared(&vertices[1]), sheared(&vertices[2]));
+
+    // Twice the signed areas facing each vertex, seen down the ray
+    let u = c.0 * b.1 - c.1 * b.0;
+    let v = a.0 * c.1 - a.1 * c.0;
+    let w = b.0 * a.1 - b.1 * a.0;
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None;
+    }
+
+    let det = u + v + w;
+    if det == 0.0 {
+        return None;
+    }
+
+    let t = (u * a.2 + v * b.2 + w * c.2) / det;
+    if t < t_min || t > t_max {
+        return None;
+    }
+
+    Some((t, u / det, v / det, w / det))
+}
+
 pub struct Triangle {
     vertices: [Vec3; 3],
 
@@ -96,7 +247,13 @@ pub struct Triangle {
     // Used in textured triangles, can be [UvValue; 3]::default() otherwise.
     texinfo: [UvValue; 3],
 
-    material: Box<Material+Send+Sync>
+    // Constant across the triangle, so worked out once from the UVs
+    dpdu: Vec3,
+    dpdv: Vec3,
+
+    material: Box<Material+Send+Sync>,
+
+    bump: Option<Arc<Bump>>
 }
 
 impl PartialBoundingBox for Triangle {
@@ -106,53 +263,41 @@ impl PartialBoundingBox for Triangle {
 }
 
 impl Prim for Triangle {
-    /// http://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
-    /// Barycentric coordinates.
     fn intersects<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
-        let e1 = self.vertices[1] - self.vertices[0];
-        let e2 = self.vertices[2] - self.vertices[0];
-        let p = ray.direction.cross(&e2);
-        let det = e1.dot(&p);
-
-        // if determinant is near zero, ray lies in plane of triangle
-        if det > -::std::f64::EPSILON && det < ::std::f64::EPSILON {
-            return None
-        }
-
-        let inv_det = 1.0 / det;
-        let s = ray.origin - self.vertices[0];
-        let beta = inv_det * s.dot(&p);
-        if beta < 0.0 || beta > 1.0 { return None }
-
-        let q = s.cross(&e1);
-        let gamma = inv_det * ray.direction.dot(&q);
-        if gamma < 0.0 || beta + gamma > 1.0 { return None }
-
-        let t = inv_det * e2.dot(&q);
-
-        if t < t_min || t > t_max {
-            None
-        } else {
-            let intersection_point = ray.origin + ray.direction.scale(t);
-
-            let alpha = 1.0 - beta - gamma;
-
-            // Interpolate normals at vertices to get normal
-            let n = self.normals[0].scale(alpha) + self.normals[1].scale(beta) + self.normals[2].scale(gamma);
-
-            // Interpolate UVs at vertices to get UV
-            let u = self.texinfo[0].u * alpha + self.texinfo[1].u * beta + self.texinfo[2].u * gamma;
-            let v = self.texinfo[0].v * alpha + self.texinfo[1].v * beta + self.texinfo[2].v * gamma;
-
-            Some(Intersection {
-                n: n,
-                t: t,
-                u: u,
-                v: v,
-                position: intersection_point,
-                material: &self.material
-            })
-        }
+        let (t, alpha, beta, gamma) = match intersects_watertight(ray, &self.vertices, t_min, t_max) {
+            Some(hit) => hit,
+            None => return None
+        };
+        let intersection_point = ray.origin + ray.direction.scale(t);
+
+        // Interpolate normals at vertices to get normal
+        let n = self.normals[0].scale(alpha) + self.normals[1].scale(beta) + self.normals[2].scale(gamma);
+
+        // Interpolate UVs at vertices to get UV
+        let u = self.texinfo[0].u * alpha + self.texinfo[1].u * beta + self.texinfo[2].u * gamma;
+        let v = self.texinfo[0].v * alpha + self.texinfo[1].v * beta + self.texinfo[2].v * gamma;
+
+        let face = (self.vertices[1] - self.vertices[0]).cross(&(self.vertices[2] - self.vertices[0])).unit();
+        let ng = if face.dot(&n) < 0.0 { -face } else { face };
+        let shading_position = get_shading_position(intersection_point, &self.vertices, &self.normals, [alpha, beta, gamma]);
+
+        let n = match self.bump {
+            Some(ref bump) => bump.perturb(&n, &self.dpdu, &self.dpdv, u, v),
+            None => n
+        };
+
+        Some(Intersection {
+            n: n,
+            ng: ng,
+            t: t,
+            u: u,
+            v: v,
+            dpdu: self.dpdu,
+            dpdv: self.dpdv,
+            position: intersection_point,
+            shading_position: shading_position,
+            material: &self.material
+        })
     }
 
     fn mut_transform(&mut self, transform: &Transform) {
@@ -160,9 +305,9 @@ impl Prim for Triangle {
         let v1_t = Mat4::mult_p(&transform.m, &self.vertices[1]);
         let v2_t = Mat4::mult_p(&transform.m, &self.vertices[2]);
 
-        let n0_t = Mat4::transform_normal(&self.normals[0], &transform.m);
-        let n1_t = Mat4::transform_normal(&self.normals[1], &transform.m);
-        let n2_t = Mat4::transform_normal(&self.normals[2], &transform.m);
+        let n0_t = Mat4::transform_normal_inv(&self.normals[0], &transform.inv);
+        let n1_t = Mat4::transform_normal_inv(&self.normals[1], &transform.inv);
+        let n2_t = Mat4::transform_normal_inv(&self.normals[2], &transform.inv);
 
         self.vertices[0] = v0_t;
         self.vertices[1] = v1_t;
@@ -171,6 +316,10 @@ impl Prim for Triangle {
         self.normals[0] = n0_t;
         self.normals[1] = n1_t;
         self.normals[2] = n2_t;
+
+        let (dpdu, dpdv) = get_uv_derivatives(&self.vertices, &uv_tuples(&self.texinfo));
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
     }
 }
 
@@ -215,6 +364,68 @@ fn it_intersects_and_interpolates() {
     assert!(non_intersection.is_none());
 }
 
+#[test]
+fn it_bumps_and_displaces() {
+    let mut triopts = TriangleOptions::new(
+        Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+        Vec3 { x: 1.0, y: 0.0, z: 0.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 });
+    triopts.texinfo([(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]);
+
+    // Normal map pointing along u
+    let along_u = ColorRGBA::new_rgb(1.0, 0.5, 0.5);
+    let texture = CheckerTexture { color1: along_u, color2: along_u, scale: 1.0 };
+    let bump = Arc::new(Bump::NormalMap { texture: Box::new(texture) });
+    triopts.bump(bump.clone());
+
+    let triangle = triopts.build();
+    let ray = Ray::new(Vec3 { x: 0.25, y: 0.25, z: -1.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    let hit = triangle.intersects(&ray, 0.0, 10.0).unwrap();
+    assert!((hit.n - Vec3 { x: 1.0, y: 0.0, z: 0.0 }).len() < 1e-9);
+
+    let mut triopts = TriangleOptions::new(
+        Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+        Vec3 { x: 1.0, y: 0.0, z: 0.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 });
+    triopts.texinfo([(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]);
+    triopts.bump(bump);
+    triopts.material(Box::new(FlatMaterial { color: Vec3 { x: 1.0, y: 0.0, z: 0.0 } }));
+
+    // Far more levels than could ever be split are clamped
+    let clamped = TriangleOptions::new(Vec3::zero(), Vec3 { x: 1.0, y: 0.0, z: 0.0 }, Vec3 { x: 0.0, y: 1.0, z: 0.0 })
+        .displaced(&CheckerTexture::black_and_white(4.0), 0.5, 40);
+    assert_eq!(clamped.len(), 1 << (2 * MAX_DISPLACEMENT_LEVELS));
+
+    let pieces = triopts.displaced(&CheckerTexture::black_and_white(4.0), 0.5, 2);
+    assert_eq!(pieces.len(), 16);
+
+    // Corners of the checker are white, raised half a unit
+    let corner = pieces[0].bounding_box();
+    assert!((corner.min.z - 0.5).abs() < 1e-9);
+
+    // Each piece keeps the material
+    let red = pieces.into_iter().next().unwrap().build();
+    let color = red.material.sample(Vec3::zero(), Vec3::zero(), Vec3::zero(), 0.0, 0.0);
+    assert_eq!(color, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+}
+
+#[test]
+fn it_transforms_uv_derivatives() {
+    let mut triopts = TriangleOptions::new(
+        Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+        Vec3 { x: 1.0, y: 0.0, z: 0.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 });
+    triopts.texinfo([(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]);
+
+    let mut triangle = triopts.build();
+    triangle.mut_transform(&Transform::new(Mat4::scale_matrix(&Vec3 { x: 2.0, y: 3.0, z: 1.0 })));
+
+    let ray = Ray::new(Vec3 { x: 0.5, y: 0.5, z: -1.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    let hit = triangle.intersects(&ray, 0.0, 10.0).unwrap();
+    assert!((hit.dpdu - Vec3 { x: 2.0, y: 0.0, z: 0.0 }).len() < 1e-9);
+    assert!((hit.dpdv - Vec3 { x: 0.0, y: 3.0, z: 0.0 }).len() < 1e-9);
+}
+
 #[test]
 fn it_intersects_only_in_tmin_tmax() {
     let mut triopts = TriangleOptions::new(
@@ -235,3 +446,45 @@ fn it_intersects_only_in_tmin_tmax() {
     non_intersection = triangle.intersects(&intersecting_ray, 0.0, 0.0001);
     assert!(non_intersection.is_none());
 }
+
+#[test]
+fn it_is_watertight() {
+    // A fan of thin triangles around a vertex at awkward coordinates
+    let center = Vec3 { x: 0.1, y: 0.7, z: 0.3 };
+    let rim = |i: u32| {
+        let angle = (i % 7) as f64 * 2.0 * ::std::f64::consts::PI / 7.0;
+        center + Vec3 { x: angle.cos() * 1.3, y: angle.sin() * 0.9, z: angle.sin() * 0.2 }
+    };
+    let fan: Vec<Triangle> = (0..7).map(|i| TriangleOptions::new(center, rim(i), rim(i + 1)).build()).collect();
+
+    // Rays through the shared vertex and along the shared edges hit at least
+    // one of the triangles meeting there
+    let origin = Vec3 { x: 0.3, y: -0.2, z: -5.0 };
+    for i in 0..7 {
+        for step in 0..100 {
+            let target = Vec3::lerp(&center, &rim(i), step as f64 / 100.0);
+            let ray = Ray::new(origin, (target - origin).unit());
+            assert!(fan.iter().any(|triangle| triangle.intersects(&ray, 0.0, 100.0).is_some()), "ray through edge {} at {} leaked", i, step);
+        }
+    }
+}
+
+#[test]
+fn it_lifts_smooth_shadow_origins() {
+    // A facet of a ball, with normals pointing out from its centre
+    let vertices = [Vec3 { x: 1.0, y: 0.0, z: 0.0 }, Vec3 { x: 0.0, y: 1.0, z: 0.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 }];
+    let mut triopts = TriangleOptions::new(vertices[0], vertices[1], vertices[2]);
+    triopts.normals(vertices);
+    let smooth = triopts.build();
+
+    let origin = Vec3 { x: 2.0, y: 2.0, z: 2.0 };
+    let ray = Ray::new(origin, (Vec3 { x: 1.0, y: 1.0, z: 1.0 }.scale(1.0 / 3.0) - origin).unit());
+    let hit = smooth.intersects(&ray, 0.0, 10.0).unwrap();
+    let lift = hit.shading_position - hit.position;
+    assert!(lift.dot(&hit.ng) > 0.0 && lift.len() < 1.0 - 1.0 / 3.0f64.sqrt());
+
+    // Flat facets are left alone
+    let flat = TriangleOptions::new(vertices[0], vertices[1], vertices[2]).build();
+    let hit = flat.intersects(&ray, 0.0, 10.0).unwrap();
+    assert!((hit.shading_position - hit.position).len() < 1e-12);
+}
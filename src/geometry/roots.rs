@@ -0,0 +1,143 @@
+//! Real roots of the polynomials that come up in ray-surface intersections
+
+/// Roots of ax^2 + bx + c in ascending order. A degenerate quadratic gives a
+/// single (repeated) root when linear, or None without any roots.
+pub fn quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let t = -c / b;
+        return Some((t, t));
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    // Avoids cancellation between -b and the square root
+    let q = if b < 0.0 {
+        -0.5 * (b - discriminant.sqrt())
+    } else {
+        -0.5 * (b + discriminant.sqrt())
+    };
+
+    let t0 = q / a;
+    let t1 = if q == 0.0 { t0 } else { c / q };
+
+    Some(if t0 < t1 { (t0, t1) } else { (t1, t0) })
+}
+
+/// Real roots in [lo, hi] of the polynomial with `coeffs`, highest power first,
+/// in ascending order. The roots of the derivative split the range into
+/// pieces on which the polynomial is monotonic, each holding at most one root.
+pub fn polynomial_in_range(coeffs: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+    let degree = coeffs.len() - 1;
+
+    if degree == 0 {
+        return Vec::new();
+    }
+
+    if degree == 1 {
+        let t = -coeffs[1] / coeffs[0];
+        return if t >= lo && t <= hi { vec![t] } else { Vec::new() };
+    }
+
+    let derivative: Vec<f64> = coeffs[..degree].iter().enumerate()
+        .map(|(i, c)| c * (degree - i) as f64)
+        .collect();
+
+    let mut bounds = vec![lo];
+    bounds.extend(polynomial_in_range(&derivative, lo, hi));
+    bounds.push(hi);
+
+    let mut roots: Vec<f64> = Vec::new();
+    for pair in bounds.windows(2) {
+        if let Some(root) = monotonic_root(coeffs, pair[0], pair[1]) {
+            // A root on the bound between two pieces, as a double root is, is
+            // found from both sides. The piece before has already reported it.
+            let repeated = roots.last().map_or(false, |&last| {
+                is_near(last, pair[0]) && is_near(root, pair[0])
+            });
+            if !repeated {
+                roots.push(root);
+            }
+        }
+    }
+
+    roots
+}
+
+/// Double roots are only found to around the square root of the precision
+fn is_near(x: f64, bound: f64) -> bool {
+    (x - bound).abs() <= 1e-6 * bound.abs().max(1.0)
+}
+
+fn evaluate(coeffs: &[f64], x: f64) -> f64 {
+    coeffs.iter().fold(0.0, |acc, c| acc * x + c)
+}
+
+/// Bisection on a piece where the polynomial is monotonic
+fn monotonic_root(coeffs: &[f64], lo: f64, hi: f64) -> Option<f64> {
+    let f_lo = evaluate(coeffs, lo);
+    let f_hi = evaluate(coeffs, hi);
+
+    if f_lo == 0.0 {
+        return Some(lo);
+    }
+    if f_lo.signum() == f_hi.signum() {
+        return None;
+    }
+
+    let (mut lo, mut hi) = (lo, hi);
+    for _ in 0..64 {
+        let mid = 0.5 * (lo + hi);
+        if mid <= lo || mid >= hi {
+            break;
+        }
+
+        if evaluate(coeffs, mid).signum() == f_lo.signum() {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    Some(0.5 * (lo + hi))
+}
+
+#[test]
+fn it_solves_quadratics() {
+    assert_eq!(quadratic(1.0, -3.0, 2.0), Some((1.0, 2.0)));
+    assert_eq!(quadratic(0.0, 2.0, -4.0), Some((2.0, 2.0)));
+    assert_eq!(quadratic(1.0, 0.0, 1.0), None);
+}
+
+#[test]
+fn it_finds_polynomial_roots_in_range() {
+    // (x - 1)(x - 2)(x - 3)(x - 4)
+    let coeffs = [1.0, -10.0, 35.0, -50.0, 24.0];
+    let roots = polynomial_in_range(&coeffs, 0.0, 10.0);
+    assert_eq!(roots.len(), 4);
+    for (root, expected) in roots.iter().zip([1.0, 2.0, 3.0, 4.0].iter()) {
+        assert!((root - expected).abs() < 1e-9);
+    }
+
+    assert_eq!(polynomial_in_range(&coeffs, 1.5, 2.5).len(), 1);
+    assert!(polynomial_in_range(&[1.0, 0.0, 0.0, 0.0, 1.0], -10.0, 10.0).is_empty());
+}
+
+#[test]
+fn it_finds_double_roots_once() {
+    // (x - 1)^2 (x - 3): the double root is also where the derivative is zero,
+    // the bound between two monotonic pieces
+    let roots = polynomial_in_range(&[1.0, -5.0, 7.0, -3.0], 0.0, 5.0);
+    assert_eq!(roots.len(), 2, "{:?}", roots);
+    assert!((roots[0] - 1.0).abs() < 1e-6);
+    assert!((roots[1] - 3.0).abs() < 1e-9);
+
+    // (x - 1)^2 (x + 1)^2, touching zero twice
+    let roots = polynomial_in_range(&[1.0, 0.0, -2.0, 0.0, 1.0], -2.0, 2.0);
+    assert_eq!(roots.len(), 2, "{:?}", roots);
+}
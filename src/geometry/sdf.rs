@@ -0,0 +1,197 @@
+//! Signed distance functions: negative inside a shape, positive outside and
+//! never more than the true distance to the surface, so a ray can always
+//! step forward by the distance without passing through anything.
+
+use geometry::bbox::{self, BBox};
+use geometry::prims::CsgOp;
+use vec3::Vec3;
+
+pub trait Sdf {
+    fn distance(&self, p: &Vec3) -> f64;
+
+    /// Everywhere the distance can be negative
+    fn bounds(&self) -> BBox;
+}
+
+pub struct SdfSphere {
+    pub center: Vec3,
+    pub radius: f64
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: &Vec3) -> f64 {
+        (*p - self.center).len() - self.radius
+    }
+
+    fn bounds(&self) -> BBox {
+        let r = Vec3 { x: self.radius, y: self.radius, z: self.radius };
+        BBox { min: self.center - r, max: self.center + r }
+    }
+}
+
+/// An axis-aligned box with its edges rounded off by `rounding`, which is
+/// taken out of the half extents
+pub struct SdfBox {
+    pub center: Vec3,
+    pub half_extents: Vec3,
+    pub rounding: f64
+}
+
+impl Sdf for SdfBox {
+    fn distance(&self, p: &Vec3) -> f64 {
+        let local = *p - self.center;
+        let q = Vec3 {
+            x: local.x.abs() - self.half_extents.x + self.rounding,
+            y: local.y.abs() - self.half_extents.y + self.rounding,
+            z: local.z.abs() - self.half_extents.z + self.rounding
+        };
+        let outside = Vec3 { x: q.x.max(0.0), y: q.y.max(0.0), z: q.z.max(0.0) };
+
+        outside.len() + q.x.max(q.y).max(q.z).min(0.0) - self.rounding
+    }
+
+    fn bounds(&self) -> BBox {
+        BBox { min: self.center - self.half_extents, max: self.center + self.half_extents }
+    }
+}
+
+/// A ring around the y axis through `center`
+pub struct SdfTorus {
+    pub center: Vec3,
+    pub major_radius: f64,
+    pub minor_radius: f64
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: &Vec3) -> f64 {
+        let local = *p - self.center;
+        let ring_distance = (local.x * local.x + local.z * local.z).sqrt() - self.major_radius;
+
+        (ring_distance * ring_distance + local.y * local.y).sqrt() - self.minor_radius
+    }
+
+    fn bounds(&self) -> BBox {
+        let outer = self.major_radius + self.minor_radius;
+        let r = Vec3 { x: outer, y: self.minor_radius, z: outer };
+        BBox { min: self.center - r, max: self.center + r }
+    }
+}
+
+/// A line segment thickened by `radius`
+pub struct SdfCapsule {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub radius: f64
+}
+
+impl Sdf for SdfCapsule {
+    fn distance(&self, p: &Vec3) -> f64 {
+        let ab = self.b - self.a;
+        let ap = *p - self.a;
+        let h = (ap.dot(&ab) / ab.dot(&ab)).max(0.0).min(1.0);
+
+        (ap - ab.scale(h)).len() - self.radius
+    }
+
+    fn bounds(&self) -> BBox {
+        bbox::union_points(&self.a, &self.b).expand(self.radius)
+    }
+}
+
+/// Two distance functions combined as in CSG. A non-zero `smoothness` rounds
+/// the seams, blending the shapes over about that distance.
+pub struct SdfBlend {
+    pub op: CsgOp,
+    pub smoothness: f64,
+    pub left: Box<Sdf+Send+Sync>,
+    pub right: Box<Sdf+Send+Sync>
+}
+
+impl SdfBlend {
+    pub fn new(op: CsgOp, smoothness: f64, left: Box<Sdf+Send+Sync>, right: Box<Sdf+Send+Sync>) -> SdfBlend {
+        SdfBlend {
+            op: op,
+            smoothness: smoothness,
+            left: left,
+            right: right
+        }
+    }
+}
+
+/// Polynomial smooth minimum, which stays within k / 4 of `a.min(b)`
+pub fn smooth_min(a: f64, b: f64, k: f64) -> f64 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+
+    let h = (k - (a - b).abs()).max(0.0) / k;
+    a.min(b) - h * h * k * 0.25
+}
+
+pub fn smooth_max(a: f64, b: f64, k: f64) -> f64 {
+    -smooth_min(-a, -b, k)
+}
+
+impl Sdf for SdfBlend {
+    fn distance(&self, p: &Vec3) -> f64 {
+        let a = self.left.distance(p);
+        let b = self.right.distance(p);
+
+        match self.op {
+            CsgOp::Union => smooth_min(a, b, self.smoothness),
+            CsgOp::Intersection => smooth_max(a, b, self.smoothness),
+            CsgOp::Difference => smooth_max(a, -b, self.smoothness)
+        }
+    }
+
+    fn bounds(&self) -> BBox {
+        let left = self.left.bounds();
+        let right = self.right.bounds();
+
+        match self.op {
+            // The blend fills in the seam a little beyond either shape
+            CsgOp::Union => bbox::union_bbox(&left, &right).expand(self.smoothness * 0.25),
+            CsgOp::Intersection => BBox {
+                min: Vec3 { x: left.min.x.max(right.min.x), y: left.min.y.max(right.min.y), z: left.min.z.max(right.min.z) },
+                max: Vec3 { x: left.max.x.min(right.max.x), y: left.max.y.min(right.max.y), z: left.max.z.min(right.max.z) }
+            },
+            CsgOp::Difference => left
+        }
+    }
+}
+
+#[test]
+fn it_measures_primitives() {
+    let sphere = SdfSphere { center: Vec3 { x: 1.0, y: 0.0, z: 0.0 }, radius: 1.0 };
+    assert!((sphere.distance(&Vec3 { x: 4.0, y: 0.0, z: 0.0 }) - 2.0).abs() < 1e-9);
+    assert!((sphere.distance(&Vec3 { x: 1.0, y: 0.0, z: 0.0 }) + 1.0).abs() < 1e-9);
+
+    let rounded = SdfBox { center: Vec3::zero(), half_extents: Vec3 { x: 1.0, y: 1.0, z: 1.0 }, rounding: 0.25 };
+    assert!((rounded.distance(&Vec3 { x: 3.0, y: 0.0, z: 0.0 }) - 2.0).abs() < 1e-9);
+    // The corner is cut back by the rounding
+    let corner = 0.75 + 0.25 / 3.0f64.sqrt();
+    assert!(rounded.distance(&Vec3 { x: corner, y: corner, z: corner }).abs() < 1e-9);
+
+    let torus = SdfTorus { center: Vec3::zero(), major_radius: 2.0, minor_radius: 0.5 };
+    assert!((torus.distance(&Vec3::zero()) - 1.5).abs() < 1e-9);
+
+    let capsule = SdfCapsule { a: Vec3::zero(), b: Vec3 { x: 0.0, y: 2.0, z: 0.0 }, radius: 0.5 };
+    assert!((capsule.distance(&Vec3 { x: 0.0, y: 3.0, z: 0.0 }) - 0.5).abs() < 1e-9);
+}
+
+#[test]
+fn it_blends_smoothly() {
+    let left = Box::new(SdfSphere { center: Vec3 { x: -1.0, y: 0.0, z: 0.0 }, radius: 1.0 });
+    let right = Box::new(SdfSphere { center: Vec3 { x: 1.0, y: 0.0, z: 0.0 }, radius: 1.0 });
+    let blend = SdfBlend::new(CsgOp::Union, 0.5, left, right);
+
+    // The spheres just touch at the origin; the blend fills in around it
+    let above = Vec3 { x: 0.0, y: 0.1, z: 0.0 };
+    let hard = (Vec3 { x: 1.0, y: 0.1, z: 0.0 }).len() - 1.0;
+    assert!(blend.distance(&above) < hard);
+    assert!(blend.distance(&above) >= hard - 0.5 * 0.25);
+
+    // Far away the blend has no effect
+    assert!((blend.distance(&Vec3 { x: 5.0, y: 0.0, z: 0.0 }) - 3.0).abs() < 1e-9);
+    assert!((smooth_min(1.0, 2.0, 0.0) - 1.0).abs() < 1e-9);
+}
@@ -0,0 +1,416 @@
+//! Loop and Catmull-Clark subdivision of polygon meshes. Edges between faces
+//! in different smoothing groups, and edges on the boundary, are creases:
+//! they stay sharp through every level, as do vertices where more than two
+//! creases meet.
+
+use std::collections::HashMap;
+use geometry::prims::TriangleOptions;
+use vec3::Vec3;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SubdivisionScheme {
+    /// Splits triangles into four; polygons are triangulated first
+    Loop,
+    /// Splits an n-sided polygon into n quads
+    CatmullClark
+}
+
+#[derive(Clone)]
+pub struct PolyFace {
+    /// All different, so no edge of the face is degenerate
+    pub vertices: Vec<usize>,
+    /// One per vertex
+    pub tex_coords: Vec<(f64, f64)>,
+    /// OBJ smoothing group, with 0 for `s off`: those faces are faceted,
+    /// creased against every neighbour
+    pub smoothing_group: u32
+}
+
+/// A polygon mesh with shared vertices, as needed to find neighbours
+#[derive(Clone)]
+pub struct PolyMesh {
+    pub positions: Vec<Vec3>,
+    pub faces: Vec<PolyFace>
+}
+
+/// The faces on either side of each edge, keyed by its vertices lowest first
+struct Edges {
+    index: HashMap<(usize, usize), usize>,
+    faces: Vec<Vec<usize>>,
+    ends: Vec<(usize, usize)>
+}
+
+impl Edges {
+    fn key(a: usize, b: usize) -> (usize, usize) {
+        if a < b { (a, b) } else { (b, a) }
+    }
+
+    fn of(&self, a: usize, b: usize) -> usize {
+        self.index[&Edges::key(a, b)]
+    }
+}
+
+fn midpoint(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    ((a.0 + b.0) * 0.5, (a.1 + b.1) * 0.5)
+}
+
+fn average(points: &[Vec3]) -> Vec3 {
+    points.iter().fold(Vec3::zero(), |sum, &p| sum + p).scale(1.0 / points.len() as f64)
+}
+
+impl PolyMesh {
+    pub fn subdivide(&self, scheme: SubdivisionScheme, levels: u32) -> PolyMesh {
+        let mut mesh = match scheme {
+            SubdivisionScheme::Loop => self.triangulated(),
+            SubdivisionScheme::CatmullClark => self.clone()
+        };
+
+        for _ in 0..levels {
+            mesh = match scheme {
+                SubdivisionScheme::Loop => mesh.loop_step(),
+                SubdivisionScheme::CatmullClark => mesh.catmull_clark_step()
+            };
+        }
+
+        mesh
+    }
+
+    /// Fans each polygon out from its first vertex
+    pub fn triangulated(&self) -> PolyMesh {
+        let mut faces = Vec::new();
+
+        for face in &self.faces {
+            for i in 1..face.vertices.len() - 1 {
+                faces.push(PolyFace {
+                    vertices: vec![face.vertices[0], face.vertices[i], face.vertices[i + 1]],
+                    tex_coords: vec![face.tex_coords[0], face.tex_coords[i], face.tex_coords[i + 1]],
+                    smoothing_group: face.smoothing_group
+                });
+            }
+        }
+
+        PolyMesh { positions: self.positions.clone(), faces: faces }
+    }
+
+    /// Triangles with normals averaged across faces sharing each vertex in
+    /// the same smoothing group. Faces in group 0 keep their own normal.
+    pub fn triangles(&self, flip_normals: bool) -> Vec<TriangleOptions> {
+        let mut normals: HashMap<(usize, u32), Vec3> = HashMap::new();
+        for face in self.faces.iter().filter(|face| face.smoothing_group != 0) {
+            // Unnormalised, so larger faces count for more
+            let n = self.face_normal(face);
+            for &v in &face.vertices {
+                let sum = normals.entry((v, face.smoothing_group)).or_insert_with(Vec3::zero);
+                *sum = *sum + n;
+            }
+        }
+
+        let normal_scale = if flip_normals { -1.0 } else { 1.0 };
+
+        self.triangulated().faces.iter().map(|face| {
+            let (a, b, c) = (face.vertices[0], face.vertices[1], face.vertices[2]);
+            let flat = self.face_normal(face);
+            let corner_normal = |v: usize| match face.smoothing_group {
+                0 => flat.unit().scale(normal_scale),
+                group => normals[&(v, group)].unit().scale(normal_scale)
+            };
+
+            let mut triopts = TriangleOptions::new(self.positions[a], self.positions[b], self.positions[c]);
+            triopts.normals([corner_normal(a), corner_normal(b), corner_normal(c)]);
+            triopts.texinfo([face.tex_coords[0], face.tex_coords[1], face.tex_coords[2]]);
+            triopts
+        }).collect()
+    }
+
+    fn face_normal(&self, face: &PolyFace) -> Vec3 {
+        let origin = self.positions[face.vertices[0]];
+        (1..face.vertices.len() - 1).fold(Vec3::zero(), |n, i| {
+            let a = self.positions[face.vertices[i]] - origin;
+            let b = self.positions[face.vertices[i + 1]] - origin;
+            n + a.cross(&b)
+        })
+    }
+
+    fn edges(&self) -> Edges {
+        let mut edges = Edges { index: HashMap::new(), faces: Vec::new(), ends: Vec::new() };
+
+        for (f, face) in self.faces.iter().enumerate() {
+            let count = face.vertices.len();
+            for i in 0..count {
+                let key = Edges::key(face.vertices[i], face.vertices[(i + 1) % count]);
+                let next_index = edges.ends.len();
+                let index = *edges.index.entry(key).or_insert(next_index);
+                if index == next_index {
+                    edges.faces.push(Vec::new());
+                    edges.ends.push(key);
+                }
+                edges.faces[index].push(f);
+            }
+        }
+
+        edges
+    }
+
+    fn is_crease(&self, faces: &[usize]) -> bool {
+        if faces.len() != 2 {
+            return true;
+        }
+
+        let (a, b) = (self.faces[faces[0]].smoothing_group, self.faces[faces[1]].smoothing_group);
+        a == 0 || a != b
+    }
+
+    /// The other ends of every edge at each vertex, and of the creases only
+    fn neighbours(&self, edges: &Edges) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+        let mut all = vec![Vec::new(); self.positions.len()];
+        let mut creases = vec![Vec::new(); self.positions.len()];
+
+        for (e, &(a, b)) in edges.ends.iter().enumerate() {
+            all[a].push(b);
+            all[b].push(a);
+            if self.is_crease(&edges.faces[e]) {
+                creases[a].push(b);
+                creases[b].push(a);
+            }
+        }
+
+        (all, creases)
+    }
+
+    /// Vertices on a crease slide along it; corners where more than two
+    /// creases meet stay put. `None` for vertices that follow the smooth rule.
+    fn crease_vertex(&self, v: usize, creases: &[usize]) -> Option<Vec3> {
+        let p = self.positions[v];
+        match creases.len() {
+            0 | 1 => None,
+            2 => Some(p.scale(0.75) + (self.positions[creases[0]] + self.positions[creases[1]]).scale(0.125)),
+            _ => Some(p)
+        }
+    }
+
+    fn catmull_clark_step(&self) -> PolyMesh {
+        let edges = self.edges();
+        let (neighbours, creases) = self.neighbours(&edges);
+        let edge_base = self.positions.len();
+        let face_base = edge_base + edges.ends.len();
+
+        let face_points: Vec<Vec3> = self.faces.iter().map(|face| {
+            average(&face.vertices.iter().map(|&v| self.positions[v]).collect::<Vec<_>>())
+        }).collect();
+
+        let edge_points: Vec<Vec3> = edges.ends.iter().zip(edges.faces.iter()).map(|(&(a, b), faces)| {
+            let ends = [self.positions[a], self.positions[b]];
+            if self.is_crease(faces) {
+                average(&ends)
+            } else {
+                average(&[ends[0], ends[1], face_points[faces[0]], face_points[faces[1]]])
+            }
+        }).collect();
+
+        let mut vertex_faces = vec![Vec::new(); self.positions.len()];
+        for (f, face) in self.faces.iter().enumerate() {
+            for &v in &face.vertices {
+                vertex_faces[v].push(face_points[f]);
+            }
+        }
+
+        let vertex_points: Vec<Vec3> = (0..self.positions.len()).map(|v| {
+            let p = self.positions[v];
+            if neighbours[v].is_empty() {
+                return p;
+            }
+
+            self.crease_vertex(v, &creases[v]).unwrap_or_else(|| {
+                let n = neighbours[v].len() as f64;
+                let f = average(&vertex_faces[v]);
+                let r = average(&neighbours[v].iter().map(|&w| (p + self.positions[w]).scale(0.5)).collect::<Vec<_>>());
+                (f + r.scale(2.0) + p.scale(n - 3.0)).scale(1.0 / n)
+            })
+        }).collect();
+
+        let mut faces = Vec::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            let count = face.vertices.len();
+            let center_uv = face.tex_coords.iter().fold((0.0, 0.0), |sum, uv| (sum.0 + uv.0, sum.1 + uv.1));
+            let center_uv = (center_uv.0 / count as f64, center_uv.1 / count as f64);
+
+            for i in 0..count {
+                let prev = (i + count - 1) % count;
+                let next = (i + 1) % count;
+                let (v, v_prev, v_next) = (face.vertices[i], face.vertices[prev], face.vertices[next]);
+
+                faces.push(PolyFace {
+                    vertices: vec![v, edge_base + edges.of(v, v_next), face_base + f, edge_base + edges.of(v_prev, v)],
+                    tex_coords: vec![
+                        face.tex_coords[i],
+                        midpoint(face.tex_coords[i], face.tex_coords[next]),
+                        center_uv,
+                        midpoint(face.tex_coords[prev], face.tex_coords[i])
+                    ],
+                    smoothing_group: face.smoothing_group
+                });
+            }
+        }
+
+        let mut positions = vertex_points;
+        positions.extend(edge_points);
+        positions.extend(face_points);
+
+        PolyMesh { positions: positions, faces: faces }
+    }
+
+    /// Expects triangles only
+    fn loop_step(&self) -> PolyMesh {
+        let edges = self.edges();
+        let (neighbours, creases) = self.neighbours(&edges);
+        let edge_base = self.positions.len();
+
+        // Faces never repeat a vertex, so each triangle has one left over
+        let opposite = |f: usize, (a, b): (usize, usize)| {
+            self.faces[f].vertices.iter().cloned().find(|&v| v != a && v != b).unwrap_or(a)
+        };
+
+        let edge_points: Vec<Vec3> = edges.ends.iter().zip(edges.faces.iter()).map(|(&(a, b), faces)| {
+            let ends = self.positions[a] + self.positions[b];
+            if self.is_crease(faces) {
+                ends.scale(0.5)
+            } else {
+                let far = self.positions[opposite(faces[0], (a, b))] + self.positions[opposite(faces[1], (a, b))];
+                ends.scale(0.375) + far.scale(0.125)
+            }
+        }).collect();
+
+        let vertex_points: Vec<Vec3> = (0..self.positions.len()).map(|v| {
+            let p = self.positions[v];
+            if neighbours[v].is_empty() {
+                return p;
+            }
+
+            self.crease_vertex(v, &creases[v]).unwrap_or_else(|| {
+                let n = neighbours[v].len() as f64;
+                let beta = if neighbours[v].len() == 3 { 3.0 / 16.0 } else { 3.0 / (8.0 * n) };
+                let sum = neighbours[v].iter().fold(Vec3::zero(), |sum, &w| sum + self.positions[w]);
+                p.scale(1.0 - n * beta) + sum.scale(beta)
+            })
+        }).collect();
+
+        let mut faces = Vec::new();
+        for face in &self.faces {
+            let v = &face.vertices;
+            let uv = &face.tex_coords;
+            let e = [edge_base + edges.of(v[0], v[1]), edge_base + edges.of(v[1], v[2]), edge_base + edges.of(v[2], v[0])];
+            let e_uv = [midpoint(uv[0], uv[1]), midpoint(uv[1], uv[2]), midpoint(uv[2], uv[0])];
+
+            for i in 0..3 {
+                let prev = (i + 2) % 3;
+                faces.push(PolyFace {
+                    vertices: vec![v[i], e[i], e[prev]],
+                    tex_coords: vec![uv[i], e_uv[i], e_uv[prev]],
+                    smoothing_group: face.smoothing_group
+                });
+            }
+            faces.push(PolyFace {
+                vertices: e.to_vec(),
+                tex_coords: e_uv.to_vec(),
+                smoothing_group: face.smoothing_group
+            });
+        }
+
+        let mut positions = vertex_points;
+        positions.extend(edge_points);
+
+        PolyMesh { positions: positions, faces: faces }
+    }
+}
+
+#[cfg(test)]
+use geometry::prim::Prim;
+#[cfg(test)]
+use raytracer::Ray;
+
+#[cfg(test)]
+fn cube(separate_groups: bool) -> PolyMesh {
+    let mut positions = Vec::new();
+    for &x in [-1.0, 1.0].iter() {
+        for &y in [-1.0, 1.0].iter() {
+            for &z in [-1.0, 1.0].iter() {
+                positions.push(Vec3 { x: x, y: y, z: z });
+            }
+        }
+    }
+
+    // Vertex index bits are x, y, z from high to low; all wound outwards
+    let quads = [[0, 1, 3, 2], [4, 6, 7, 5], [0, 4, 5, 1], [2, 3, 7, 6], [0, 2, 6, 4], [1, 5, 7, 3]];
+    let faces = quads.iter().enumerate().map(|(i, quad)| PolyFace {
+        vertices: quad.to_vec(),
+        tex_coords: vec![(0.0, 0.0); 4],
+        smoothing_group: if separate_groups { i as u32 + 1 } else { 1 }
+    }).collect();
+
+    PolyMesh { positions: positions, faces: faces }
+}
+
+#[test]
+fn it_rounds_a_cube_with_catmull_clark() {
+    let smooth = cube(false).subdivide(SubdivisionScheme::CatmullClark, 1);
+    assert_eq!(smooth.faces.len(), 24);
+    assert_eq!(smooth.positions.len(), 8 + 12 + 6);
+
+    // Corners pull in to (F + 2R) / 3 with three faces around them
+    let corner = smooth.positions[7];
+    assert!((corner - Vec3 { x: 5.0 / 9.0, y: 5.0 / 9.0, z: 5.0 / 9.0 }).len() < 1e-9);
+
+    // Every face in its own smoothing group keeps the cube sharp
+    let sharp = cube(true).subdivide(SubdivisionScheme::CatmullClark, 2);
+    assert_eq!(sharp.faces.len(), 96);
+    assert!(sharp.positions.iter().all(|p| p.x.abs().max(p.y.abs()).max(p.z.abs()) > 1.0 - 1e-9));
+    assert!((sharp.positions[7] - Vec3::one()).len() < 1e-9);
+
+    // Normals stay flat across creases
+    let triangles = cube(true).triangles(false);
+    assert_eq!(triangles.len(), 12);
+}
+
+#[test]
+fn it_splits_triangles_with_loop() {
+    let triangle = PolyMesh {
+        positions: vec![Vec3::zero(), Vec3 { x: 1.0, y: 0.0, z: 0.0 }, Vec3 { x: 0.0, y: 1.0, z: 0.0 }],
+        faces: vec![PolyFace { vertices: vec![0, 1, 2], tex_coords: vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)], smoothing_group: 0 }]
+    };
+
+    let split = triangle.subdivide(SubdivisionScheme::Loop, 1);
+    assert_eq!(split.faces.len(), 4);
+    assert_eq!(split.positions.len(), 6);
+
+    // All edges are on the boundary, so the corners slide along them
+    assert!((split.positions[0] - Vec3 { x: 0.125, y: 0.125, z: 0.0 }).len() < 1e-9);
+    // And edge points are midpoints, with matching texture coordinates
+    assert!((split.positions[3] - Vec3 { x: 0.5, y: 0.0, z: 0.0 }).len() < 1e-9);
+    assert_eq!(split.faces[0].tex_coords[1], (0.5, 0.0));
+
+    let smooth = cube(false).subdivide(SubdivisionScheme::Loop, 1);
+    assert_eq!(smooth.faces.len(), 48);
+}
+
+#[test]
+fn it_keeps_smoothing_group_zero_faceted() {
+    let mut faceted = cube(false);
+    for face in faceted.faces.iter_mut() {
+        face.smoothing_group = 0;
+    }
+
+    // Every edge is a crease, so the cube keeps its corners
+    let sharp = faceted.subdivide(SubdivisionScheme::CatmullClark, 1);
+    assert!((sharp.positions[7] - Vec3::one()).len() < 1e-9);
+
+    // And shades with the face normal rather than one bent towards the corner
+    let ray = Ray::new(Vec3::zero(), Vec3 { x: 1.0, y: 0.8, z: 0.7 });
+    let shading_normal = |mesh: &PolyMesh| {
+        let triangles: Vec<_> = mesh.triangles(false).into_iter().map(|triopts| triopts.build()).collect();
+        let hit = triangles.iter().filter_map(|tri| tri.intersects(&ray, 0.0, 10.0)).next().unwrap();
+        hit.n.dot(&hit.ng).abs()
+    };
+    assert!(shading_normal(&faceted) > 1.0 - 1e-9);
+    assert!(shading_normal(&cube(false)) < 0.9);
+}
@@ -0,0 +1,29 @@
+use geometry::Emitter;
+use light::light::Light;
+use vec3::Vec3;
+
+/// An area light shaped like a disk, box, cylinder, cone or torus. Like the
+/// sphere light it is not visible itself; add a matching prim for that.
+#[allow(dead_code)]
+pub struct ShapeLight<S> {
+    pub shape: S,
+    pub color: Vec3
+}
+
+impl<S: Emitter> Light for ShapeLight<S> {
+    fn position(&self) -> Vec3 {
+        self.shape.sample_surface()
+    }
+
+    fn color(&self) -> Vec3 {
+        self.color
+    }
+
+    fn center(&self) -> Vec3 {
+        self.shape.center()
+    }
+
+    fn is_point(&self) -> bool {
+        false
+    }
+}
@@ -3,8 +3,10 @@ pub mod light;
 
 pub mod lights {
     pub use self::pointlight::PointLight;
+    pub use self::shapelight::ShapeLight;
     pub use self::spherelight::SphereLight;
 
     mod pointlight;
+    mod shapelight;
     mod spherelight;
 }
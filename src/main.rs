@@ -45,7 +45,16 @@ struct SceneConfig {
     animating: bool,
     fps: f64,
     time_slice: (f64, f64),
-    starting_frame_number: u32
+    starting_frame_number: u32,
+    projection: Option<scene::Projection>,
+    shutter: Option<(f64, f64)>,
+    stereo: Option<raytracer::Stereo>,
+    exposure: Option<scene::Exposure>,
+    fov_axis: Option<scene::FovAxis>,
+    roll: Option<f64>,
+    aspect_ratio: Option<f64>,
+    accelerator: Option<raytracer::AcceleratorKind>,
+    cache_accelerator: Option<bool>
 }
 
 fn parse_args(args: env::Args) -> Result<ProgramArgs, String> {
@@ -100,6 +109,13 @@ fn main() {
         }
     };
 
+    if let Some(ref stereo) = config.stereo {
+        if let Err(err) = stereo.validate() {
+            write!(&mut io::stderr(), "{}\n", err).unwrap();
+            process::exit(1)
+        }
+    }
+
     println!("Job started at {}...\nLoading scene...", start_time);
 
     let scene_config = match my_scene::scene_by_name(&config.name) {
@@ -114,14 +130,48 @@ fn main() {
     let fov = config.fov;
 
     // Hackish solution for animator
-    let shared_scene = Arc::new(scene_config.get_scene());
+    let accelerator = raytracer::AcceleratorOptions {
+        kind: config.accelerator.unwrap_or(scene_config.default_accelerator()),
+        cache: config.cache_accelerator.unwrap_or(false)
+    };
+    // Animated scenes are built by the animator for each frame instead
+    let animation = if config.animating { scene_config.get_animation(accelerator) } else { None };
+    let shared_scene = match animation {
+        Some(_) => None,
+        None => Some(Arc::new(scene_config.get_scene(accelerator)))
+    };
 
-    let camera = if config.animating {
+    let mut camera = if config.animating {
         scene_config.get_animation_camera(image_width, image_height, fov)
     } else {
         scene_config.get_camera(image_width, image_height, fov)
     };
 
+    if let Some(fov_axis) = config.fov_axis {
+        camera.set_fov_axis(fov_axis);
+    }
+
+    if let Some(aspect_ratio) = config.aspect_ratio {
+        camera.set_aspect_ratio(aspect_ratio);
+    }
+
+    if let Some(roll) = config.roll {
+        camera.set_roll(roll);
+    }
+
+    if let Some(projection) = config.projection {
+        camera.projection = projection;
+    }
+
+    if let Some((shutter_open, shutter_close)) = config.shutter {
+        camera.shutter_open = shutter_open;
+        camera.shutter_close = shutter_close;
+    }
+
+    if let Some(exposure) = config.exposure {
+        camera.exposure = Some(exposure);
+    }
+
     let scene_time = ::time::get_time().sec;
     println!("Scene loaded at {} ({}s)...", scene_time, scene_time - start_time);
 
@@ -146,26 +196,41 @@ fn main() {
             animate_from: animate_from,
             animate_to: animate_to,
             starting_frame_number: config.starting_frame_number,
-            renderer: renderer
+            renderer: renderer,
+            stereo: config.stereo
         };
 
         println!("Animating - tasks: {}, FPS: {}, start: {}s, end:{}s, starting frame: {}",
                  ::num_cpus::get(), animator.fps, animator.animate_from, animator.animate_to,
                  animator.starting_frame_number);
-        animator.animate(camera, shared_scene, &config.output_file);
+        match animation {
+            Some(animation) => {
+                animator.animate(camera, &|time| Arc::new(animation.scene_at_time(time)), &config.output_file)
+            },
+            None => {
+                let shared_scene = shared_scene.unwrap();
+                animator.animate(camera, &|_| Arc::clone(&shared_scene), &config.output_file)
+            }
+        }
         let render_time = ::time::get_time().sec;
         println!("Render done at {} ({}s)",
                  render_time, render_time - scene_time);
     } else {
         // Still frame
         println!("Rendering with {} tasks...", ::num_cpus::get());
-        let image_data = renderer.render(camera, shared_scene);
+        let shared_scene = shared_scene.unwrap();
+        let images = match config.stereo {
+            Some(ref stereo) => stereo.render(&renderer, camera, shared_scene),
+            None => vec![("", renderer.render(camera, shared_scene))]
+        };
         let render_time = ::time::get_time().sec;
         println!("Render done at {} ({}s)...\nWriting file...",
                  render_time, render_time - scene_time);
 
-        let out_file = format!("{}{}", config.output_file, ".ppm");
-        util::export::to_ppm(&image_data, &out_file).expect("ppm write failure");
+        for &(ref suffix, ref image_data) in &images {
+            let out_file = format!("{}{}{}", config.output_file, suffix, ".ppm");
+            util::export::to_ppm(image_data, &out_file).expect("ppm write failure");
+        }
         let export_time = ::time::get_time().sec;
 
         println!("Write done: {} ({}s). Written to {}\nTotal: {}s",
@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use geometry::bbox::BBox;
+use geometry::bbox::{union_point, union_points, BBox};
 use raytracer::Ray;
 use std::cmp;
 use std::f64;
@@ -17,6 +17,7 @@ pub struct Mat4 {
 }
 
 /// We store the inverse matrix for convenience as per pbrt's recommendation
+#[derive(Clone, Copy)]
 pub struct Transform {
     pub m: Mat4,
     pub inv: Mat4
@@ -29,6 +30,21 @@ impl Transform {
             inv: mat.inverse()
         }
     }
+
+    pub fn identity() -> Transform {
+        Transform {
+            m: Mat4::identity(),
+            inv: Mat4::identity()
+        }
+    }
+
+    /// `inner` applied first, then `outer`, without re-inverting
+    pub fn compose(outer: &Transform, inner: &Transform) -> Transform {
+        Transform {
+            m: Mat4::mult_m(&outer.m, &inner.m),
+            inv: Mat4::mult_m(&inner.inv, &outer.inv)
+        }
+    }
 }
 
 fn are_equal_rel(a: f64, b: f64) -> bool {
@@ -194,6 +210,26 @@ impl Mat4 {
         Mat4 { m: m }
     }
 
+    /// A rotation taking the y axis onto `axis`. The x and z axes end up at
+    /// an arbitrary but fixed turn around it.
+    pub fn align_y_matrix(axis: &Vec3) -> Mat4 {
+        let y = axis.unit();
+        let helper = if y.x.abs() < 0.9 {
+            Vec3 { x: 1.0, y: 0.0, z: 0.0 }
+        } else {
+            Vec3 { x: 0.0, y: 1.0, z: 0.0 }
+        };
+        let z = helper.cross(&y).unit();
+        let x = y.cross(&z);
+
+        Mat4::new(
+            x.x, y.x, z.x, 0.0,
+            x.y, y.y, z.y, 0.0,
+            x.z, y.z, z.z, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        )
+    }
+
     /// This matrix translates between world-space and camera-space
     pub fn look_at_matrix(pos: &Vec3, up: &Vec3, look_at: &Vec3) -> Mat4 {
         let dir = (*look_at - *pos).unit();
@@ -228,12 +264,45 @@ impl Mat4 {
         }
     }
 
-    pub fn transform_ray(_r: &Ray) -> Ray {
-        panic!("Ray transform not implemented");
+    /// Like `transform_normal` with the inverse already at hand. The result is unit length.
+    pub fn transform_normal_inv(n: &Vec3, inv: &Mat4) -> Vec3 {
+        Mat4::mult_v(&inv.transpose(), n).unit()
     }
 
-    pub fn transform_bbox(_bbox: &BBox) -> BBox {
-        panic!("BBox transform not implemented");
+    /// The scale factor of a matrix made up only of rotations, translations and
+    /// uniform scaling, or None if it stretches or shears
+    pub fn similarity_scale(&self) -> Option<f64> {
+        let x = Mat4::mult_v(self, &Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+        let y = Mat4::mult_v(self, &Vec3 { x: 0.0, y: 1.0, z: 0.0 });
+        let z = Mat4::mult_v(self, &Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+        let scale = x.len();
+        let tolerance = 1e-9 * scale * scale;
+
+        if (y.len() - scale).abs() <= 1e-9 * scale && (z.len() - scale).abs() <= 1e-9 * scale &&
+           x.dot(&y).abs() <= tolerance && y.dot(&z).abs() <= tolerance && z.dot(&x).abs() <= tolerance {
+            Some(scale)
+        } else {
+            None
+        }
+    }
+
+    /// The direction is not normalised, so distances along the new ray are
+    /// in units of the original one
+    pub fn transform_ray(r: &Ray, transform: &Mat4) -> Ray {
+        Ray::new_at_time(
+            Mat4::mult_p(transform, &r.origin),
+            Mat4::mult_v(transform, &r.direction),
+            r.time
+        )
+    }
+
+    /// Bounds of the transformed corners of the box
+    pub fn transform_bbox(bbox: &BBox, transform: &Mat4) -> BBox {
+        let corner = |i: usize| {
+            Mat4::mult_p(transform, &bbox.lerp((i & 1) as f64, ((i >> 1) & 1) as f64, ((i >> 2) & 1) as f64))
+        };
+
+        (2..8).fold(union_points(&corner(0), &corner(1)), |acc, i| union_point(&acc, &corner(i)))
     }
 
     pub fn inverse(&self) -> Mat4 {
@@ -304,6 +373,20 @@ impl Mat4 {
         out
     }
 
+    /// Element by element, so points move in straight lines from where `a`
+    /// puts them to where `b` does
+    pub fn lerp(a: &Mat4, b: &Mat4, alpha: f64) -> Mat4 {
+        let mut out = Mat4::zero();
+
+        for i in 0usize..4 {
+            for j in 0usize..4 {
+                out.m[i][j] = (1.0 - alpha) * a.m[i][j] + alpha * b.m[i][j];
+            }
+        }
+
+        out
+    }
+
     pub fn mult_v(m: &Mat4, v: &Vec3) -> Vec3 {
         Vec3 {
             x: m.m[0][0] * v.x + m.m[0][1] * v.y + m.m[0][2] * v.z,
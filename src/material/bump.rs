@@ -0,0 +1,97 @@
+use material::Texture;
+use vec3::Vec3;
+
+#[cfg(test)]
+use material::textures::CheckerTexture;
+#[cfg(test)]
+use raytracer::compositor::ColorRGBA;
+
+/// Step in (u, v) for finite differences of height textures
+const HEIGHT_DELTA: f64 = 1.0 / 1024.0;
+
+/// Fine surface detail that tilts the shading normal without moving the
+/// surface. Needs texture coordinates to orient the map.
+pub enum Bump {
+    /// Heights from the texture's brightness, raising the surface `scale`
+    /// units where it is white
+    Height { texture: Box<Texture+Send+Sync>, scale: f64 },
+    /// Normals in tangent space, with x, y and z in the red, green and blue
+    /// channels mapped from [0, 1] to [-1, 1]
+    NormalMap { texture: Box<Texture+Send+Sync> }
+}
+
+impl Bump {
+    /// Tilts the shading normal `n`. `dpdu` and `dpdv` are how the surface
+    /// moves as u and v increase.
+    pub fn perturb(&self, n: &Vec3, dpdu: &Vec3, dpdv: &Vec3, u: f64, v: f64) -> Vec3 {
+        match *self {
+            Bump::Height { ref texture, scale } => height_normal(&**texture, scale, n, dpdu, dpdv, u, v),
+            Bump::NormalMap { ref texture } => {
+                let n = n.unit();
+                let tangent = *dpdu - n.scale(n.dot(dpdu));
+                if tangent.len() < ::std::f64::EPSILON {
+                    return n;
+                }
+                let tangent = tangent.unit();
+
+                // Mirrored UVs flip the bitangent
+                let bitangent = n.cross(&tangent);
+                let bitangent = if bitangent.dot(dpdv) < 0.0 { -bitangent } else { bitangent };
+
+                let color = texture.color(u, v);
+                (tangent.scale(2.0 * color.r - 1.0) +
+                 bitangent.scale(2.0 * color.g - 1.0) +
+                 n.scale(2.0 * color.b - 1.0)).unit()
+            }
+        }
+    }
+}
+
+/// Brightness of the texture, as a height between 0 and 1
+pub fn height(texture: &Texture, u: f64, v: f64) -> f64 {
+    let color = texture.color(u, v);
+    (color.r + color.g + color.b) / 3.0
+}
+
+/// The normal of the surface after raising it by `scale` times the height
+/// texture, which is what both bump mapping and displacement shade with
+pub fn height_normal(texture: &Texture, scale: f64, n: &Vec3, dpdu: &Vec3, dpdv: &Vec3, u: f64, v: f64) -> Vec3 {
+    let n = n.unit();
+    let h = height(texture, u, v);
+    let dhdu = (height(texture, u + HEIGHT_DELTA, v) - h) / HEIGHT_DELTA * scale;
+    let dhdv = (height(texture, u, v + HEIGHT_DELTA) - h) / HEIGHT_DELTA * scale;
+
+    let bumped = (*dpdu + n.scale(dhdu)).cross(&(*dpdv + n.scale(dhdv)));
+    if bumped.len() < ::std::f64::EPSILON {
+        return n;
+    }
+
+    // Keep to the side the unbumped normal faces
+    let bumped = bumped.unit();
+    if bumped.dot(&n) < 0.0 { -bumped } else { bumped }
+}
+
+#[test]
+fn it_tilts_normals() {
+    let n = Vec3 { x: 0.0, y: 0.0, z: 1.0 };
+    let dpdu = Vec3 { x: 1.0, y: 0.0, z: 0.0 };
+    let dpdv = Vec3 { x: 0.0, y: 1.0, z: 0.0 };
+
+    // Flat heights leave the normal alone
+    let flat = Bump::Height { texture: Box::new(CheckerTexture::black_and_white(1.0)), scale: 1.0 };
+    assert!((flat.perturb(&n, &dpdu, &dpdv, 0.25, 0.25) - n).len() < 1e-9);
+
+    // Stepping down from white to black in u tilts the normal towards +u
+    let edge = flat.perturb(&n, &dpdu, &dpdv, 0.5 - HEIGHT_DELTA * 0.5, 0.25);
+    assert!(edge.x > 0.0 && edge.z > 0.0 && edge.y.abs() < 1e-9);
+
+    // A normal map of pure +x tilts the normal onto the tangent
+    let color = ColorRGBA::new_rgb(1.0, 0.5, 0.5);
+    let map = Bump::NormalMap { texture: Box::new(CheckerTexture { color1: color, color2: color, scale: 1.0 }) };
+    assert!((map.perturb(&n, &dpdu, &dpdv, 0.5, 0.5) - dpdu).len() < 1e-9);
+
+    // Mirrored in v, the bitangent follows
+    let up = ColorRGBA::new_rgb(0.5, 1.0, 0.5);
+    let map = Bump::NormalMap { texture: Box::new(CheckerTexture { color1: up, color2: up, scale: 1.0 }) };
+    assert!((map.perturb(&n, &dpdu, &-dpdv, 0.5, 0.5) + dpdv).len() < 1e-9);
+}
@@ -3,6 +3,12 @@ use vec3::Vec3;
 /// TODO: Move specular/transmissive properties into traits
 pub trait Material {
     fn sample(&self, n: Vec3, i: Vec3, l: Vec3, u: f64, v: f64) -> Vec3;
+
+    /// Like `sample`, given the shading tangent as well. Anisotropic
+    /// materials such as hair shade along it; the rest ignore it.
+    fn sample_tangent(&self, n: Vec3, _tangent: Vec3, i: Vec3, l: Vec3, u: f64, v: f64) -> Vec3 {
+        self.sample(n, i, l, u, v)
+    }
     fn is_reflective(&self) -> bool;
     fn is_refractive(&self) -> bool;
     fn global_specular(&self, color: &Vec3) -> Vec3;
@@ -0,0 +1,82 @@
+use material::Material;
+use vec3::Vec3;
+
+/// Kajiya-Kay hair: fibres lit by how square they are to the light, with a
+/// highlight where the light would glance off towards the eye
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct HairMaterial {
+    pub k_d: f64,           // Diffuse coefficient
+    pub k_s: f64,           // Specular coefficient
+    pub diffuse: Vec3,      // Diffuse color
+    pub specular: Vec3,     // Specular color
+    pub shininess: f64      // Tightness of the highlight along the fibre
+}
+
+impl Material for HairMaterial {
+    /// Without a tangent, shades as a plain diffuse surface
+    fn sample(&self, n: Vec3, _i: Vec3, l: Vec3, _u: f64, _v: f64) -> Vec3 {
+        self.diffuse.scale(self.k_d * n.dot(&l).max(0.0))
+    }
+
+    fn sample_tangent(&self, _n: Vec3, tangent: Vec3, i: Vec3, l: Vec3, _u: f64, _v: f64) -> Vec3 {
+        let cos_l = tangent.dot(&l);
+        let cos_i = tangent.dot(&i);
+        let sin_l = (1.0 - cos_l * cos_l).max(0.0).sqrt();
+        let sin_i = (1.0 - cos_i * cos_i).max(0.0).sqrt();
+
+        // The light reflects off a fibre in a cone around it
+        let highlight = (sin_l * sin_i - cos_l * cos_i).max(0.0);
+
+        self.diffuse.scale(self.k_d * sin_l) + self.specular.scale(self.k_s * highlight.powf(self.shininess))
+    }
+
+    fn is_reflective(&self) -> bool {
+        false
+    }
+
+    fn is_refractive(&self) -> bool {
+        false
+    }
+
+    fn global_specular(&self, _color: &Vec3) -> Vec3 {
+        Vec3::zero()
+    }
+
+    fn global_transmissive(&self, _color: &Vec3) -> Vec3 {
+        Vec3::zero()
+    }
+
+    fn transmission(&self) -> Vec3 {
+        Vec3::zero()
+    }
+
+    fn ior(&self) -> f64 {
+        1.0
+    }
+
+    fn is_glossy(&self) -> bool {
+        false
+    }
+
+    fn glossiness(&self) -> f64 {
+        0.0
+    }
+}
+
+#[test]
+fn it_highlights_along_the_reflection_cone() {
+    let hair = HairMaterial { k_d: 1.0, k_s: 1.0, diffuse: Vec3::one(), specular: Vec3::one(), shininess: 50.0 };
+    let tangent = Vec3 { x: 1.0, y: 0.0, z: 0.0 };
+    let n = Vec3 { x: 0.0, y: 0.0, z: 1.0 };
+
+    // Light and eye mirrored about the plane across the fibre
+    let l = Vec3 { x: 1.0, y: 0.0, z: 1.0 }.unit();
+    let i = Vec3 { x: -1.0, y: 1.0, z: 0.0 }.unit();
+    let lit = hair.sample_tangent(n, tangent, i, l, 0.0, 0.0);
+    assert!((lit.x - (0.5f64.sqrt() + 1.0)).abs() < 1e-9);
+
+    // Light along the fibre gives no diffuse, nor a highlight seen from the side
+    let side = Vec3 { x: 0.0, y: 1.0, z: 0.0 };
+    assert!(hair.sample_tangent(n, tangent, side, tangent, 0.0, 0.0).len() < 1e-9);
+}
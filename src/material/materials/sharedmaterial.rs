@@ -0,0 +1,64 @@
+use std::sync::Arc;
+use material::Material;
+use vec3::Vec3;
+
+/// A material used by many prims at once. Cloning it shares the underlying
+/// material instead of copying it, so any material type can be handed out to
+/// every triangle of a mesh.
+#[derive(Clone)]
+pub struct SharedMaterial {
+    material: Arc<Material+Send+Sync>
+}
+
+impl SharedMaterial {
+    pub fn new<M: Material+Send+Sync+'static>(material: M) -> SharedMaterial {
+        SharedMaterial { material: Arc::new(material) }
+    }
+
+    /// Shares a material that has already been boxed
+    pub fn from_box(material: Box<Material+Send+Sync>) -> SharedMaterial {
+        SharedMaterial { material: Arc::from(material) }
+    }
+}
+
+impl Material for SharedMaterial {
+    fn sample(&self, n: Vec3, i: Vec3, l: Vec3, u: f64, v: f64) -> Vec3 {
+        self.material.sample(n, i, l, u, v)
+    }
+
+    fn sample_tangent(&self, n: Vec3, tangent: Vec3, i: Vec3, l: Vec3, u: f64, v: f64) -> Vec3 {
+        self.material.sample_tangent(n, tangent, i, l, u, v)
+    }
+
+    fn is_reflective(&self) -> bool {
+        self.material.is_reflective()
+    }
+
+    fn is_refractive(&self) -> bool {
+        self.material.is_refractive()
+    }
+
+    fn global_specular(&self, color: &Vec3) -> Vec3 {
+        self.material.global_specular(color)
+    }
+
+    fn global_transmissive(&self, color: &Vec3) -> Vec3 {
+        self.material.global_transmissive(color)
+    }
+
+    fn transmission(&self) -> Vec3 {
+        self.material.transmission()
+    }
+
+    fn ior(&self) -> f64 {
+        self.material.ior()
+    }
+
+    fn is_glossy(&self) -> bool {
+        self.material.is_glossy()
+    }
+
+    fn glossiness(&self) -> f64 {
+        self.material.glossiness()
+    }
+}
@@ -1,16 +1,21 @@
 pub use self::material::Material;
 pub use self::texture::Texture;
+pub mod bump;
 pub mod material;
 pub mod texture;
 
 pub mod materials {
     pub use self::cooktorrancematerial::CookTorranceMaterial;
     pub use self::flatmaterial::FlatMaterial;
+    pub use self::hairmaterial::HairMaterial;
     pub use self::phongmaterial::PhongMaterial;
+    pub use self::sharedmaterial::SharedMaterial;
 
     mod cooktorrancematerial;
     mod flatmaterial;
+    mod hairmaterial;
     mod phongmaterial;
+    mod sharedmaterial;
 }
 
 pub mod textures {
@@ -0,0 +1,130 @@
+#![allow(unused_imports)]
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+use geometry::prim::{Prim};
+use geometry::prims::{Plane, TriangleOptions};
+use light::light::{Light};
+use light::lights::{SphereLight};
+use material::{Material, Texture};
+use material::bump::Bump;
+use material::materials::{CookTorranceMaterial};
+use material::textures::ImageTexture;
+use raytracer::AcceleratorOptions;
+use raytracer::compositor::{ColorRGBA, Surface};
+use scene::{Camera, Scene};
+use vec3::Vec3;
+
+// The same ripples as a bump map and as true displacement, next to a wall
+// of tiles from a normal map
+pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
+    Camera::new(
+        Vec3 { x: 0.0, y: 4.0, z: 7.0 },
+        Vec3 { x: 0.0, y: 0.5, z: 0.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        fov,
+        image_width,
+        image_height
+    )
+}
+
+const MAP_SIZE: usize = 256;
+
+/// Concentric ripples, as brightness
+fn ripples() -> ImageTexture {
+    let mut image = Surface::new(MAP_SIZE, MAP_SIZE, ColorRGBA::black());
+    for y in 0..MAP_SIZE {
+        for x in 0..MAP_SIZE {
+            let dx = x as f64 / MAP_SIZE as f64 - 0.5;
+            let dy = y as f64 / MAP_SIZE as f64 - 0.5;
+            let height = 0.5 + 0.5 * ((dx * dx + dy * dy).sqrt() * 12.0 * PI).cos();
+            let level = (height * 255.0) as u8;
+            image[(x, y)] = ColorRGBA::new_rgb(level, level, level);
+        }
+    }
+    ImageTexture { image: image }
+}
+
+/// Four by four tiles bevelled at their edges, as tangent-space normals
+fn tiles() -> ImageTexture {
+    let tile = MAP_SIZE / 4;
+    let bevel = tile / 8;
+    let mut image = Surface::new(MAP_SIZE, MAP_SIZE, ColorRGBA::black());
+    for y in 0..MAP_SIZE {
+        for x in 0..MAP_SIZE {
+            let (tx, ty) = (x % tile, y % tile);
+            let slope_x = if tx < bevel { -0.6 } else if tx >= tile - bevel { 0.6 } else { 0.0 };
+            let slope_y = if ty < bevel { -0.6 } else if ty >= tile - bevel { 0.6 } else { 0.0 };
+            let n = Vec3 { x: slope_x, y: slope_y, z: 1.0 }.unit();
+            image[(x, y)] = ColorRGBA::new_rgb(((n.x * 0.5 + 0.5) * 255.0) as u8,
+                                               ((n.y * 0.5 + 0.5) * 255.0) as u8,
+                                               ((n.z * 0.5 + 0.5) * 255.0) as u8);
+        }
+    }
+    ImageTexture { image: image }
+}
+
+/// Two triangles covering the square from `min` along `u` and `v`, with UVs
+/// running across it once
+fn quad(min: Vec3, u: Vec3, v: Vec3) -> Vec<TriangleOptions> {
+    let mut lower = TriangleOptions::new(min, min + u, min + u + v);
+    lower.texinfo([(0.0, 0.0), (0.999, 0.0), (0.999, 0.999)]);
+    let mut upper = TriangleOptions::new(min, min + u + v, min + v);
+    upper.texinfo([(0.0, 0.0), (0.999, 0.999), (0.0, 0.999)]);
+    vec![lower, upper]
+}
+
+pub fn get_scene(accelerator: AcceleratorOptions) -> Scene {
+    let grey = CookTorranceMaterial { k_a: 0.0, k_d: 0.9, k_s: 1.0, k_sg: 0.2, k_tg: 0.0, gauss_constant: 5.0, roughness: 0.3, glossiness: 0.0, ior: 1.0, ambient: Vec3::one(), diffuse: Vec3 { x: 0.6, y: 0.6, z: 0.6 }, specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
+    let blue = CookTorranceMaterial { diffuse: Vec3 { x: 0.1, y: 0.25, z: 0.8 }, roughness: 0.1, k_sg: 0.6, ..grey.clone() };
+    let red = CookTorranceMaterial { diffuse: Vec3 { x: 0.8, y: 0.15, z: 0.1 }, roughness: 0.1, k_sg: 0.6, ..grey.clone() };
+
+    let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
+    lights.push(Box::new(SphereLight { position: Vec3 { x: -4.0, y: 6.0, z: 4.0 }, color: Vec3::one(), radius: 1.0 }));
+
+    let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
+    prims.push(Box::new(Plane { a: 0.0, b: 1.0, c: 0.0, d: 0.0, material: Box::new(grey.clone()) }));
+
+    let ripples = ripples();
+    let bumped = Arc::new(Bump::Height { texture: Box::new(ripples.clone()), scale: 0.01 });
+    for mut triopts in quad(Vec3 { x: -3.0, y: 0.01, z: 0.5 }, Vec3 { x: 2.5, y: 0.0, z: 0.0 }, Vec3 { x: 0.0, y: 0.0, z: -2.5 }) {
+        triopts.bump(bumped.clone());
+        triopts.material(Box::new(blue.clone()));
+        prims.push(Box::new(triopts.build()));
+    }
+
+    for mut triopts in quad(Vec3 { x: 0.5, y: 0.01, z: 0.5 }, Vec3 { x: 2.5, y: 0.0, z: 0.0 }, Vec3 { x: 0.0, y: 0.0, z: -2.5 }) {
+        triopts.material(Box::new(blue.clone()));
+        for piece in triopts.displaced(&ripples, 0.15, 6) {
+            prims.push(Box::new(piece.build()));
+        }
+    }
+
+    let tiled = Arc::new(Bump::NormalMap { texture: Box::new(tiles()) });
+    for mut triopts in quad(Vec3 { x: -3.0, y: 0.0, z: -3.0 }, Vec3 { x: 6.0, y: 0.0, z: 0.0 }, Vec3 { x: 0.0, y: 3.0, z: 0.0 }) {
+        triopts.bump(tiled.clone());
+        triopts.material(Box::new(red.clone()));
+        prims.push(Box::new(triopts.build()));
+    }
+
+    let accelerator = accelerator.build(prims);
+
+    Scene {
+        lights: lights,
+        accelerator: accelerator,
+        background: Vec3 { x: 0.1, y: 0.1, z: 0.12 },
+        skybox: None
+    }
+}
+
+pub struct BumpsConfig;
+
+impl super::SceneConfig for BumpsConfig {
+    fn get_camera(&self, image_width: u32, image_height: u32, fov: f64) -> Camera {
+        get_camera(image_width, image_height, fov)
+    }
+
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(accelerator)
+    }
+}
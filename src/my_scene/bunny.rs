@@ -7,6 +7,7 @@ use light::lights::{PointLight, SphereLight};
 use material::materials::{CookTorranceMaterial, FlatMaterial, PhongMaterial};
 use material::Texture;
 use material::textures::{CheckerTexture, CubeMap, UVTexture, ImageTexture};
+use raytracer::AcceleratorOptions;
 use raytracer::animator::CameraKeyframe;
 use scene::{Camera, Scene};
 use vec3::Vec3;
@@ -23,7 +24,7 @@ pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
     )
 }
 
-pub fn get_scene() -> Scene {
+pub fn get_scene(accelerator: AcceleratorOptions) -> Scene {
     let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
     lights.push(Box::new(SphereLight { position: Vec3 { x: 200.0, y: -200.0, z: 100.0 }, color: Vec3::one(), radius: 40.0 }));
     lights.push(Box::new(SphereLight { position: Vec3 { x: -95.0, y: 20.0, z: 170.0 }, color: Vec3 { x: 0.5, y: 0.5, z: 0.3 }, radius: 15.0 }));
@@ -34,18 +35,16 @@ pub fn get_scene() -> Scene {
 
     let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
     prims.push(Box::new(Plane { a: 0.0, b: 0.0, c: 1.0, d: -10.0, material: Box::new(green)}));
-    prims.push(Box::new(Sphere { center: Vec3 { x: -75.0, y: 60.0, z: 50.0 }, radius: 40.0, material: Box::new(shiny.clone()) }));
-    prims.push(Box::new(Sphere { center: Vec3 { x: -75.0, y: 60.0, z: 140.0 }, radius: 40.0, material: Box::new(shiny.clone()) }));
+    prims.push(Box::new(Sphere::new(Vec3 { x: -75.0, y: 60.0, z: 50.0 }, 40.0, Box::new(shiny.clone()))));
+    prims.push(Box::new(Sphere::new(Vec3 { x: -75.0, y: 60.0, z: 140.0 }, 40.0, Box::new(shiny.clone()))));
     let bunny = ::util::import::from_obj(red, false, "./docs/assets/models/bunny.obj").expect("failed to load obj model");
     for triangle in bunny.triangles { prims.push(triangle); }
 
-    println!("Generating octree...");
-    let octree = prims.into_iter().collect();
-    println!("Octree generated...");
+    let accelerator = accelerator.build_for_model(prims, "./docs/assets/models/bunny.obj");
 
     Scene {
         lights: lights,
-        octree: octree,
+        accelerator: accelerator,
         background: Vec3 { x: 0.3, y: 0.5, z: 0.8 },
         skybox: Some(CubeMap::load(
             "./docs/assets/textures/skyboxes/storm_y_up/left.png",
@@ -65,7 +64,7 @@ impl super::SceneConfig for BunnyConfig {
         get_camera(image_width, image_height, fov)
     }
 
-    fn get_scene(&self) -> Scene {
-        get_scene()
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(accelerator)
     }
 }
\ No newline at end of file
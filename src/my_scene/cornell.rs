@@ -7,6 +7,7 @@ use light::lights::{PointLight, SphereLight};
 use material::materials::{CookTorranceMaterial, FlatMaterial, PhongMaterial};
 use material::Texture;
 use material::textures::{CheckerTexture, CubeMap, UVTexture, ImageTexture};
+use raytracer::AcceleratorOptions;
 use raytracer::animator::CameraKeyframe;
 use raytracer::compositor::ColorRGBA;
 use scene::{Camera, Scene};
@@ -24,7 +25,7 @@ pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
     )
 }
 
-pub fn get_scene() -> Scene {
+pub fn get_scene(accelerator: AcceleratorOptions) -> Scene {
     let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
     lights.push(Box::new(SphereLight {position: Vec3 { x: 50.0, y: 80.0, z: 50.0 }, color: Vec3::one(), radius: 10.0 }));
 
@@ -49,10 +50,10 @@ pub fn get_scene() -> Scene {
     prims.push(Box::new(Plane { a:  0.0, b: -1.0, c: 0.0, d: 100.0, material: Box::new(grey.clone()) }));         // Top
     prims.push(Box::new(Plane { a:  1.0, b:  0.0, c: 0.0, d: 0.0,   material: Box::new(red.clone()) }));          // Left
     prims.push(Box::new(Plane { a: -1.0, b:  0.0, c: 0.0, d: 100.0, material: Box::new(green.clone()) }));        // Right
-    prims.push(Box::new(Sphere { center: Vec3 { x: 30.0, y: 15.0, z: 20.0 }, radius: 15.0, material: Box::new(shiny.clone())}));
-    prims.push(Box::new(Sphere { center: Vec3 { x: 70.0, y: 17.0, z: 60.0 }, radius: 17.0, material: Box::new(refract.clone())}));
-    prims.push(Box::new(Sphere { center: Vec3 { x: 50.0, y: 50.0, z: 20.0 }, radius: 10.0, material: Box::new(shiny_glossy.clone())}));
-    prims.push(Box::new(Sphere { center: Vec3 { x: 20.0, y: 13.0, z: 90.0 }, radius: 13.0, material: Box::new(blue.clone())}));
+    prims.push(Box::new(Sphere::new(Vec3 { x: 30.0, y: 15.0, z: 20.0 }, 15.0, Box::new(shiny.clone()))));
+    prims.push(Box::new(Sphere::new(Vec3 { x: 70.0, y: 17.0, z: 60.0 }, 17.0, Box::new(refract.clone()))));
+    prims.push(Box::new(Sphere::new(Vec3 { x: 50.0, y: 50.0, z: 20.0 }, 10.0, Box::new(shiny_glossy.clone()))));
+    prims.push(Box::new(Sphere::new(Vec3 { x: 20.0, y: 13.0, z: 90.0 }, 13.0, Box::new(blue.clone()))));
 
     let mut triopts = TriangleOptions::new(
         Vec3 { x: 20.0, y: 95.0, z: 20.0 },
@@ -62,13 +63,11 @@ pub fn get_scene() -> Scene {
     triopts.material(Box::new(blue));
     prims.push(Box::new(triopts.build()));
 
-    println!("Generating octree...");
-    let octree = prims.into_iter().collect();
-    println!("Octree generated...");
+    let accelerator = accelerator.build(prims);
 
     Scene {
         lights: lights,
-        octree: octree,
+        accelerator: accelerator,
         background: Vec3::one(),
         skybox: None
     }
@@ -81,7 +80,7 @@ impl super::SceneConfig for CornelConfig {
         get_camera(image_width, image_height, fov)
     }
 
-    fn get_scene(&self) -> Scene {
-        get_scene()
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(accelerator)
     }
 }
\ No newline at end of file
@@ -2,16 +2,19 @@
 
 use geometry::prim::{Prim};
 use geometry::prims::{Plane, Sphere, Triangle};
+use geometry::subdivision::SubdivisionScheme;
 use light::light::{Light};
 use light::lights::{PointLight, SphereLight};
 use material::materials::{CookTorranceMaterial, FlatMaterial, PhongMaterial};
 use material::Texture;
 use material::textures::{CheckerTexture, CubeMap, UVTexture, ImageTexture};
+use raytracer::AcceleratorOptions;
 use raytracer::animator::CameraKeyframe;
 use scene::{Camera, Scene};
 use vec3::Vec3;
 
-// 5000 polys, cow. Octree helps.
+// 5000 polys, cow. Octree helps. cow-smooth is Loop subdivided once to smooth
+// out the facets.
 pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
     Camera::new(
         Vec3 { x: -2.0, y: 4.0, z: 10.0 },
@@ -23,7 +26,7 @@ pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
     )
 }
 
-pub fn get_scene() -> Scene {
+pub fn get_scene(subdivision_levels: u32, accelerator: AcceleratorOptions) -> Scene {
     let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
     lights.push(Box::new(SphereLight { position: Vec3 {x: 3.0, y: 10.0, z: 6.0}, color: Vec3::one(), radius: 5.0 }));
 
@@ -32,29 +35,44 @@ pub fn get_scene() -> Scene {
 
     let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
     prims.push(Box::new(Plane { a: 0.0, b: 1.0, c: 0.0, d: 3.6, material: Box::new(green) }));
-    let cow = ::util::import::from_obj(red, true, "./docs/assets/models/cow.obj").expect("failed to load obj model");;
+    let cow = if subdivision_levels == 0 {
+        ::util::import::from_obj(red, true, "./docs/assets/models/cow.obj")
+    } else {
+        ::util::import::from_obj_subdivided(red, true, "./docs/assets/models/cow.obj", SubdivisionScheme::Loop, subdivision_levels)
+    }.expect("failed to load obj model");
     for triangle in cow.triangles { prims.push(triangle); }
 
-    println!("Generating octree...");
-    let octree = prims.into_iter().collect();
-    println!("Octree generated...");
+    // Subdivided models are cached separately from the plain one
+    let cache_path = match subdivision_levels {
+        0 => "./docs/assets/models/cow.obj".to_owned(),
+        levels => format!("./docs/assets/models/cow.obj.loop{}", levels)
+    };
+    let accelerator = accelerator.build_for_model(prims, &cache_path);
 
     Scene {
         lights: lights,
-        octree: octree,
+        accelerator: accelerator,
         background: Vec3 { x: 0.3, y: 0.5, z: 0.8 },
         skybox: None
     }
 }
 
-pub struct CowConfig;
+pub struct CowConfig {
+    subdivision_levels: u32
+}
+
+impl CowConfig {
+    pub fn new(subdivision_levels: u32) -> CowConfig {
+        CowConfig { subdivision_levels: subdivision_levels }
+    }
+}
 
 impl super::SceneConfig for CowConfig {
     fn get_camera(&self, image_width: u32, image_height: u32, fov: f64) -> Camera {
         get_camera(image_width, image_height, fov)
     }
 
-    fn get_scene(&self) -> Scene {
-        get_scene()
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(self.subdivision_levels, accelerator)
     }
 }
\ No newline at end of file
@@ -0,0 +1,78 @@
+#![allow(unused_imports)]
+
+use geometry::prim::{Prim};
+use geometry::prims::{Csg, CsgOp, Cuboid, Cylinder, Plane, Sphere};
+use light::light::{Light};
+use light::lights::{SphereLight};
+use material::materials::{CookTorranceMaterial, FlatMaterial};
+use mat4::{Mat4, Transform};
+use raytracer::AcceleratorOptions;
+use scene::{Camera, Scene};
+use vec3::Vec3;
+
+// A glass lens, a sphere with a wedge cut out of it and a rounded cylinder,
+// all built from unions, intersections and differences
+pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
+    Camera::new(
+        Vec3 { x: 0.0, y: 3.5, z: 9.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        fov,
+        image_width,
+        image_height
+    )
+}
+
+pub fn get_scene(accelerator: AcceleratorOptions) -> Scene {
+    let grey = CookTorranceMaterial { k_a: 0.0, k_d: 0.9, k_s: 1.0, k_sg: 0.2, k_tg: 0.0, gauss_constant: 5.0, roughness: 0.3, glossiness: 0.0, ior: 1.0, ambient: Vec3::one(), diffuse: Vec3 { x: 0.6, y: 0.6, z: 0.6 }, specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
+    let red = CookTorranceMaterial { diffuse: Vec3 { x: 0.8, y: 0.15, z: 0.1 }, roughness: 0.1, k_sg: 0.6, ..grey.clone() };
+    let blue = CookTorranceMaterial { diffuse: Vec3 { x: 0.1, y: 0.25, z: 0.8 }, roughness: 0.1, k_sg: 0.6, ..grey.clone() };
+    let glass = CookTorranceMaterial { k_d: 0.0, k_sg: 1.0, k_tg: 1.0, roughness: 0.01, ior: 1.5, diffuse: Vec3::zero(), transmission: Vec3 { x: 0.9, y: 0.9, z: 0.9 }, ..grey.clone() };
+
+    let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
+    lights.push(Box::new(SphereLight { position: Vec3 { x: -3.0, y: 8.0, z: 6.0 }, color: Vec3::one(), radius: 1.0 }));
+
+    let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
+    prims.push(Box::new(Plane { a: 0.0, b: 1.0, c: 0.0, d: 0.0, material: Box::new(grey.clone()) }));
+
+    // Two overlapping spheres leave a lens where they meet
+    prims.push(Box::new(Csg::new(CsgOp::Intersection,
+        Box::new(Sphere::new(Vec3 { x: -3.0, y: 1.2, z: -1.6 }, 2.0, Box::new(glass.clone()))),
+        Box::new(Sphere::new(Vec3 { x: -3.0, y: 1.2, z: 1.6 }, 2.0, Box::new(glass.clone()))))));
+
+    // A sphere with a wedge cut out, showing the inside of the cut
+    let wedge = Cuboid::oriented(Vec3 { x: 0.0, y: 1.2, z: 1.0 }, Vec3 { x: 1.0, y: 1.0, z: 1.0 },
+                                 &Mat4::rotate_y_deg_matrix(45.0), Box::new(red.clone()));
+    prims.push(Box::new(Csg::new(CsgOp::Difference,
+        Box::new(Sphere::new(Vec3 { x: 0.0, y: 1.2, z: 0.0 }, 1.2, Box::new(red.clone()))),
+        Box::new(wedge))));
+
+    // A cylinder with hemispherical ends
+    let capsule = Csg::new(CsgOp::Union,
+        Box::new(Cylinder::new(Vec3 { x: 3.0, y: 0.6, z: 0.0 }, Vec3 { x: 3.0, y: 2.0, z: 0.0 }, 0.6, Box::new(blue.clone()))),
+        Box::new(Csg::new(CsgOp::Union,
+            Box::new(Sphere::new(Vec3 { x: 3.0, y: 0.6, z: 0.0 }, 0.6, Box::new(blue.clone()))),
+            Box::new(Sphere::new(Vec3 { x: 3.0, y: 2.0, z: 0.0 }, 0.6, Box::new(blue.clone()))))));
+    prims.push(Box::new(capsule));
+
+    let accelerator = accelerator.build(prims);
+
+    Scene {
+        lights: lights,
+        accelerator: accelerator,
+        background: Vec3 { x: 0.1, y: 0.1, z: 0.12 },
+        skybox: None
+    }
+}
+
+pub struct CsgConfig;
+
+impl super::SceneConfig for CsgConfig {
+    fn get_camera(&self, image_width: u32, image_height: u32, fov: f64) -> Camera {
+        get_camera(image_width, image_height, fov)
+    }
+
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(accelerator)
+    }
+}
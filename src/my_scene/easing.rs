@@ -7,6 +7,7 @@ use light::lights::{PointLight, SphereLight};
 use material::materials::{CookTorranceMaterial, FlatMaterial, PhongMaterial};
 use material::Texture;
 use material::textures::{CheckerTexture, CubeMap, UVTexture, ImageTexture};
+use raytracer::AcceleratorOptions;
 use raytracer::animator::CameraKeyframe;
 use raytracer::compositor::ColorRGBA;
 use raytracer::animator::easing::Easing;
@@ -45,7 +46,7 @@ pub fn get_animation_camera(image_width: u32, image_height: u32, fov: f64) -> Ca
     )
 }
 
-pub fn get_scene() -> Scene {
+pub fn get_scene(accelerator: AcceleratorOptions) -> Scene {
     let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
     lights.push(Box::new(SphereLight {
         position: Vec3 { x: 0.0, y: 0.0, z: 150.0 },
@@ -90,11 +91,11 @@ pub fn get_scene() -> Scene {
         material: Box::new(checker_mat)
     }));
 
-    let octree = prims.into_iter().collect();
+    let accelerator = accelerator.build(prims);
 
     Scene {
         lights: lights,
-        octree: octree,
+        accelerator: accelerator,
         background: Vec3 { x: 1.0, y: 1.0, z: 1.0 },
         skybox: None
     }
@@ -111,7 +112,7 @@ impl super::SceneConfig for EasingConfig {
         get_animation_camera(image_width, image_height, fov)
     }
 
-    fn get_scene(&self) -> Scene {
-        get_scene()
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(accelerator)
     }
 }
\ No newline at end of file
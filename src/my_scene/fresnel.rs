@@ -7,6 +7,7 @@ use light::lights::{PointLight, SphereLight};
 use material::materials::{CookTorranceMaterial, FlatMaterial, PhongMaterial};
 use material::Texture;
 use material::textures::{CheckerTexture, CubeMap, UVTexture, ImageTexture};
+use raytracer::AcceleratorOptions;
 use raytracer::animator::CameraKeyframe;
 use raytracer::compositor::ColorRGBA;
 use raytracer::animator::easing::Easing;
@@ -70,7 +71,7 @@ pub fn get_animation_camera(image_width: u32, image_height: u32, fov: f64) -> Ca
     )
 }
 
-pub fn get_scene() -> Scene {
+pub fn get_scene(accelerator: AcceleratorOptions) -> Scene {
     let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
     lights.push(Box::new(SphereLight { position: Vec3 { x: 50.0, y: 80.0, z: 50.0 }, color: Vec3::one(), radius: 10.0 }));
 
@@ -84,16 +85,14 @@ pub fn get_scene() -> Scene {
     let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
     prims.push(Box::new(Plane { a: 0.0,  b:  0.0, c: 1.0, d: 0.0, material: Box::new(checker_red.clone()) })); // Ahead
     prims.push(Box::new(Plane { a: 0.0,  b:  1.0, c: 0.0, d: 0.0, material: Box::new(global_specular_only.clone()) })); // Bottom
-    prims.push(Box::new(Sphere { center: Vec3 {x: 30.0, y: 15.0, z: 20.0 }, radius: 15.0, material: Box::new(shiny.clone()) }));
-    prims.push(Box::new(Sphere { center: Vec3 {x: 70.0, y: 17.0, z: 60.0 }, radius: 17.0, material: Box::new(refract.clone()) }));
+    prims.push(Box::new(Sphere::new(Vec3 {x: 30.0, y: 15.0, z: 20.0 }, 15.0, Box::new(shiny.clone()))));
+    prims.push(Box::new(Sphere::new(Vec3 {x: 70.0, y: 17.0, z: 60.0 }, 17.0, Box::new(refract.clone()))));
 
-    println!("Generating octree...");
-    let octree = prims.into_iter().collect();
-    println!("Octree generated...");
+    let accelerator = accelerator.build(prims);
 
     Scene {
         lights: lights,
-        octree: octree,
+        accelerator: accelerator,
         background: Vec3 { x: 1.0, y: 1.0, z: 1.0 },
         skybox: None
     }
@@ -111,7 +110,7 @@ impl super::SceneConfig for FresnelConfig {
         get_animation_camera(image_width, image_height, fov)
     }
 
-    fn get_scene(&self) -> Scene {
-        get_scene()
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(accelerator)
     }
 }
\ No newline at end of file
@@ -0,0 +1,91 @@
+#![allow(unused_imports)]
+
+use std::f64::consts::PI;
+use geometry::prim::{Prim};
+use geometry::prims::{Curve, Plane, Sphere};
+use light::light::{Light};
+use light::lights::{SphereLight};
+use material::materials::{CookTorranceMaterial, HairMaterial, SharedMaterial};
+use raytracer::AcceleratorOptions;
+use scene::{Camera, Scene};
+use vec3::Vec3;
+
+// A furry ball, with strands drooping under their own weight
+pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
+    Camera::new(
+        Vec3 { x: 0.0, y: 2.0, z: 5.0 },
+        Vec3 { x: 0.0, y: 1.2, z: 0.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        fov,
+        image_width,
+        image_height
+    )
+}
+
+const STRANDS: usize = 6000;
+const STRAND_LENGTH: f64 = 0.35;
+
+pub fn get_scene(accelerator: AcceleratorOptions) -> Scene {
+    let grey = CookTorranceMaterial { k_a: 0.0, k_d: 0.9, k_s: 1.0, k_sg: 0.2, k_tg: 0.0, gauss_constant: 5.0, roughness: 0.3, glossiness: 0.0, ior: 1.0, ambient: Vec3::one(), diffuse: Vec3 { x: 0.6, y: 0.6, z: 0.6 }, specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
+    let skin = CookTorranceMaterial { diffuse: Vec3 { x: 0.3, y: 0.15, z: 0.05 }, ..grey.clone() };
+    let hair = SharedMaterial::new(HairMaterial {
+        k_d: 0.7,
+        k_s: 0.4,
+        diffuse: Vec3 { x: 0.6, y: 0.35, z: 0.12 },
+        specular: Vec3 { x: 1.0, y: 0.9, z: 0.7 },
+        shininess: 40.0
+    });
+
+    let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
+    lights.push(Box::new(SphereLight { position: Vec3 { x: -3.0, y: 6.0, z: 4.0 }, color: Vec3::one(), radius: 1.0 }));
+
+    let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
+    prims.push(Box::new(Plane { a: 0.0, b: 1.0, c: 0.0, d: 0.0, material: Box::new(grey.clone()) }));
+
+    let center = Vec3 { x: 0.0, y: 1.2, z: 0.0 };
+    let radius = 0.8;
+    prims.push(Box::new(Sphere::new(center, radius, Box::new(skin))));
+
+    // Roots spread evenly over the ball on a golden-angle spiral
+    let golden_angle = PI * (3.0 - 5.0f64.sqrt());
+    let gravity = Vec3 { x: 0.0, y: -1.0, z: 0.0 };
+    for i in 0..STRANDS {
+        let y = 1.0 - 2.0 * (i as f64 + 0.5) / STRANDS as f64;
+        let ring = (1.0 - y * y).sqrt();
+        let angle = golden_angle * i as f64;
+        let n = Vec3 { x: ring * angle.cos(), y: y, z: ring * angle.sin() };
+
+        let root = center + n.scale(radius);
+        let points = [
+            root,
+            root + n.scale(STRAND_LENGTH * 0.4),
+            root + n.scale(STRAND_LENGTH * 0.7) + gravity.scale(STRAND_LENGTH * 0.2),
+            root + n.scale(STRAND_LENGTH * 0.8) + gravity.scale(STRAND_LENGTH * 0.5)
+        ];
+
+        for piece in Curve::pieces(points, (0.012, 0.002), 2, &hair) {
+            prims.push(Box::new(piece));
+        }
+    }
+
+    let accelerator = accelerator.build(prims);
+
+    Scene {
+        lights: lights,
+        accelerator: accelerator,
+        background: Vec3 { x: 0.1, y: 0.1, z: 0.12 },
+        skybox: None
+    }
+}
+
+pub struct FurConfig;
+
+impl super::SceneConfig for FurConfig {
+    fn get_camera(&self, image_width: u32, image_height: u32, fov: f64) -> Camera {
+        get_camera(image_width, image_height, fov)
+    }
+
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(accelerator)
+    }
+}
@@ -7,6 +7,7 @@ use light::lights::{PointLight, SphereLight};
 use material::materials::{CookTorranceMaterial, FlatMaterial, PhongMaterial};
 use material::Texture;
 use material::textures::{CheckerTexture, CubeMap, UVTexture, ImageTexture};
+use raytracer::AcceleratorOptions;
 use raytracer::animator::CameraKeyframe;
 use scene::{Camera, Scene};
 use vec3::Vec3;
@@ -23,7 +24,7 @@ pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
     )
 }
 
-pub fn get_scene(material_option: HeptoroidMaterial) -> Scene {
+pub fn get_scene(material_option: HeptoroidMaterial, accelerator: AcceleratorOptions) -> Scene {
     let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
     lights.push(Box::new(SphereLight { position: Vec3 { x: 2.0, y: 3.0, z: -2.0 }, color: Vec3 { x: 1.0, y: 1.0, z: 1.0 }, radius: 1.0 }));
 
@@ -43,13 +44,11 @@ pub fn get_scene(material_option: HeptoroidMaterial) -> Scene {
     let heptoroid = ::util::import::from_obj(heptoroid_material, false, "./docs/assets/models/heptoroid.obj").ok().expect("failed to load obj model");;
     for triangle in heptoroid.triangles.into_iter() { prims.push(triangle); }
 
-    println!("Generating octree...");
-    let octree = prims.into_iter().collect();
-    println!("Octree generated...");
+    let accelerator = accelerator.build_for_model(prims, "./docs/assets/models/heptoroid.obj");
 
     Scene {
         lights: lights,
-        octree: octree,
+        accelerator: accelerator,
         background: Vec3 { x: 0.84, y: 0.34, z: 0.0 },
         skybox: Some(CubeMap::load(
             "./docs/assets/textures/skyboxes/miramar_y_up/left.png",
@@ -92,7 +91,7 @@ impl super::SceneConfig for HeptoroidConfig {
         get_camera(image_width, image_height, fov)
     }
 
-    fn get_scene(&self) -> Scene {
-        get_scene(self.material)
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(self.material, accelerator)
     }
 }
\ No newline at end of file
@@ -0,0 +1,89 @@
+#![allow(unused_imports)]
+
+use geometry::prim::{Prim};
+use geometry::prims::{CsgOp, Implicit, Plane};
+use geometry::sdf::{Sdf, SdfBlend, SdfBox, SdfCapsule, SdfSphere, SdfTorus};
+use light::light::{Light};
+use light::lights::{SphereLight};
+use material::materials::{CookTorranceMaterial};
+use mat4::{Mat4, Transform};
+use raytracer::AcceleratorOptions;
+use scene::{Camera, Scene};
+use vec3::Vec3;
+
+// Shapes defined by signed distance functions and blended into each other
+pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
+    Camera::new(
+        Vec3 { x: 0.0, y: 3.5, z: 9.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        fov,
+        image_width,
+        image_height
+    )
+}
+
+/// Spheres around a ring, melted together where they meet
+fn blob_ring(center: Vec3, count: u32) -> Box<Sdf+Send+Sync> {
+    let mut sdf: Box<Sdf+Send+Sync> = Box::new(SdfTorus { center: center, major_radius: 1.0, minor_radius: 0.15 });
+
+    for i in 0..count {
+        let angle = 2.0 * ::std::f64::consts::PI * i as f64 / count as f64;
+        let position = center + Vec3 { x: angle.cos(), y: 0.0, z: angle.sin() };
+        let ball = Box::new(SdfSphere { center: position, radius: 0.35 });
+        sdf = Box::new(SdfBlend::new(CsgOp::Union, 0.4, sdf, ball));
+    }
+
+    sdf
+}
+
+pub fn get_scene(accelerator: AcceleratorOptions) -> Scene {
+    let grey = CookTorranceMaterial { k_a: 0.0, k_d: 0.9, k_s: 1.0, k_sg: 0.2, k_tg: 0.0, gauss_constant: 5.0, roughness: 0.3, glossiness: 0.0, ior: 1.0, ambient: Vec3::one(), diffuse: Vec3 { x: 0.6, y: 0.6, z: 0.6 }, specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
+    let red = CookTorranceMaterial { diffuse: Vec3 { x: 0.8, y: 0.15, z: 0.1 }, roughness: 0.1, k_sg: 0.6, ..grey.clone() };
+    let blue = CookTorranceMaterial { diffuse: Vec3 { x: 0.1, y: 0.25, z: 0.8 }, roughness: 0.1, k_sg: 0.6, ..grey.clone() };
+    let gold = CookTorranceMaterial { diffuse: Vec3 { x: 0.9, y: 0.7, z: 0.2 }, specular: Vec3 { x: 0.9, y: 0.7, z: 0.2 }, roughness: 0.05, k_sg: 1.0, ior: 0.4, ..grey.clone() };
+
+    let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
+    lights.push(Box::new(SphereLight { position: Vec3 { x: -3.0, y: 8.0, z: 6.0 }, color: Vec3::one(), radius: 1.0 }));
+
+    let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
+    prims.push(Box::new(Plane { a: 0.0, b: 1.0, c: 0.0, d: 0.0, material: Box::new(grey.clone()) }));
+
+    // A rounded box with a softened spherical scoop taken out of its top
+    let scooped = SdfBlend::new(CsgOp::Difference, 0.2,
+        Box::new(SdfBox { center: Vec3 { x: -3.0, y: 1.0, z: 0.0 }, half_extents: Vec3 { x: 1.0, y: 1.0, z: 1.0 }, rounding: 0.2 }),
+        Box::new(SdfSphere { center: Vec3 { x: -3.0, y: 2.2, z: 0.0 }, radius: 0.9 }));
+    prims.push(Box::new(Implicit::new(Box::new(scooped), Box::new(red.clone()))));
+
+    let mut ring = Implicit::new(blob_ring(Vec3::zero(), 7), Box::new(gold.clone()));
+    ring.mut_transform(&Transform::new(Mat4::mult_m(&Mat4::translate_matrix(&Vec3 { x: 0.0, y: 1.3, z: 0.0 }),
+                                                    &Mat4::rotate_x_deg_matrix(60.0))));
+    prims.push(Box::new(ring));
+
+    // A capsule rounded off where a sphere cuts it
+    let pinched = SdfBlend::new(CsgOp::Intersection, 0.3,
+        Box::new(SdfCapsule { a: Vec3 { x: 3.0, y: 0.5, z: 0.0 }, b: Vec3 { x: 3.0, y: 2.5, z: 0.0 }, radius: 0.8 }),
+        Box::new(SdfSphere { center: Vec3 { x: 3.0, y: 1.5, z: 0.0 }, radius: 1.3 }));
+    prims.push(Box::new(Implicit::new(Box::new(pinched), Box::new(blue.clone()))));
+
+    let accelerator = accelerator.build(prims);
+
+    Scene {
+        lights: lights,
+        accelerator: accelerator,
+        background: Vec3 { x: 0.1, y: 0.1, z: 0.12 },
+        skybox: None
+    }
+}
+
+pub struct ImplicitConfig;
+
+impl super::SceneConfig for ImplicitConfig {
+    fn get_camera(&self, image_width: u32, image_height: u32, fov: f64) -> Camera {
+        get_camera(image_width, image_height, fov)
+    }
+
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(accelerator)
+    }
+}
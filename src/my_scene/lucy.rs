@@ -7,6 +7,7 @@ use light::lights::{PointLight, SphereLight};
 use material::materials::{CookTorranceMaterial, FlatMaterial, PhongMaterial};
 use material::Texture;
 use material::textures::{CheckerTexture, CubeMap, UVTexture, ImageTexture};
+use raytracer::AcceleratorOptions;
 use raytracer::animator::CameraKeyframe;
 use scene::{Camera, Scene};
 use vec3::Vec3;
@@ -23,23 +24,23 @@ pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
     )
 }
 
-pub fn get_scene() -> Scene {
+pub fn get_scene(accelerator: AcceleratorOptions) -> Scene {
     let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
     lights.push(Box::new(SphereLight { position: Vec3 { x: -1400.0, y: 200.0, z: 100.0 }, color: Vec3 { x: 1.0, y: 0.80, z: 0.40 }, radius: 50.0 }));
 
     let grey = CookTorranceMaterial { k_a: 0.0, k_d: 0.5, k_s: 0.8, k_sg: 0.5, k_tg: 0.0, gauss_constant: 5.0, roughness: 0.1, glossiness: 0.0, ior: 0.4, ambient: Vec3::one(), diffuse: Vec3 { x: 0.6, y: 0.6, z: 0.65 }, specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
 
+    // One prim with a hierarchy of its own over the triangles, which is cached
+    // next to the model like other scenes' acceleration structures
     let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
-    let lucy = ::util::import::from_obj(grey, true, "./docs/assets/models/lucy.obj").ok().expect("failed to load obj model");;
-    for triangle in lucy.triangles.into_iter() { prims.push(triangle); }
+    let lucy = ::util::import::from_obj_mesh(Box::new(grey), true, accelerator.cache, "./docs/assets/models/lucy.obj").ok().expect("failed to load obj model");
+    prims.push(Box::new(lucy));
 
-    println!("Generating octree...");
-    let octree = prims.into_iter().collect();
-    println!("Octree generated...");
+    let accelerator = accelerator.build(prims);
 
     Scene {
         lights: lights,
-        octree: octree,
+        accelerator: accelerator,
         background: Vec3 { x: 0.84, y: 0.34, z: 0.0 },
         skybox: Some(CubeMap::load(
             "./docs/assets/textures/skyboxes/storm_y_up/left.png",
@@ -59,7 +60,7 @@ impl super::SceneConfig for LucyConfig {
         get_camera(image_width, image_height, fov)
     }
     
-    fn get_scene(&self) -> Scene {
-        get_scene()
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(accelerator)
     }
 }
\ No newline at end of file
@@ -1,18 +1,29 @@
 #![cfg_attr(test, allow(dead_code))]
+use ::raytracer::{AcceleratorKind, AcceleratorOptions};
 use ::scene::{Camera, Scene};
 
+pub mod bumps;
 pub mod bunny;
 pub mod cornell;
 pub mod cow;
+pub mod csg;
 pub mod easing;
 pub mod fresnel;
+pub mod fur;
 pub mod heptoroid;
+pub mod implicit;
 pub mod lucy;
+pub mod particles;
+pub mod primitives;
+pub mod shadows;
 pub mod sibenik;
 pub mod sphere;
+pub mod spin;
 pub mod sponza;
 pub mod tachikoma;
+pub mod terrain;
 pub mod teapot;
+pub mod teapots;
 
 pub trait SceneConfig {
     fn get_camera(&self, image_width: u32, image_height: u32, fov: f64) -> Camera;
@@ -21,25 +32,57 @@ pub trait SceneConfig {
         self.get_camera(image_width, image_height, fov)
     }
 
-    fn get_scene(&self) -> Scene;
+    /// The acceleration structure used unless the config file asks for
+    /// another. Override only with `tools/bench.sh` timings to back it.
+    fn default_accelerator(&self) -> AcceleratorKind {
+        AcceleratorKind::Octree
+    }
+
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene;
+
+    /// Scenes with keyframed objects, lights or materials return their
+    /// animation here, which is used instead of `get_scene` when animating
+    fn get_animation(&self, _accelerator: AcceleratorOptions) -> Option<Box<SceneAnimation>> {
+        None
+    }
+}
+
+/// Builds the scene afresh for each frame. Heavy geometry should be loaded
+/// once and placed with `Instance`s so only the top level is rebuilt.
+pub trait SceneAnimation {
+    fn scene_at_time(&self, time: f64) -> Scene;
 }
 
 pub fn scene_by_name(name: &str) -> Option<Box<SceneConfig>> {
     Some(match name {
+        "bumps" => Box::new(bumps::BumpsConfig),
         "bunny" => Box::new(bunny::BunnyConfig),
         "cornell" => Box::new(cornell::CornelConfig),
-        "cow" => Box::new(cow::CowConfig),
+        "cow" => Box::new(cow::CowConfig::new(0)),
+        "cow-smooth" => Box::new(cow::CowConfig::new(1)),
+        "csg" => Box::new(csg::CsgConfig),
         "easing" => Box::new(easing::EasingConfig),
         "fresnel" => Box::new(fresnel::FresnelConfig),
+        "fur" => Box::new(fur::FurConfig),
         "heptoroid-shiny" => Box::new(heptoroid::HeptoroidConfig::shiny()),
         "heptoroid-white" => Box::new(heptoroid::HeptoroidConfig::white()),
         "heptoroid-refractive" => Box::new(heptoroid::HeptoroidConfig::refractive()),
+        "implicit" => Box::new(implicit::ImplicitConfig),
         "lucy" => Box::new(lucy::LucyConfig),
+        "particles" => Box::new(particles::ParticlesConfig),
+        "primitives" => Box::new(primitives::PrimitivesConfig),
+        "shadows-tiny" => Box::new(shadows::ShadowsConfig::new(0.001)),
+        "shadows" => Box::new(shadows::ShadowsConfig::new(1.0)),
+        "shadows-huge" => Box::new(shadows::ShadowsConfig::new(10000.0)),
         "sibenik" => Box::new(sibenik::SibenikConfig),
         "sphere" => Box::new(sphere::SphereConfig),
+        "spin" => Box::new(spin::SpinConfig),
         "sponza" => Box::new(sponza::SponzaConfig),
+        "sponza-mtl" => Box::new(sponza::SponzaMtlConfig),
         "tachikoma" => Box::new(tachikoma::TachikomaConfig),
+        "terrain" => Box::new(terrain::TerrainConfig),
         "teapot" => Box::new(teapot::TeapotConfig),
+        "teapots" => Box::new(teapots::TeapotsConfig),
         _ => return None,
     })
 }
\ No newline at end of file
@@ -0,0 +1,102 @@
+#![allow(unused_imports)]
+
+use std::f64::consts::PI;
+use geometry::prim::{Prim};
+use geometry::prims::{ParticleSet, ParticleShape, Plane, PointCloud};
+use light::light::{Light};
+use light::lights::{SphereLight};
+use material::materials::{CookTorranceMaterial, FlatMaterial};
+use raytracer::AcceleratorOptions;
+use scene::{Camera, Scene};
+use vec3::Vec3;
+
+// A spiral galaxy of glowing discs above a cloud of small coloured spheres,
+// each set a single prim
+const EYE: Vec3 = Vec3 { x: 0.0, y: 6.0, z: 9.0 };
+
+pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
+    Camera::new(
+        EYE,
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        fov,
+        image_width,
+        image_height
+    )
+}
+
+const STARS: usize = 200000;
+const BEADS: usize = 20000;
+
+/// Cheap repeatable scatter in [0, 1), so the scene is the same every render
+fn scatter(i: usize, salt: u64) -> f64 {
+    let mut x = (i as u64).wrapping_mul(0x9E3779B97F4A7C15) ^ salt.wrapping_mul(0xBF58476D1CE4E5B9);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D049BB133111EB);
+    (x ^ (x >> 31)) as f64 / 18446744073709551616.0
+}
+
+fn galaxy() -> PointCloud {
+    let mut stars = ParticleSet::new();
+    for i in 0..STARS {
+        let arm = (i % 2) as f64 * PI;
+        let r = 0.2 + 3.8 * scatter(i, 1).sqrt();
+        let angle = arm + r * 1.3 + (scatter(i, 2) - 0.5) * 0.6;
+        let height = (scatter(i, 3) - 0.5) * 0.3 / (1.0 + r);
+
+        // Yellow at the core, blue out along the arms
+        let warmth = (1.0 - r / 4.0).max(0.0);
+        let color = Vec3 { x: 0.6 + 0.4 * warmth, y: 0.7 + 0.2 * warmth, z: 1.0 - 0.5 * warmth };
+        stars.add(Vec3 { x: r * angle.cos(), y: 2.5 + height, z: r * angle.sin() }, 0.01, color);
+    }
+
+    // Facing the camera, so every star shows as a full round disc
+    stars.build(ParticleShape::Disc(EYE), |color| Box::new(FlatMaterial { color: color }))
+}
+
+fn beads() -> PointCloud {
+    let grey = CookTorranceMaterial { k_a: 0.0, k_d: 0.9, k_s: 0.6, k_sg: 0.0, k_tg: 0.0, gauss_constant: 5.0, roughness: 0.2, glossiness: 0.0, ior: 1.0, ambient: Vec3::one(), diffuse: Vec3::one(), specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
+
+    let mut beads = ParticleSet::new();
+    for i in 0..BEADS {
+        let position = Vec3 { x: (scatter(i, 4) - 0.5) * 6.0, y: 0.0, z: (scatter(i, 5) - 0.5) * 6.0 };
+        let radius = 0.02 + 0.03 * scatter(i, 6);
+        let color = Vec3 { x: scatter(i, 7), y: scatter(i, 8), z: scatter(i, 9) };
+        beads.add(position + Vec3 { x: 0.0, y: radius, z: 0.0 }, radius, color);
+    }
+
+    beads.build(ParticleShape::Sphere, |color| Box::new(CookTorranceMaterial { diffuse: color, ..grey.clone() }))
+}
+
+pub fn get_scene(accelerator: AcceleratorOptions) -> Scene {
+    let grey = CookTorranceMaterial { k_a: 0.0, k_d: 0.9, k_s: 0.0, k_sg: 0.0, k_tg: 0.0, gauss_constant: 5.0, roughness: 0.3, glossiness: 0.0, ior: 1.0, ambient: Vec3::one(), diffuse: Vec3 { x: 0.3, y: 0.3, z: 0.3 }, specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
+
+    let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
+    lights.push(Box::new(SphereLight { position: Vec3 { x: 3.0, y: 8.0, z: 4.0 }, color: Vec3::one(), radius: 1.0 }));
+
+    let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
+    prims.push(Box::new(Plane { a: 0.0, b: 1.0, c: 0.0, d: 0.0, material: Box::new(grey) }));
+    prims.push(Box::new(galaxy()));
+    prims.push(Box::new(beads()));
+
+    let accelerator = accelerator.build(prims);
+
+    Scene {
+        lights: lights,
+        accelerator: accelerator,
+        background: Vec3 { x: 0.02, y: 0.02, z: 0.05 },
+        skybox: None
+    }
+}
+
+pub struct ParticlesConfig;
+
+impl super::SceneConfig for ParticlesConfig {
+    fn get_camera(&self, image_width: u32, image_height: u32, fov: f64) -> Camera {
+        get_camera(image_width, image_height, fov)
+    }
+
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(accelerator)
+    }
+}
@@ -0,0 +1,68 @@
+#![allow(unused_imports)]
+
+use geometry::prim::{Prim};
+use geometry::prims::{Cone, Cuboid, Cylinder, Disk, Plane, Sphere, Torus};
+use light::light::{Light};
+use light::lights::{ShapeLight, SphereLight};
+use material::materials::{CookTorranceMaterial, FlatMaterial, PhongMaterial};
+use mat4::{Mat4, Transform};
+use raytracer::AcceleratorOptions;
+use scene::{Camera, Scene};
+use vec3::Vec3;
+
+// One of each analytic primitive, lit by a disk-shaped softbox
+pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
+    Camera::new(
+        Vec3 { x: 0.0, y: 4.0, z: 10.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        fov,
+        image_width,
+        image_height
+    )
+}
+
+pub fn get_scene(accelerator: AcceleratorOptions) -> Scene {
+    let white = FlatMaterial { color: Vec3::one() };
+    let grey = CookTorranceMaterial { k_a: 0.0, k_d: 0.9, k_s: 1.0, k_sg: 0.2, k_tg: 0.0, gauss_constant: 5.0, roughness: 0.3, glossiness: 0.0, ior: 1.0, ambient: Vec3::one(), diffuse: Vec3 { x: 0.6, y: 0.6, z: 0.6 }, specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
+    let red = CookTorranceMaterial { diffuse: Vec3 { x: 0.8, y: 0.15, z: 0.1 }, roughness: 0.1, k_sg: 0.6, ..grey.clone() };
+    let blue = CookTorranceMaterial { diffuse: Vec3 { x: 0.1, y: 0.25, z: 0.8 }, roughness: 0.1, k_sg: 0.6, ..grey.clone() };
+    let gold = CookTorranceMaterial { diffuse: Vec3 { x: 0.9, y: 0.7, z: 0.2 }, specular: Vec3 { x: 0.9, y: 0.7, z: 0.2 }, roughness: 0.05, k_sg: 1.0, ior: 0.4, ..grey.clone() };
+
+    let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
+    lights.push(Box::new(ShapeLight {
+        shape: Disk::new(Vec3 { x: -2.0, y: 6.0, z: 4.0 }, Vec3 { x: 0.3, y: -1.0, z: -0.5 }, 1.5, Box::new(white.clone())),
+        color: Vec3::one()
+    }));
+
+    let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
+    prims.push(Box::new(Plane { a: 0.0, b: 1.0, c: 0.0, d: 0.0, material: Box::new(grey.clone()) }));
+    prims.push(Box::new(Cuboid::new(Vec3 { x: -4.5, y: 0.0, z: -1.0 }, Vec3 { x: -2.5, y: 1.0, z: 1.0 }, Box::new(grey.clone()))));
+    prims.push(Box::new(Cuboid::oriented(Vec3 { x: -3.5, y: 1.6, z: 0.0 }, Vec3 { x: 0.6, y: 0.6, z: 0.6 }, &Mat4::rotate_y_deg_matrix(30.0), Box::new(red.clone()))));
+    prims.push(Box::new(Cylinder::new(Vec3 { x: -1.0, y: 0.0, z: 0.0 }, Vec3 { x: -1.0, y: 2.0, z: 0.0 }, 0.7, Box::new(blue.clone()))));
+    prims.push(Box::new(Cone::new(Vec3 { x: 1.0, y: 0.0, z: 0.0 }, Vec3 { x: 1.0, y: 2.2, z: 0.0 }, 0.8, Box::new(red.clone()))));
+    prims.push(Box::new(Torus::new(Vec3 { x: 3.5, y: 0.9, z: 0.0 }, Vec3 { x: 0.0, y: 0.2, z: 1.0 }, 0.8, 0.3, Box::new(gold.clone()))));
+    prims.push(Box::new(Disk::new(Vec3 { x: 0.0, y: 0.01, z: 2.5 }, Vec3 { x: 0.0, y: 1.0, z: 0.0 }, 1.0, Box::new(blue.clone()))));
+    prims.push(Box::new(Sphere::new(Vec3 { x: 0.0, y: 0.5, z: 2.5 }, 0.5, Box::new(gold.clone()))));
+
+    let accelerator = accelerator.build(prims);
+
+    Scene {
+        lights: lights,
+        accelerator: accelerator,
+        background: Vec3 { x: 0.1, y: 0.1, z: 0.12 },
+        skybox: None
+    }
+}
+
+pub struct PrimitivesConfig;
+
+impl super::SceneConfig for PrimitivesConfig {
+    fn get_camera(&self, image_width: u32, image_height: u32, fov: f64) -> Camera {
+        get_camera(image_width, image_height, fov)
+    }
+
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(accelerator)
+    }
+}
@@ -0,0 +1,106 @@
+#![allow(unused_imports)]
+
+use std::f64::consts::PI;
+use geometry::prim::{Prim};
+use geometry::prims::{Cuboid, TriangleOptions};
+use light::light::{Light};
+use light::lights::{PointLight};
+use material::materials::{CookTorranceMaterial};
+use raytracer::AcceleratorOptions;
+use scene::{Camera, Scene};
+use vec3::Vec3;
+
+// A coarse smooth-shaded ball and a box on a triangle floor under a low
+// light. The same scene at any scale should render the same: no speckled
+// self-shadowing on lit faces, and no light leaking under the box.
+pub fn get_camera(scale: f64, image_width: u32, image_height: u32, fov: f64) -> Camera {
+    Camera::new(
+        Vec3 { x: 0.0, y: 2.5, z: 7.0 }.scale(scale),
+        Vec3 { x: 0.0, y: 0.6, z: 0.0 }.scale(scale),
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        fov,
+        image_width,
+        image_height
+    )
+}
+
+/// A latitude-longitude ball with interpolated normals, whose flat facets
+/// disagree with its shading near the shadow line
+fn ball(center: Vec3, radius: f64, material: &CookTorranceMaterial) -> Vec<TriangleOptions> {
+    let (rings, segments) = (8, 16);
+    let normal = |ring: u32, segment: u32| {
+        let theta = PI * ring as f64 / rings as f64;
+        let phi = 2.0 * PI * (segment % segments) as f64 / segments as f64;
+        Vec3 { x: theta.sin() * phi.cos(), y: theta.cos(), z: theta.sin() * phi.sin() }
+    };
+
+    let mut triangles = Vec::new();
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let corners = [normal(ring, segment), normal(ring + 1, segment), normal(ring + 1, segment + 1), normal(ring, segment + 1)];
+            for &(a, b, c) in [(0, 1, 2), (0, 2, 3)].iter() {
+                let mut triopts = TriangleOptions::new(center + corners[a].scale(radius), center + corners[b].scale(radius), center + corners[c].scale(radius));
+                triopts.normals([corners[a], corners[b], corners[c]]);
+                triopts.material(Box::new(material.clone()));
+                triangles.push(triopts);
+            }
+        }
+    }
+
+    triangles
+}
+
+pub fn get_scene(scale: f64, accelerator: AcceleratorOptions) -> Scene {
+    let grey = CookTorranceMaterial { k_a: 0.0, k_d: 1.0, k_s: 0.3, k_sg: 0.0, k_tg: 0.0, gauss_constant: 5.0, roughness: 0.2, glossiness: 0.0, ior: 1.0, ambient: Vec3::one(), diffuse: Vec3 { x: 0.7, y: 0.7, z: 0.7 }, specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
+    let red = CookTorranceMaterial { diffuse: Vec3 { x: 0.8, y: 0.2, z: 0.15 }, ..grey.clone() };
+
+    let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
+    lights.push(Box::new(PointLight { position: Vec3 { x: -8.0, y: 1.5, z: 2.0 }.scale(scale), color: Vec3::one() }));
+
+    let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
+
+    let floor = [Vec3 { x: -20.0, y: 0.0, z: -20.0 }, Vec3 { x: 20.0, y: 0.0, z: -20.0 },
+                 Vec3 { x: 20.0, y: 0.0, z: 20.0 }, Vec3 { x: -20.0, y: 0.0, z: 20.0 }];
+    for &(a, b, c) in [(0, 2, 1), (0, 3, 2)].iter() {
+        let mut triopts = TriangleOptions::new(floor[a].scale(scale), floor[b].scale(scale), floor[c].scale(scale));
+        triopts.material(Box::new(grey.clone()));
+        prims.push(Box::new(triopts.build()));
+    }
+
+    for triopts in ball(Vec3 { x: -0.8, y: 1.0, z: 0.0 }.scale(scale), scale, &red) {
+        prims.push(Box::new(triopts.build()));
+    }
+
+    prims.push(Box::new(Cuboid::new(Vec3 { x: 0.8, y: 0.0, z: -0.6 }.scale(scale),
+                                    Vec3 { x: 2.0, y: 1.2, z: 0.6 }.scale(scale),
+                                    Box::new(grey.clone()))));
+
+    let accelerator = accelerator.build(prims);
+
+    Scene {
+        lights: lights,
+        accelerator: accelerator,
+        background: Vec3 { x: 0.2, y: 0.25, z: 0.3 },
+        skybox: None
+    }
+}
+
+pub struct ShadowsConfig {
+    scale: f64
+}
+
+impl ShadowsConfig {
+    pub fn new(scale: f64) -> ShadowsConfig {
+        ShadowsConfig { scale: scale }
+    }
+}
+
+impl super::SceneConfig for ShadowsConfig {
+    fn get_camera(&self, image_width: u32, image_height: u32, fov: f64) -> Camera {
+        get_camera(self.scale, image_width, image_height, fov)
+    }
+
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(self.scale, accelerator)
+    }
+}
@@ -7,6 +7,7 @@ use light::lights::{PointLight, SphereLight};
 use material::materials::{CookTorranceMaterial, FlatMaterial, PhongMaterial};
 use material::Texture;
 use material::textures::{CheckerTexture, CubeMap, UVTexture, ImageTexture};
+use raytracer::AcceleratorOptions;
 use raytracer::animator::CameraKeyframe;
 use raytracer::animator::easing::Easing;
 use scene::{Camera, Scene};
@@ -80,23 +81,21 @@ pub fn get_animation_camera(image_width: u32, image_height: u32, fov: f64) -> Ca
     camera
 }
 
-pub fn get_scene() -> Scene {
+pub fn get_scene(accelerator: AcceleratorOptions) -> Scene {
     let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
     lights.push(Box::new(SphereLight { position: Vec3 { x: 3.0, y: 10.0, z: 6.0 }, color: Vec3::one(), radius: 5.0 }));
 
     let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
     let shiny = CookTorranceMaterial { k_a: 0.0, k_d: 0.2, k_s: 1.0, k_sg: 1.0, k_tg: 0.0, gauss_constant: 5.0, roughness: 0.01, glossiness: 0.0, ior: 0.05, ambient: Vec3::one(), diffuse: Vec3 { x: 1.0, y: 1.0, z: 1.0 }, specular: Vec3 { x: 0.9, y: 0.9, z: 0.9 }, transmission: Vec3::zero(), diffuse_texture: None };
-    prims.push(Box::new(Sphere { center: Vec3::zero(), radius: 2.0, material: Box::new(shiny) }));
+    prims.push(Box::new(Sphere::new(Vec3::zero(), 2.0, Box::new(shiny))));
 
-    println!("Generating octree...");
-    let octree = prims.into_iter().collect();
-    println!("Octree generated...");
+    let accelerator = accelerator.build(prims);
 
     // For y as up
     Scene {
         lights: lights,
         background: Vec3 { x: 0.3, y: 0.5, z: 0.8 },
-        octree: octree,
+        accelerator: accelerator,
         skybox: Some(CubeMap::load(
             "./docs/assets/textures/skyboxes/storm_y_up/left.png",
             "./docs/assets/textures/skyboxes/storm_y_up/right.png",
@@ -120,7 +119,7 @@ impl super::SceneConfig for SphereConfig {
         get_animation_camera(image_width, image_height, fov)
     }
 
-    fn get_scene(&self) -> Scene {
-        get_scene()
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(accelerator)
     }
 }
\ No newline at end of file
@@ -0,0 +1,104 @@
+#![allow(unused_imports)]
+
+use std::sync::Arc;
+use geometry::prim::{Prim};
+use geometry::prims::{Instance, Plane, Sphere};
+use light::light::{Light};
+use light::lights::{SphereLight};
+use material::materials::{CookTorranceMaterial, FlatMaterial, PhongMaterial};
+use material::textures::{CubeMap};
+use mat4::{Mat4, Transform};
+use raytracer::{Accelerator, AcceleratorOptions};
+use raytracer::animator::{Pose, Track};
+use scene::{Camera, Scene};
+use vec3::Vec3;
+
+// Product spin (0s-10s animation): the teapot turns once while the light sweeps
+// around it and the sphere beside it changes colour and roughness. The keyframes
+// are in docs/animations/spin.json.
+// How far ahead the teapot's motion is worked out from each frame, which is
+// as long a shutter interval as will blur it
+const MOTION_STEP: f64 = 0.1;
+
+pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
+    Camera::new(
+        Vec3 { x: 0.0, y: 1.5, z: 4.0 },
+        Vec3 { x: 0.0, y: 0.6, z: 0.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        fov,
+        image_width,
+        image_height
+    )
+}
+
+pub struct SpinAnimation {
+    accelerator: AcceleratorOptions,
+    teapot: Arc<Accelerator+Send+Sync>,
+    teapot_pose: Track<Pose>,
+    light_position: Track<Vec3>,
+    light_color: Track<Vec3>,
+    sphere_diffuse: Track<Vec3>,
+    sphere_roughness: Track<f64>
+}
+
+impl SpinAnimation {
+    pub fn new(accelerator: AcceleratorOptions) -> SpinAnimation {
+        let porcelain = CookTorranceMaterial { k_a: 0.0, k_d: 0.9, k_s: 1.0, k_sg: 1.0, k_tg: 0.0, gauss_constant: 5.0, roughness: 0.1, glossiness: 0.0, ior: 1.1, ambient: Vec3::one(), diffuse: Vec3 { x: 0.9, y: 0.85, z: 0.7 }, specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
+        let teapot = ::util::import::from_obj(porcelain, false, "./docs/assets/models/teapot.obj").ok().expect("failed to load obj model");
+        let teapot = Arc::from(accelerator.build_for_model(teapot.triangles, "./docs/assets/models/teapot.obj"));
+
+        let tracks = ::util::import::from_tracks("./docs/animations/spin.json").ok().expect("failed to load keyframe tracks");
+
+        SpinAnimation {
+            accelerator: accelerator,
+            teapot: teapot,
+            teapot_pose: tracks.track("teapot_pose").expect("bad keyframe track"),
+            light_position: tracks.track("light_position").expect("bad keyframe track"),
+            light_color: tracks.track("light_color").expect("bad keyframe track"),
+            sphere_diffuse: tracks.track("sphere_diffuse").expect("bad keyframe track"),
+            sphere_roughness: tracks.track("sphere_roughness").expect("bad keyframe track")
+        }
+    }
+}
+
+impl super::SceneAnimation for SpinAnimation {
+    fn scene_at_time(&self, time: f64) -> Scene {
+        let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
+        lights.push(Box::new(SphereLight { position: self.light_position.at(time), color: self.light_color.at(time), radius: 1.0 }));
+
+        let grey = CookTorranceMaterial { k_a: 0.0, k_d: 0.9, k_s: 1.0, k_sg: 0.2, k_tg: 0.0, gauss_constant: 5.0, roughness: 0.3, glossiness: 0.0, ior: 1.0, ambient: Vec3::one(), diffuse: Vec3 { x: 0.6, y: 0.6, z: 0.6 }, specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
+        let shiny = CookTorranceMaterial { k_a: 0.0, k_d: 0.9, k_s: 1.0, k_sg: 1.0, k_tg: 0.0, gauss_constant: 5.0, roughness: self.sphere_roughness.at(time), glossiness: 0.0, ior: 1.5, ambient: Vec3::one(), diffuse: self.sphere_diffuse.at(time), specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
+
+        let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
+        prims.push(Box::new(Plane { a: 0.0, b: 1.0, c: 0.0, d: 0.0, material: Box::new(grey) }));
+        prims.push(Box::new(Sphere::new(Vec3 { x: 1.6, y: 0.5, z: -0.5 }, 0.5, Box::new(shiny))));
+        // Moving through the frame, so a `shutter` interval in the config blurs it
+        let end_time = time + MOTION_STEP;
+        prims.push(Box::new(Instance::moving(self.teapot.clone(),
+                                             (time, self.teapot_pose.at(time).transform()),
+                                             (end_time, self.teapot_pose.at(end_time).transform()))));
+
+        Scene {
+            lights: lights,
+            accelerator: self.accelerator.build(prims),
+            background: Vec3 { x: 0.3, y: 0.5, z: 0.8 },
+            skybox: None
+        }
+    }
+}
+
+pub struct SpinConfig;
+
+impl super::SceneConfig for SpinConfig {
+    fn get_camera(&self, image_width: u32, image_height: u32, fov: f64) -> Camera {
+        get_camera(image_width, image_height, fov)
+    }
+
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        super::SceneAnimation::scene_at_time(&SpinAnimation::new(accelerator), 0.0)
+    }
+
+    fn get_animation(&self, accelerator: AcceleratorOptions) -> Option<Box<super::SceneAnimation>> {
+        Some(Box::new(SpinAnimation::new(accelerator)))
+    }
+}
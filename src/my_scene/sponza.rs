@@ -7,6 +7,7 @@ use light::lights::{PointLight, SphereLight};
 use material::materials::{CookTorranceMaterial, FlatMaterial, PhongMaterial};
 use material::Texture;
 use material::textures::{CheckerTexture, CubeMap, UVTexture, ImageTexture};
+use raytracer::AcceleratorOptions;
 use raytracer::animator::CameraKeyframe;
 use raytracer::compositor::ColorRGBA;
 use scene::{Camera, Scene};
@@ -24,11 +25,25 @@ pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
     )
 }
 
-pub fn get_scene() -> Scene {
+fn get_lights() -> Vec<Box<Light+Send+Sync>> {
     let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
     lights.push(Box::new(SphereLight { position: Vec3 { x: 0.0, y: 3000.0, z: 1000.0 }, color: Vec3 { x: 1.0, y: 0.8, z: 0.4 }, radius: 50.0 }));
     lights.push(Box::new(SphereLight { position: Vec3 { x: 300.0, y: 300.0, z: 60.0 }, color: Vec3 { x: 0.38, y: 0.32, z: 0.28 }, radius: 20.0 }));
+    lights
+}
+
+fn get_skybox() -> CubeMap {
+    CubeMap::load(
+        "./docs/assets/textures/skyboxes/storm_y_up/left.png",
+        "./docs/assets/textures/skyboxes/storm_y_up/right.png",
+        "./docs/assets/textures/skyboxes/storm_y_up/down.png",
+        "./docs/assets/textures/skyboxes/storm_y_up/up.png",
+        "./docs/assets/textures/skyboxes/storm_y_up/front.png",
+        "./docs/assets/textures/skyboxes/storm_y_up/back.png"
+    )
+}
 
+pub fn get_scene(accelerator: AcceleratorOptions) -> Scene {
     let checker: Box<Texture+Send+Sync> = Box::new(CheckerTexture { color1: ColorRGBA::white(), color2: ColorRGBA::new_rgb(0.15, 0.11, 0.1), scale: 32.0 });
 
     let stone     = CookTorranceMaterial { k_a: 0.1,  k_d: 0.8, k_s: 0.2, k_sg: 0.2,  k_tg: 0.0, gauss_constant: 50.0, roughness: 1.0, glossiness: 0.0, ior: 1.5, ambient: Vec3 { x: 0.88, y: 0.83, z: 0.77 }, diffuse: Vec3 { x: 0.88, y: 0.83, z: 0.77 }, specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
@@ -48,22 +63,32 @@ pub fn get_scene() -> Scene {
     let sponza_cloth = ::util::import::from_obj(cloth, false, "./docs/assets/models/sponza_cloth.obj").ok().expect("failed to load obj model");;
     for triangle in sponza_cloth.triangles.into_iter() { prims.push(triangle); }
 
-    println!("Generating octree...");
-    let octree = prims.into_iter().collect();
-    println!("Octree generated...");
+    let accelerator = accelerator.build_for_model(prims, "./docs/assets/models/sponza_other.obj");
+
+    Scene {
+        lights: get_lights(),
+        accelerator: accelerator,
+        background: Vec3 { x: 0.84, y: 0.34, z: 0.0 },
+        skybox: Some(get_skybox())
+    }
+}
+
+/// Crytek Sponza in one OBJ, shaded with the materials and textures of its
+/// MTL file. Not in the assets repository: expects `sponza.obj`, `sponza.mtl`
+/// and the textures in `docs/assets/models/sponza/`.
+pub fn get_mtl_scene(accelerator: AcceleratorOptions) -> Scene {
+    // Only faces without a material of their own use this
+    let stone = CookTorranceMaterial { k_a: 0.1, k_d: 0.8, k_s: 0.2, k_sg: 0.2, k_tg: 0.0, gauss_constant: 50.0, roughness: 1.0, glossiness: 0.0, ior: 1.5, ambient: Vec3 { x: 0.88, y: 0.83, z: 0.77 }, diffuse: Vec3 { x: 0.88, y: 0.83, z: 0.77 }, specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
+
+    let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
+    let sponza = ::util::import::from_obj_mesh(Box::new(stone), false, accelerator.cache, "./docs/assets/models/sponza/sponza.obj").ok().expect("failed to load obj model");
+    prims.push(Box::new(sponza));
 
     Scene {
-        lights: lights,
-        octree: octree,
+        lights: get_lights(),
+        accelerator: accelerator.build(prims),
         background: Vec3 { x: 0.84, y: 0.34, z: 0.0 },
-        skybox: Some(CubeMap::load(
-            "./docs/assets/textures/skyboxes/storm_y_up/left.png",
-            "./docs/assets/textures/skyboxes/storm_y_up/right.png",
-            "./docs/assets/textures/skyboxes/storm_y_up/down.png",
-            "./docs/assets/textures/skyboxes/storm_y_up/up.png",
-            "./docs/assets/textures/skyboxes/storm_y_up/front.png",
-            "./docs/assets/textures/skyboxes/storm_y_up/back.png"
-        ))
+        skybox: Some(get_skybox())
     }
 }
 
@@ -73,8 +98,20 @@ impl super::SceneConfig for SponzaConfig {
     fn get_camera(&self, image_width: u32, image_height: u32, fov: f64) -> Camera {
         get_camera(image_width, image_height, fov)
     }
-    
-    fn get_scene(&self) -> Scene {
-        get_scene()
+
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(accelerator)
     }
-}
\ No newline at end of file
+}
+
+pub struct SponzaMtlConfig;
+
+impl super::SceneConfig for SponzaMtlConfig {
+    fn get_camera(&self, image_width: u32, image_height: u32, fov: f64) -> Camera {
+        get_camera(image_width, image_height, fov)
+    }
+
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_mtl_scene(accelerator)
+    }
+}
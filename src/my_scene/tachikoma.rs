@@ -1,14 +1,17 @@
 #![allow(unused_imports)]
 
+use geometry::bbox::{union_bbox, BBox};
 use geometry::prim::{Prim};
 use geometry::prims::{Plane, Sphere, Triangle};
 use light::light::{Light};
 use light::lights::{PointLight, SphereLight};
-use material::materials::{CookTorranceMaterial, FlatMaterial, PhongMaterial};
+use material::materials::{CookTorranceMaterial, FlatMaterial, PhongMaterial, SharedMaterial};
 use material::Texture;
 use material::textures::{CheckerTexture, CubeMap, UVTexture, ImageTexture};
+use mat4::{Mat4, Transform};
+use raytracer::AcceleratorOptions;
 use raytracer::animator::CameraKeyframe;
-use scene::{Camera, Scene};
+use scene::{Camera, Geometry, Scene, SceneNode};
 use vec3::Vec3;
 
 pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
@@ -22,7 +25,7 @@ pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
     )
 }
 
-pub fn get_scene() -> Scene {
+pub fn get_scene(accelerator: AcceleratorOptions) -> Scene {
     let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
     lights.push(Box::new(SphereLight { position: Vec3 { x: 0.0, y: 100.0, z: 0.0 }, color: Vec3 { x: 1.0, y: 1.0, z: 1.0 }, radius: 25.0 }));
 
@@ -32,16 +35,26 @@ pub fn get_scene() -> Scene {
     let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
     prims.push(Box::new(Plane { a: 0.0, b: 1.0, c: 0.0, d: 0.0, material: Box::new(floor.clone()) })); // Bottom
 
-    let tachikoma = ::util::import::from_obj(blue, false, "./docs/assets/models/tachikoma.obj").ok().expect("failed to load obj model");;
-    for triangle in tachikoma.triangles.into_iter() { prims.push(triangle); }
+    let mut root = SceneNode::new("scene");
+    root.geometry.push(Geometry::Prims(prims));
 
-    println!("Generating octree...");
-    let octree = prims.into_iter().collect();
-    println!("Octree generated...");
+    let groups = ::util::import::from_obj_groups(false, "./docs/assets/models/tachikoma.obj").ok().expect("failed to load obj model");
+    {
+        let tachikoma = root.add_child(SceneNode::new("tachikoma"));
+        tachikoma.material = Some(SharedMaterial::new(blue));
+        for (name, triangles) in groups.into_iter() {
+            let part = tachikoma.add_child(SceneNode::new(&name));
+            part.geometry.push(Geometry::Triangles(triangles));
+        }
+    }
+    pose_legs(&mut root, LEG_SWING_DEG);
+
+    let prims = root.flatten();
+    let accelerator = accelerator.build_for_model(prims, "./docs/assets/models/tachikoma.obj");
 
     Scene {
         lights: lights,
-        octree: octree,
+        accelerator: accelerator,
         background: Vec3 { x: 0.2, y: 0.2, z: 0.2 },
         // skybox: None
         skybox: Some(CubeMap::load(
@@ -55,6 +68,47 @@ pub fn get_scene() -> Scene {
     }
 }
 
+/// Swing of each leg about its hip, alternating direction between legs
+const LEG_SWING_DEG: f64 = 15.0;
+
+/// Whether a group of the model is a leg: `leg` as a word of its own in the
+/// group name, as in `Leg_FL` or `leg.003`
+fn is_leg(name: &str) -> bool {
+    name.split(|c: char| !c.is_alphanumeric()).any(|word| word.eq_ignore_ascii_case("leg"))
+}
+
+/// Turns every leg about the vertical axis through the top of the part,
+/// leaving the feet on the floor. A model without leg groups is left
+/// unposed, with a warning naming the groups it does have.
+fn pose_legs(root: &mut SceneNode, swing_deg: f64) {
+    let tachikoma = root.find_mut("tachikoma").unwrap();
+    if !tachikoma.children.iter().any(|part| is_leg(&part.name)) {
+        let names: Vec<&str> = tachikoma.children.iter().map(|part| &part.name[..]).collect();
+        eprintln!("No leg groups to pose in the tachikoma model, found {:?}", names);
+        return;
+    }
+    let legs = tachikoma.children.iter_mut().filter(|part| is_leg(&part.name));
+
+    for (i, leg) in legs.enumerate() {
+        let bbox = leg.geometry.iter().fold(None, |bbox: Option<BBox>, geometry| {
+            match *geometry {
+                Geometry::Triangles(ref triangles) => triangles.iter().fold(bbox, |bbox, triangle| {
+                    let triangle_bbox = triangle.bounding_box();
+                    Some(bbox.map_or(triangle_bbox, |bbox| union_bbox(&bbox, &triangle_bbox)))
+                }),
+                _ => bbox
+            }
+        });
+
+        if let Some(bbox) = bbox {
+            let hip = Vec3 { x: (bbox.min.x + bbox.max.x) * 0.5, y: bbox.max.y, z: (bbox.min.z + bbox.max.z) * 0.5 };
+            let angle = if i % 2 == 0 { swing_deg } else { -swing_deg };
+            let pose = Mat4::mult_m(&Mat4::translate_matrix(&hip), &Mat4::mult_m(&Mat4::rotate_y_deg_matrix(angle), &Mat4::translate_matrix(&(hip * -1.0))));
+            leg.transform = Transform::new(pose);
+        }
+    }
+}
+
 pub struct TachikomaConfig;
 
 impl super::SceneConfig for TachikomaConfig {
@@ -62,7 +116,7 @@ impl super::SceneConfig for TachikomaConfig {
         get_camera(image_width, image_height, fov)
     }
 
-    fn get_scene(&self) -> Scene {
-        get_scene()
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(accelerator)
     }
 }
\ No newline at end of file
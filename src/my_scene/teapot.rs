@@ -8,6 +8,7 @@ use material::materials::{CookTorranceMaterial, FlatMaterial, PhongMaterial};
 use material::Texture;
 use material::textures::{CheckerTexture, CubeMap, UVTexture, ImageTexture};
 use mat4::{Mat4, Transform};
+use raytracer::AcceleratorOptions;
 use raytracer::animator::CameraKeyframe;
 use scene::{Camera, Scene};
 use vec3::Vec3;
@@ -27,7 +28,7 @@ pub fn get_teapot_camera(image_width: u32, image_height: u32, fov: f64) -> Camer
     )
 }
 
-pub fn get_teapot_scene() -> Scene {
+pub fn get_teapot_scene(accelerator: AcceleratorOptions) -> Scene {
     let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
     lights.push(Box::new(SphereLight { position: Vec3 { x: 0.6, y: 2.0, z: 1.2 }, color: Vec3::one(), radius: 1.0 }));
 
@@ -40,13 +41,11 @@ pub fn get_teapot_scene() -> Scene {
     teapot.mut_transform(&rotate);
     for triangle in teapot.triangles.into_iter() { prims.push(triangle); }
 
-    println!("Generating octree...");
-    let octree = prims.into_iter().collect();
-    println!("Octree generated...");
+    let accelerator = accelerator.build_for_model(prims, "./docs/assets/models/teapot.obj");
 
     Scene {
         lights: lights,
-        octree: octree,
+        accelerator: accelerator,
         background: Vec3 { x: 0.3, y: 0.5, z: 0.8 },
         skybox: Some(CubeMap::load(
             "./docs/assets/textures/skyboxes/miramar_y_up/left.png",
@@ -67,7 +66,7 @@ impl super::SceneConfig for TeapotConfig {
         get_teapot_camera(image_width, image_height, fov)
     }
 
-    fn get_scene(&self) -> Scene {
-        get_teapot_scene()
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_teapot_scene(accelerator)
     }
 }
\ No newline at end of file
@@ -0,0 +1,81 @@
+#![allow(unused_imports)]
+
+use std::sync::Arc;
+use geometry::prim::{Prim};
+use geometry::prims::{Instance, Plane};
+use light::light::{Light};
+use light::lights::{SphereLight};
+use material::materials::{CookTorranceMaterial, FlatMaterial, PhongMaterial};
+use material::textures::{CubeMap};
+use mat4::{Mat4, Transform};
+use raytracer::{Accelerator, AcceleratorOptions};
+use scene::{Camera, Scene};
+use vec3::Vec3;
+
+// A grid of teapots sharing one mesh and its acceleration structure
+pub fn get_teapots_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
+    Camera::new(
+        Vec3 { x: -1.0, y: 6.0, z: 12.0 },
+        Vec3 { x: 0.0, y: 0.5, z: 0.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        fov,
+        image_width,
+        image_height
+    )
+}
+
+pub fn get_teapots_scene(accelerator: AcceleratorOptions) -> Scene {
+    let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
+    lights.push(Box::new(SphereLight { position: Vec3 { x: 3.0, y: 10.0, z: 6.0 }, color: Vec3::one(), radius: 2.0 }));
+
+    let porcelain = CookTorranceMaterial { k_a: 0.0, k_d: 0.9, k_s: 1.0, k_sg: 1.0, k_tg: 0.0, gauss_constant: 5.0, roughness: 0.1, glossiness: 0.0, ior: 1.1, ambient: Vec3::one(), diffuse: Vec3 { x: 0.9, y: 0.85, z: 0.7 }, specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
+    let grey = PhongMaterial { k_a: 0.0, k_d: 0.6, k_s: 0.4, k_sg: 0.4, k_tg: 0.0, shininess: 10.0, glossiness: 0.0, ior: 1.0, ambient: Vec3::one(), diffuse: Vec3 { x: 0.6, y: 0.6, z: 0.6 }, specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
+
+    let mut teapot = ::util::import::from_obj(porcelain, false, "./docs/assets/models/teapot.obj").ok().expect("failed to load obj model");
+    // Same orientation as the teapot scene so the two can share a cached structure
+    teapot.mut_transform(&Transform::new(Mat4::rotate_x_deg_matrix(1.0)));
+    let teapot: Arc<Accelerator+Send+Sync> = Arc::from(accelerator.build_for_model(teapot.triangles, "./docs/assets/models/teapot.obj"));
+
+    let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
+    prims.push(Box::new(Plane { a: 0.0, b: 1.0, c: 0.0, d: 0.0, material: Box::new(grey) }));
+
+    for i in 0..7 {
+        for j in 0..7 {
+            let n = i * 7 + j;
+            let translate = Mat4::translate_matrix(&Vec3 { x: (i as f64 - 3.0) * 2.5, y: 0.0, z: (j as f64 - 3.0) * 2.5 });
+            let rotate = Mat4::rotate_y_deg_matrix(n as f64 * 37.0);
+            let scale = Mat4::scale_matrix(&Vec3::one().scale(0.8 + 0.1 * (n % 5) as f64));
+            let m = Mat4::mult_m(&translate, &Mat4::mult_m(&rotate, &scale));
+            prims.push(Box::new(Instance::new(teapot.clone(), Transform::new(m))));
+        }
+    }
+
+    let accelerator = accelerator.build(prims);
+
+    Scene {
+        lights: lights,
+        accelerator: accelerator,
+        background: Vec3 { x: 0.3, y: 0.5, z: 0.8 },
+        skybox: Some(CubeMap::load(
+            "./docs/assets/textures/skyboxes/miramar_y_up/left.png",
+            "./docs/assets/textures/skyboxes/miramar_y_up/right.png",
+            "./docs/assets/textures/skyboxes/miramar_y_up/down.png",
+            "./docs/assets/textures/skyboxes/miramar_y_up/up.png",
+            "./docs/assets/textures/skyboxes/miramar_y_up/front.png",
+            "./docs/assets/textures/skyboxes/miramar_y_up/back.png"
+        ))
+    }
+}
+
+
+pub struct TeapotsConfig;
+
+impl super::SceneConfig for TeapotsConfig {
+    fn get_camera(&self, image_width: u32, image_height: u32, fov: f64) -> Camera {
+        get_teapots_camera(image_width, image_height, fov)
+    }
+
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_teapots_scene(accelerator)
+    }
+}
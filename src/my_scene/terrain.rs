@@ -0,0 +1,92 @@
+#![allow(unused_imports)]
+
+use std::f64::consts::PI;
+use geometry::prim::{Prim};
+use geometry::prims::{Heightfield, Plane};
+use light::light::{Light};
+use light::lights::{SphereLight};
+use material::materials::{CookTorranceMaterial};
+use material::textures::{CubeMap};
+use mat4::{Mat4, Transform};
+use raytracer::AcceleratorOptions;
+use scene::{Camera, Scene};
+use vec3::Vec3;
+
+// Rolling hills, with water in the valleys, under a skybox. The heights are
+// worked out here rather than loaded, so only the skybox comes from assets.
+const SAMPLES: usize = 256;
+
+/// Overlapping waves over [0, 1] x [0, 1], sinking towards the edges so the
+/// hills end in water
+fn hill_heights() -> Vec<f32> {
+    let mut heights = Vec::with_capacity(SAMPLES * SAMPLES);
+    for row in 0..SAMPLES {
+        for column in 0..SAMPLES {
+            let x = column as f64 / (SAMPLES - 1) as f64;
+            let z = row as f64 / (SAMPLES - 1) as f64;
+
+            // Between -1 and 1
+            let waves = 0.6 * (x * 7.0).sin() * (z * 5.0 + 1.0).cos()
+                      + 0.3 * (x * 13.0 + z * 11.0).sin()
+                      + 0.1 * ((x - z) * 29.0).cos();
+            let falloff = ((x * PI).sin() * (z * PI).sin()).sqrt();
+            heights.push((0.5 + 0.5 * waves) as f32 * falloff as f32);
+        }
+    }
+    heights
+}
+
+pub fn get_camera(image_width: u32, image_height: u32, fov: f64) -> Camera {
+    Camera::new(
+        Vec3 { x: 0.0, y: 9.0, z: 28.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        fov,
+        image_width,
+        image_height
+    )
+}
+
+pub fn get_scene(accelerator: AcceleratorOptions) -> Scene {
+    let grass = CookTorranceMaterial { k_a: 0.0, k_d: 1.0, k_s: 0.1, k_sg: 0.0, k_tg: 0.0, gauss_constant: 5.0, roughness: 0.6, glossiness: 0.0, ior: 1.0, ambient: Vec3::one(), diffuse: Vec3 { x: 0.35, y: 0.45, z: 0.2 }, specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
+    let water = CookTorranceMaterial { k_a: 0.0, k_d: 0.3, k_s: 1.0, k_sg: 0.6, k_tg: 0.0, gauss_constant: 5.0, roughness: 0.05, glossiness: 0.0, ior: 1.33, ambient: Vec3::one(), diffuse: Vec3 { x: 0.1, y: 0.2, z: 0.3 }, specular: Vec3::one(), transmission: Vec3::zero(), diffuse_texture: None };
+
+    let mut lights: Vec<Box<Light+Send+Sync>> = Vec::new();
+    lights.push(Box::new(SphereLight { position: Vec3 { x: 30.0, y: 40.0, z: 20.0 }, color: Vec3::one(), radius: 3.0 }));
+
+    let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
+    prims.push(Box::new(Plane { a: 0.0, b: 1.0, c: 0.0, d: -0.6, material: Box::new(water) }));
+
+    let size = Vec3 { x: 40.0, y: 6.0, z: 40.0 };
+    let mut hills = Heightfield::from_heights(SAMPLES, SAMPLES, hill_heights(), size, Box::new(grass));
+    hills.mut_transform(&Transform::new(Mat4::translate_matrix(&Vec3 { x: -size.x / 2.0, y: 0.0, z: -size.z / 2.0 })));
+    prims.push(Box::new(hills));
+
+    let accelerator = accelerator.build(prims);
+
+    Scene {
+        lights: lights,
+        accelerator: accelerator,
+        background: Vec3 { x: 0.3, y: 0.5, z: 0.8 },
+        skybox: Some(CubeMap::load(
+            "./docs/assets/textures/skyboxes/miramar_y_up/left.png",
+            "./docs/assets/textures/skyboxes/miramar_y_up/right.png",
+            "./docs/assets/textures/skyboxes/miramar_y_up/down.png",
+            "./docs/assets/textures/skyboxes/miramar_y_up/up.png",
+            "./docs/assets/textures/skyboxes/miramar_y_up/front.png",
+            "./docs/assets/textures/skyboxes/miramar_y_up/back.png"
+        ))
+    }
+}
+
+pub struct TerrainConfig;
+
+impl super::SceneConfig for TerrainConfig {
+    fn get_camera(&self, image_width: u32, image_height: u32, fov: f64) -> Camera {
+        get_camera(image_width, image_height, fov)
+    }
+
+    fn get_scene(&self, accelerator: AcceleratorOptions) -> Scene {
+        get_scene(accelerator)
+    }
+}
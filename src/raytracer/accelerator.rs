@@ -0,0 +1,280 @@
+use std::collections::HashSet;
+use geometry::{BBox, Prim};
+use raytracer::{Bvh, Intersection, KdTree, Octree, Ray};
+use raytracer::cache;
+use vec3::Vec3;
+
+/// Spatial index over the scene's primitives. Scenes pick a default with
+/// `SceneConfig::default_accelerator`, which the `accelerator` config option overrides.
+#[derive(Clone, Copy, PartialEq, Debug, RustcDecodable, RustcEncodable)]
+pub enum AcceleratorKind {
+    Octree,
+    Bvh,
+    KdTree
+}
+
+impl AcceleratorKind {
+    pub fn build(self, prims: Vec<Box<Prim+Send+Sync>>) -> Box<Accelerator+Send+Sync> {
+        println!("Generating {:?}...", self);
+        let accelerator: Box<Accelerator+Send+Sync> = match self {
+            AcceleratorKind::Octree => Box::new(prims.into_iter().collect::<Octree<_>>()),
+            AcceleratorKind::Bvh => Box::new(prims.into_iter().collect::<Bvh<_>>()),
+            AcceleratorKind::KdTree => Box::new(prims.into_iter().collect::<KdTree<_>>())
+        };
+        println!("{:?} generated...", self);
+
+        accelerator
+    }
+}
+
+/// How a scene builds its acceleration structure
+#[derive(Clone, Copy)]
+pub struct AcceleratorOptions {
+    pub kind: AcceleratorKind,
+    pub cache: bool // Save built structures next to the models they index, and reuse them
+}
+
+impl AcceleratorOptions {
+    pub fn build(self, prims: Vec<Box<Prim+Send+Sync>>) -> Box<Accelerator+Send+Sync> {
+        self.kind.build(prims)
+    }
+
+    /// Like `build`, but if caching is on, loads the structure from a file next
+    /// to the model at `model_path` or saves it there once built. The file is
+    /// rebuilt whenever the scene's prims no longer match it.
+    pub fn build_for_model(self, prims: Vec<Box<Prim+Send+Sync>>, model_path: &str) -> Box<Accelerator+Send+Sync> {
+        if !self.cache {
+            return self.build(prims);
+        }
+
+        println!("Generating {:?}...", self.kind);
+        let accelerator: Box<Accelerator+Send+Sync> = match self.kind {
+            AcceleratorKind::Octree => Box::new(cache::load_or_build::<_, Octree<_>>(prims, &format!("{}.octree", model_path))),
+            AcceleratorKind::Bvh => Box::new(cache::load_or_build::<_, Bvh<_>>(prims, &format!("{}.bvh", model_path))),
+            AcceleratorKind::KdTree => Box::new(cache::load_or_build::<_, KdTree<_>>(prims, &format!("{}.kdtree", model_path)))
+        };
+        println!("{:?} generated...", self.kind);
+
+        accelerator
+    }
+}
+
+pub trait Accelerator {
+    /// Bounds of everything in the structure, `None` if it holds infinite prims
+    fn bounds(&self) -> Option<BBox>;
+
+    /// Nearest intersection strictly between t_min and t_max
+    fn nearest_hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>>;
+
+    /// Every primitive whose bounds the ray may pass through, in no particular order,
+    /// with an index unique to it within the structure. Primitives may be returned
+    /// more than once.
+    fn candidates<'a>(&'a self, ray: &'a Ray) -> Box<Iterator<Item=(usize, &'a Box<Prim+Send+Sync>)> + 'a>;
+
+    /// Fraction of light that passes along the ray between t_min and t_max.
+    /// Stops at the first opaque occluder, otherwise multiplies together the
+    /// transmission of every translucent one.
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec3 {
+        let mut transmittance = Vec3::one();
+        // Backends may return a prim once per node it overlaps, only count it once
+        let mut translucent: HashSet<usize> = HashSet::new();
+
+        for (index, prim) in self.candidates(ray) {
+            let occlusion = match prim.intersects(ray, t_min, t_max) {
+                Some(occlusion) => occlusion,
+                None => continue
+            };
+
+            let transmission = occlusion.material.transmission();
+            if transmission.x <= 0.0 && transmission.y <= 0.0 && transmission.z <= 0.0 {
+                return Vec3::zero();
+            }
+
+            if translucent.insert(index) {
+                transmittance = transmittance * transmission;
+            }
+        }
+
+        transmittance
+    }
+}
+
+impl Accelerator for Octree<Box<Prim+Send+Sync>> {
+    fn bounds(&self) -> Option<BBox> {
+        self.bounds()
+    }
+
+    fn nearest_hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        let mut nearest_hit = None;
+        let mut nearest_t = t_max;
+
+        self.traverse(ray, |prim| {
+            if let Some(t) = nearer_hit(prim, ray, t_min, nearest_t, &mut nearest_hit) {
+                nearest_t = t;
+            }
+        });
+
+        nearest_hit
+    }
+
+    fn candidates<'a>(&'a self, ray: &'a Ray) -> Box<Iterator<Item=(usize, &'a Box<Prim+Send+Sync>)> + 'a> {
+        Box::new(self.intersect_iter(ray))
+    }
+}
+
+impl Accelerator for Bvh<Box<Prim+Send+Sync>> {
+    fn bounds(&self) -> Option<BBox> {
+        self.bounds()
+    }
+
+    fn nearest_hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        let mut nearest_hit = None;
+        self.traverse(ray, t_max, |prim, nearest_t| nearer_hit(prim, ray, t_min, nearest_t, &mut nearest_hit));
+        nearest_hit
+    }
+
+    fn candidates<'a>(&'a self, ray: &'a Ray) -> Box<Iterator<Item=(usize, &'a Box<Prim+Send+Sync>)> + 'a> {
+        Box::new(self.intersect_iter(ray))
+    }
+}
+
+impl Accelerator for KdTree<Box<Prim+Send+Sync>> {
+    fn bounds(&self) -> Option<BBox> {
+        self.bounds()
+    }
+
+    fn nearest_hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection<'a>> {
+        let mut nearest_hit = None;
+        self.traverse(ray, t_max, |prim, nearest_t| nearer_hit(prim, ray, t_min, nearest_t, &mut nearest_hit));
+        nearest_hit
+    }
+
+    fn candidates<'a>(&'a self, ray: &'a Ray) -> Box<Iterator<Item=(usize, &'a Box<Prim+Send+Sync>)> + 'a> {
+        Box::new(self.intersect_iter(ray))
+    }
+}
+
+/// Traversal visitor shared by the backends: records the prim's hit
+/// if it is nearer than the current one and returns its distance.
+fn nearer_hit<'a>(prim: &'a Box<Prim+Send+Sync>, ray: &Ray, t_min: f64, t_max: f64,
+                  nearest_hit: &mut Option<Intersection<'a>>) -> Option<f64> {
+    match prim.intersects(ray, t_min, t_max) {
+        Some(intersection) => {
+            if intersection.t > t_min && intersection.t < t_max {
+                let t = intersection.t;
+                *nearest_hit = Some(intersection);
+                Some(t)
+            } else {
+                None
+            }
+        },
+        None => None
+    }
+}
+
+#[cfg(test)]
+use geometry::prims::{Plane, Sphere};
+#[cfg(test)]
+use material::materials::{CookTorranceMaterial, FlatMaterial};
+
+#[cfg(test)]
+fn get_test_prims() -> Vec<Box<Prim+Send+Sync>> {
+    let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
+    prims.push(Box::new(Plane { a: 0.0, b: 1.0, c: 0.0, d: 2.0, material: Box::new(FlatMaterial { color: Vec3::one() }) }));
+    for i in 0..64 {
+        prims.push(Box::new(Sphere::new(
+            Vec3 { x: (i % 4) as f64 * 3.0, y: (i / 4 % 4) as f64 * 3.0, z: (i / 16) as f64 * 3.0 },
+            1.0,
+            Box::new(FlatMaterial { color: Vec3::one() })
+        )));
+    }
+    prims
+}
+
+/// The `i`th of a fixed spread of rays aimed at the middle of the test prims
+#[cfg(test)]
+fn get_test_ray(i: usize) -> Ray {
+    let spread = Vec3 { x: (i % 10) as f64 - 4.5, y: (i / 10 % 10) as f64 - 4.5, z: (i % 3) as f64 - 1.0 };
+    let origin = Vec3 { x: 4.5, y: 4.5, z: 20.0 } + spread.scale(2.0);
+    Ray::new(origin, (Vec3 { x: 4.5, y: 4.5, z: 4.5 } - origin).unit())
+}
+
+#[test]
+fn it_agrees_across_backends() {
+    let accelerators: Vec<Box<Accelerator+Send+Sync>> = [AcceleratorKind::Octree, AcceleratorKind::Bvh, AcceleratorKind::KdTree]
+        .iter().map(|kind| kind.build(get_test_prims())).collect();
+
+    for i in 0..100 {
+        let ray = get_test_ray(i);
+
+        let hits: Vec<Option<f64>> = accelerators.iter()
+            .map(|accelerator| accelerator.nearest_hit(&ray, 0.000001, ::std::f64::INFINITY).map(|hit| hit.t))
+            .collect();
+        assert_eq!(hits[0], hits[1], "ray {}", i);
+        assert_eq!(hits[0], hits[2], "ray {}", i);
+
+        // Every prim is opaque, so any hit blocks the ray
+        for accelerator in accelerators.iter() {
+            assert_eq!(hits[0].is_some(), accelerator.transmittance(&ray, 0.000001, ::std::f64::INFINITY) == Vec3::zero());
+        }
+    }
+}
+
+#[test]
+fn it_limits_transmittance_to_the_range() {
+    let accelerator = AcceleratorKind::Bvh.build(get_test_prims());
+    let ray = Ray::new(Vec3 { x: -5.0, y: 0.0, z: 0.0 }, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+
+    assert_eq!(Vec3::one(), accelerator.transmittance(&ray, 0.0, 3.9));
+    assert_eq!(Vec3::zero(), accelerator.transmittance(&ray, 0.0, 4.1));
+}
+
+#[test]
+fn it_stops_at_opaque_occluders() {
+    let glass = CookTorranceMaterial { k_a: 0.0, k_d: 0.0, k_s: 1.0, k_sg: 1.0, k_tg: 1.0, gauss_constant: 5.0, roughness: 0.01, glossiness: 0.0, ior: 1.5, ambient: Vec3::one(), diffuse: Vec3::one(), specular: Vec3::one(), transmission: Vec3 { x: 0.5, y: 0.5, z: 0.5 }, diffuse_texture: None };
+
+    for kind in [AcceleratorKind::Octree, AcceleratorKind::Bvh, AcceleratorKind::KdTree].iter() {
+        let mut prims = get_test_prims();
+        // Large enough to span several octree and k-d tree nodes
+        prims.push(Box::new(Sphere::new(Vec3 { x: -20.0, y: 3.0, z: 3.0 }, 8.0, Box::new(glass.clone()))));
+        let accelerator = kind.build(prims);
+
+        let through_glass = Ray::new(Vec3 { x: -40.0, y: 3.0, z: 3.0 }, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+        assert_eq!(Vec3 { x: 0.5, y: 0.5, z: 0.5 }, accelerator.transmittance(&through_glass, 0.000001, 30.0), "{:?}", kind);
+        assert_eq!(Vec3::zero(), accelerator.transmittance(&through_glass, 0.000001, 50.0), "{:?}", kind);
+
+        let clear = Ray::new(Vec3 { x: -40.0, y: 30.0, z: 4.5 }, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+        assert_eq!(Vec3::one(), accelerator.transmittance(&clear, 0.000001, 50.0), "{:?}", kind);
+    }
+}
+
+#[test]
+fn it_reuses_cached_structures() {
+    let model_path = ::std::env::temp_dir().join(format!("accelerator-cache-{}.obj", ::std::process::id()));
+    let model_path = model_path.to_str().unwrap();
+
+    for kind in [AcceleratorKind::Octree, AcceleratorKind::Bvh, AcceleratorKind::KdTree].iter() {
+        let options = AcceleratorOptions { kind: *kind, cache: true };
+        let uncached = kind.build(get_test_prims());
+        let saved = options.build_for_model(get_test_prims(), model_path);
+        let loaded = options.build_for_model(get_test_prims(), model_path);
+
+        for i in 0..50 {
+            let ray = get_test_ray(i);
+            let expected = uncached.nearest_hit(&ray, 0.000001, ::std::f64::INFINITY).map(|hit| hit.t);
+
+            assert_eq!(expected, saved.nearest_hit(&ray, 0.000001, ::std::f64::INFINITY).map(|hit| hit.t), "{:?} ray {}", kind, i);
+            assert_eq!(expected, loaded.nearest_hit(&ray, 0.000001, ::std::f64::INFINITY).map(|hit| hit.t), "{:?} ray {}", kind, i);
+        }
+
+        // Stale caches are rebuilt rather than trusted
+        let mut changed = get_test_prims();
+        changed.truncate(10);
+        let rebuilt = options.build_for_model(changed, model_path);
+        let ray = Ray::new(Vec3 { x: 9.0, y: 9.0, z: 20.0 }, Vec3 { x: 0.0, y: 0.0, z: -1.0 });
+        assert!(rebuilt.nearest_hit(&ray, 0.000001, ::std::f64::INFINITY).is_none(), "{:?}", kind);
+
+        let extension = format!("{:?}", kind).to_lowercase();
+        ::std::fs::remove_file(format!("{}.{}", model_path, extension)).unwrap();
+    }
+}
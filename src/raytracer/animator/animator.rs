@@ -1,24 +1,25 @@
-use raytracer::animator::CameraKeyframe;
-use raytracer::Renderer;
+use raytracer::{Renderer, Stereo};
 use scene::{Camera, Scene};
 use std::sync::mpsc::sync_channel;
 use std::sync::Arc;
 use std::thread;
-use vec3::Vec3;
 
 pub struct Animator {
     pub fps: f64,
     pub animate_from: f64, // Number of frames is rounded down to nearest frame
     pub animate_to: f64,
     pub starting_frame_number: u32, // For filename
-    pub renderer: Renderer
+    pub renderer: Renderer,
+    pub stereo: Option<Stereo>
 }
 
 // TODO: Non-linear interpolation
 impl Animator {
     // TODO: make this a Surface iterator so both single frame and animation
     // process flows are similar
-    pub fn animate(&self, camera: Camera, shared_scene: Arc<Scene>, filename: &str) {
+    /// `scene_at_time` gives the scene for each frame, so keyframed objects,
+    /// lights and materials can be posed before the frame is rendered.
+    pub fn animate(&self, camera: Camera, scene_at_time: &Fn(f64) -> Arc<Scene>, filename: &str) {
         let animate_start = ::time::get_time();
         let length = self.animate_to - self.animate_from;
         let total_frames = (self.fps * length).floor() as u32;
@@ -34,8 +35,10 @@ impl Animator {
             for (frame_num, frame_data) in frame_rx.iter().enumerate() {
                 let file_frame_number = starting_frame_number as usize + frame_num;
 
-                let shared_name = format!("{}{:06}.ppm", filename, file_frame_number);
-                ::util::export::to_ppm(&frame_data, &shared_name).expect("ppm write failure");
+                for &(ref suffix, ref surface) in &frame_data {
+                    let shared_name = format!("{}{}{:06}.ppm", filename, suffix, file_frame_number);
+                    ::util::export::to_ppm(surface, &shared_name).expect("ppm write failure");
+                }
             }
 
             exit_tx.send(()).unwrap();
@@ -44,7 +47,11 @@ impl Animator {
         for frame_number in 0..total_frames {
             let time = self.animate_from + f64::from(frame_number) / self.fps;
             let lerped_camera = Animator::lerp_camera(&camera, time);
-            let frame_data = self.renderer.render(lerped_camera, Arc::clone(&shared_scene));
+            let shared_scene = scene_at_time(time);
+            let frame_data: Vec<(&str, _)> = match self.stereo {
+                Some(ref stereo) => stereo.render(&self.renderer, lerped_camera, Arc::clone(&shared_scene)),
+                None => vec![("", self.renderer.render(lerped_camera, Arc::clone(&shared_scene)))]
+            };
             frame_tx.send(frame_data).unwrap();
 
             ::util::print_progress("*** Frame", animate_start, frame_number as usize + 1usize, total_frames as usize);
@@ -55,67 +62,22 @@ impl Animator {
         exit_rx.recv().unwrap();
     }
 
-    fn get_neighbour_keyframes(keyframes: Vec<CameraKeyframe>, time: f64)
-                               -> (CameraKeyframe, CameraKeyframe, f64) {
-
-        if keyframes.len() <= 1 {
-            panic!("Not enough keyframes to interpolate: got: {} expected: >= 2", keyframes.len());
-        }
-
-        // Get the two keyframes inbetween current time
-        let mut first = &keyframes[0];
-        let mut second = &keyframes[1];
-
-        for keyframe in &keyframes {
-            if keyframe.time <= time && time - keyframe.time >= first.time - time {
-                first = keyframe;
-            }
-
-            if keyframe.time > time &&
-               (keyframe.time - time < second.time - time || second.time < time) {
-                second = keyframe;
-            }
-        }
-
-        let keyframe_length = second.time - first.time;
-
-        let alpha = if keyframe_length == 0.0 {
-            0.0
-        } else {
-            second.easing.t((time - first.time) / keyframe_length)
-        };
-
-        (first.clone(), second.clone(), alpha)
-    }
-
     fn lerp_camera(camera: &Camera, time: f64) -> Camera {
-        let keyframes = match camera.keyframes.clone() {
-            Some(k) => k,
-            None => panic!("Cannot lerp a camera with no keyframes!")
-        };
-
-        let (first, second, alpha) = Animator::get_neighbour_keyframes(keyframes, time);
-
-        let lerped_position = Vec3::lerp(&first.position, &second.position, alpha);
-        let lerped_look_at  = Vec3::lerp(&first.look_at, &second.look_at, alpha);
-        let lerped_up       = Vec3::lerp(&first.up, &second.up, alpha);
-
-        let mut lerped_camera = Camera::new(
-            lerped_position,
-            lerped_look_at,
-            lerped_up,
-            camera.fov_deg,
-            camera.image_width,
-            camera.image_height,
-        );
+        // A still camera can watch an animated scene
+        if camera.keyframes.is_none() {
+            return Camera { time: time, ..camera.clone() };
+        }
 
+        let mut lerped_camera = camera.at_time(time);
         lerped_camera.keyframes = camera.keyframes.clone();
         lerped_camera
     }
 }
 
 #[cfg(test)]
-use raytracer::animator::Easing;
+use raytracer::animator::{CameraKeyframe, Easing};
+#[cfg(test)]
+use vec3::Vec3;
 
 #[test]
 fn test_lerp_camera_position() {
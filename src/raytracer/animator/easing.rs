@@ -1,6 +1,6 @@
 /// Tries to fit a curve where t is in the range [0, 1] and
 /// a is t=0, b is t=0.33.., c is t=0.66.., and d is t=1.0
-#[derive(Clone)]
+#[derive(Clone, RustcDecodable)]
 pub struct Easing {
     pub a: f64,
     pub b: f64,
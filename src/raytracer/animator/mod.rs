@@ -1,7 +1,9 @@
 pub use self::animator::Animator;
 pub use self::easing::Easing;
 pub use self::camerakeyframe::CameraKeyframe;
+pub use self::track::{Pose, Track, TrackSet};
 
 pub mod animator;
 pub mod easing;
 pub mod camerakeyframe;
+pub mod track;
@@ -0,0 +1,237 @@
+use mat4::{Mat4, Transform};
+use raytracer::animator::Easing;
+use rustc_serialize::{Decodable, Decoder};
+use rustc_serialize::json::{self, Json};
+use vec3::Vec3;
+
+/// Values that can be blended between keyframes
+pub trait Lerp {
+    fn lerp(a: &Self, b: &Self, alpha: f64) -> Self;
+}
+
+impl Lerp for f64 {
+    fn lerp(a: &f64, b: &f64, alpha: f64) -> f64 {
+        (1.0 - alpha) * a + alpha * b
+    }
+}
+
+impl Lerp for Vec3 {
+    fn lerp(a: &Vec3, b: &Vec3, alpha: f64) -> Vec3 {
+        Vec3::lerp(a, b, alpha)
+    }
+}
+
+/// An object transform split into parts that interpolate sensibly, unlike
+/// the matrix itself. Applied as scale, then rotation about x, y and z (in
+/// degrees, so a spin can go past 360), then translation.
+#[derive(Clone)]
+pub struct Pose {
+    pub translation: Vec3,
+    pub rotation_deg: Vec3,
+    pub scale: Vec3
+}
+
+impl Pose {
+    pub fn identity() -> Pose {
+        Pose {
+            translation: Vec3::zero(),
+            rotation_deg: Vec3::zero(),
+            scale: Vec3::one()
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        let rotation = Mat4::mult_m(&Mat4::rotate_z_deg_matrix(self.rotation_deg.z),
+                       &Mat4::mult_m(&Mat4::rotate_y_deg_matrix(self.rotation_deg.y),
+                                     &Mat4::rotate_x_deg_matrix(self.rotation_deg.x)));
+
+        Transform::new(Mat4::mult_m(&Mat4::translate_matrix(&self.translation),
+                       &Mat4::mult_m(&rotation, &Mat4::scale_matrix(&self.scale))))
+    }
+}
+
+/// Parts left out of a track file stay at the identity
+impl Decodable for Pose {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Pose, D::Error> {
+        d.read_struct("Pose", 3, |d| {
+            let identity = Pose::identity();
+            Ok(Pose {
+                translation: try!(d.read_struct_field("translation", 0, Option::<Vec3>::decode)).unwrap_or(identity.translation),
+                rotation_deg: try!(d.read_struct_field("rotation_deg", 1, Option::<Vec3>::decode)).unwrap_or(identity.rotation_deg),
+                scale: try!(d.read_struct_field("scale", 2, Option::<Vec3>::decode)).unwrap_or(identity.scale)
+            })
+        })
+    }
+}
+
+impl Lerp for Pose {
+    fn lerp(a: &Pose, b: &Pose, alpha: f64) -> Pose {
+        Pose {
+            translation: Vec3::lerp(&a.translation, &b.translation, alpha),
+            rotation_deg: Vec3::lerp(&a.rotation_deg, &b.rotation_deg, alpha),
+            scale: Vec3::lerp(&a.scale, &b.scale, alpha)
+        }
+    }
+}
+
+/// `easing` shapes the transition from the previous keyframe into this one,
+/// as with camera keyframes
+#[derive(Clone)]
+pub struct Keyframe<T> {
+    pub time: f64,
+    pub value: T,
+    pub easing: Easing
+}
+
+/// Keyframes without an easing are eased linearly
+impl<T: Decodable> Decodable for Keyframe<T> {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Keyframe<T>, D::Error> {
+        d.read_struct("Keyframe", 3, |d| {
+            Ok(Keyframe {
+                time: try!(d.read_struct_field("time", 0, Decodable::decode)),
+                value: try!(d.read_struct_field("value", 1, Decodable::decode)),
+                easing: try!(d.read_struct_field("easing", 2, Option::<Easing>::decode)).unwrap_or_else(Easing::linear)
+            })
+        })
+    }
+}
+
+/// Keyframed value of an object transform, light or material parameter.
+/// Holds the first value before the first keyframe and the last value after the last.
+#[derive(Clone)]
+pub struct Track<T> {
+    keyframes: Vec<Keyframe<T>>
+}
+
+impl<T: Lerp + Clone> Track<T> {
+    pub fn new(mut keyframes: Vec<Keyframe<T>>) -> Track<T> {
+        if keyframes.is_empty() {
+            panic!("A track needs at least one keyframe");
+        }
+
+        keyframes.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        Track { keyframes: keyframes }
+    }
+
+    #[allow(dead_code)]
+    pub fn constant(value: T) -> Track<T> {
+        Track::new(vec![Keyframe { time: 0.0, value: value, easing: Easing::linear() }])
+    }
+
+    pub fn at(&self, time: f64) -> T {
+        let next_index = match self.keyframes.iter().position(|keyframe| keyframe.time > time) {
+            Some(0) => return self.keyframes[0].value.clone(),
+            Some(index) => index,
+            None => return self.keyframes[self.keyframes.len() - 1].value.clone()
+        };
+
+        let previous = &self.keyframes[next_index - 1];
+        let next = &self.keyframes[next_index];
+        let alpha = next.easing.t((time - previous.time) / (next.time - previous.time));
+
+        T::lerp(&previous.value, &next.value, alpha)
+    }
+}
+
+/// Named tracks read from JSON, an object mapping each name to a list of
+/// keyframes. Each track is decoded when asked for, as the type of its
+/// values is only known to the scene using it.
+pub struct TrackSet {
+    tracks: json::Object
+}
+
+impl TrackSet {
+    pub fn from_json(data: &str) -> Result<TrackSet, String> {
+        match Json::from_str(data) {
+            Ok(Json::Object(tracks)) => Ok(TrackSet { tracks: tracks }),
+            Ok(_) => Err("Expected an object of named tracks".to_owned()),
+            Err(err) => Err(format!("{}", err))
+        }
+    }
+
+    pub fn track<T: Decodable + Lerp + Clone>(&self, name: &str) -> Result<Track<T>, String> {
+        let keyframes = match self.tracks.get(name) {
+            Some(keyframes) => keyframes.clone(),
+            None => return Err(format!("No track named `{}`", name))
+        };
+
+        let keyframes: Vec<Keyframe<T>> = match Decodable::decode(&mut json::Decoder::new(keyframes)) {
+            Ok(keyframes) => keyframes,
+            Err(err) => return Err(format!("Bad track `{}`: {:?}", name, err))
+        };
+
+        if keyframes.is_empty() {
+            return Err(format!("Track `{}` has no keyframes", name));
+        }
+
+        Ok(Track::new(keyframes))
+    }
+}
+
+#[test]
+fn test_track_holds_and_interpolates() {
+    let track = Track::new(vec![
+        Keyframe { time: 10.0, value: 4.0, easing: Easing::linear() },
+        Keyframe { time: 2.0, value: 0.0, easing: Easing::linear() },
+        Keyframe { time: 6.0, value: 2.0, easing: Easing::linear() },
+    ]);
+
+    assert_eq!(track.at(0.0), 0.0);
+    assert_eq!(track.at(4.0), 1.0);
+    assert_eq!(track.at(6.0), 2.0);
+    assert_eq!(track.at(8.0), 3.0);
+    assert_eq!(track.at(20.0), 4.0);
+
+    assert_eq!(Track::constant(Vec3::one()).at(5.0), Vec3::one());
+}
+
+#[test]
+fn test_track_uses_easing_of_next_keyframe() {
+    let ease_in = Easing { a: 0.0, b: 0.0, c: 0.0, d: 1.0 };
+    let track = Track::new(vec![
+        Keyframe { time: 0.0, value: 0.0, easing: Easing::linear() },
+        Keyframe { time: 1.0, value: 1.0, easing: ease_in },
+    ]);
+
+    assert_eq!(track.at(0.5), 0.125);
+}
+
+#[test]
+fn test_pose_transform() {
+    let pose = Pose::lerp(&Pose::identity(), &Pose {
+        translation: Vec3 { x: 0.0, y: 2.0, z: 0.0 },
+        rotation_deg: Vec3 { x: 0.0, y: 180.0, z: 0.0 },
+        scale: Vec3 { x: 3.0, y: 3.0, z: 3.0 }
+    }, 0.5);
+
+    // Scaled by 2, turned 90 degrees about y, then lifted by 1
+    let p = Mat4::mult_p(&pose.transform().m, &Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+    assert!((p - Vec3 { x: 0.0, y: 1.0, z: -2.0 }).len() < 1e-9);
+}
+
+#[test]
+fn test_track_set_decodes_tracks() {
+    let tracks = TrackSet::from_json(r#"{
+        "roughness": [
+            { "time": 0.0, "value": 0.0 },
+            { "time": 1.0, "value": 1.0, "easing": { "a": 0.0, "b": 0.0, "c": 0.0, "d": 1.0 } }
+        ],
+        "color": [{ "time": 0.0, "value": { "x": 1.0, "y": 0.5, "z": 0.0 } }],
+        "pose": [
+            { "time": 0.0, "value": {} },
+            { "time": 2.0, "value": { "rotation_deg": { "x": 0.0, "y": 360.0, "z": 0.0 } } }
+        ],
+        "empty": []
+    }"#).unwrap();
+
+    assert_eq!(tracks.track::<f64>("roughness").unwrap().at(0.5), 0.125);
+    assert_eq!(tracks.track::<Vec3>("color").unwrap().at(3.0), Vec3 { x: 1.0, y: 0.5, z: 0.0 });
+
+    let pose = tracks.track::<Pose>("pose").unwrap().at(1.0);
+    assert_eq!(pose.rotation_deg, Vec3 { x: 0.0, y: 180.0, z: 0.0 });
+    assert_eq!(pose.scale, Vec3::one());
+
+    assert!(tracks.track::<f64>("missing").is_err());
+    assert!(tracks.track::<f64>("empty").is_err());
+    assert!(tracks.track::<f64>("color").is_err());
+}
@@ -0,0 +1,461 @@
+use std::io::{self, Read, Write};
+use std::iter::{Enumerate, FromIterator};
+use std::slice::Iter;
+use std::thread;
+use geometry::{BBox, PartialBoundingBox};
+use geometry::bbox::{union_bbox, union_point};
+use raytracer::Ray;
+use raytracer::cache::{self, CachedIndex};
+
+/// Number of buckets candidate splits are binned into along the split axis
+const SAH_BUCKETS: usize = 12;
+/// Cost of visiting an interior node, relative to one primitive intersection test
+const SAH_TRAVERSAL_COST: f64 = 0.125;
+/// Nodes with this many primitives or fewer may become leaves
+const MAX_LEAF_PRIMS: usize = 4;
+/// Subtrees smaller than this are not worth handing to another thread
+const PARALLEL_MIN_PRIMS: usize = 4096;
+
+/// Bounding volume hierarchy built with the surface area heuristic (pbrt, 4.3).
+/// Nodes are stored flattened in depth-first order, so the first child of an
+/// interior node immediately follows it.
+pub struct Bvh<T> where T: PartialBoundingBox {
+    prims: Vec<T>,
+    infinites: Vec<T>, // for infinite prims (planes)
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>, // Indices into prims, each leaf covers a contiguous range
+}
+
+#[derive(Clone, Copy)]
+pub struct BvhNode {
+    bbox: BBox,
+    offset: usize, // Leaves: index of the first prim. Interior nodes: index of the second child
+    count: usize,  // Number of prims in a leaf, 0 for interior nodes
+}
+
+#[derive(Clone, Copy)]
+struct BuildPrim {
+    index: usize,
+    bbox: BBox,
+}
+
+#[derive(Clone, Copy)]
+struct SahBucket {
+    count: usize,
+    bbox: Option<BBox>,
+}
+
+impl<T> FromIterator<T> for Bvh<T> where T: PartialBoundingBox {
+    fn from_iter<I>(iterator: I) -> Self where I: IntoIterator<Item=T> {
+        let (finites, infinites): (Vec<T>, Vec<T>) =
+            iterator.into_iter().partition(|item| item.partial_bounding_box().is_some());
+
+        let build_prims: Vec<BuildPrim> = finites.iter().enumerate().map(|(i, prim)| {
+            BuildPrim { index: i, bbox: prim.partial_bounding_box().unwrap() }
+        }).collect();
+
+        let (nodes, build_prims) = if build_prims.is_empty() {
+            (Vec::new(), build_prims)
+        } else {
+            build_parallel(build_prims, 0, ::num_cpus::get())
+        };
+
+        Bvh {
+            prims: finites,
+            infinites: infinites,
+            nodes: nodes,
+            indices: build_prims.iter().map(|p| p.index).collect(),
+        }
+    }
+}
+
+impl<T> CachedIndex<T> for Bvh<T> where T: PartialBoundingBox {
+    type Index = (Vec<BvhNode>, Vec<usize>);
+
+    fn write_index<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        try!(cache::write_u64(writer, self.nodes.len() as u64));
+        for node in self.nodes.iter() {
+            try!(cache::write_bbox(writer, &node.bbox));
+            try!(cache::write_u64(writer, node.offset as u64));
+            try!(cache::write_u64(writer, node.count as u64));
+        }
+
+        try!(cache::write_u64(writer, self.indices.len() as u64));
+        for &index in self.indices.iter() {
+            try!(cache::write_u64(writer, index as u64));
+        }
+        Ok(())
+    }
+
+    fn read_index<R: Read>(reader: &mut R, finite_count: usize) -> io::Result<Self::Index> {
+        let node_count = try!(cache::read_len(reader, 2 * finite_count));
+        let mut nodes = Vec::with_capacity(node_count);
+        for i in 0..node_count {
+            let bbox = try!(cache::read_bbox(reader));
+            let offset = try!(cache::read_len(reader, finite_count.max(node_count)));
+            let count = try!(cache::read_len(reader, finite_count));
+            let in_range = if count == 0 { offset > i + 1 && offset < node_count } else { offset + count <= finite_count };
+            if !in_range {
+                return Err(cache::invalid_data("BVH node out of range"));
+            }
+            nodes.push(BvhNode { bbox: bbox, offset: offset, count: count });
+        }
+
+        let index_count = try!(cache::read_len(reader, finite_count));
+        if index_count != finite_count {
+            return Err(cache::invalid_data("BVH covers a different number of prims"));
+        }
+        let indices = try!(cache::read_indices(reader, index_count, finite_count));
+
+        Ok((nodes, indices))
+    }
+
+    fn with_index(prims: Vec<T>, index: Self::Index) -> Bvh<T> {
+        let (finites, infinites): (Vec<T>, Vec<T>) =
+            prims.into_iter().partition(|item| item.partial_bounding_box().is_some());
+        let (nodes, indices) = index;
+
+        Bvh {
+            prims: finites,
+            infinites: infinites,
+            nodes: nodes,
+            indices: indices,
+        }
+    }
+}
+
+/// Builds the subtree over `prims`, which start at `offset` in the final prim
+/// order, splitting the work across up to `threads` threads. Returns its nodes,
+/// numbered from the subtree's root, and the prims in leaf order.
+fn build_parallel(mut prims: Vec<BuildPrim>, offset: usize, threads: usize) -> (Vec<BvhNode>, Vec<BuildPrim>) {
+    let bbox = bounds(&prims);
+    let mid = if threads > 1 && prims.len() >= PARALLEL_MIN_PRIMS { split(&mut prims, &bbox) } else { None };
+
+    let mid = match mid {
+        Some(mid) => mid,
+        None => {
+            let mut nodes = Vec::new();
+            build_recursive(&mut prims, offset, &mut nodes);
+            return (nodes, prims);
+        }
+    };
+
+    let above = prims.split_off(mid);
+    let above_threads = threads / 2;
+    let above_handle = thread::spawn(move || build_parallel(above, offset + mid, above_threads));
+    let (below_nodes, mut prims) = build_parallel(prims, offset, threads - above_threads);
+    let (above_nodes, above_prims) = above_handle.join().unwrap();
+
+    // Renumber both subtrees to follow their new parent
+    let second_child = 1 + below_nodes.len();
+    let shift = |node: BvhNode, by: usize| {
+        if node.count == 0 { BvhNode { offset: node.offset + by, ..node } } else { node }
+    };
+
+    let mut nodes = Vec::with_capacity(second_child + above_nodes.len());
+    nodes.push(BvhNode { bbox: bbox, offset: second_child, count: 0 });
+    nodes.extend(below_nodes.into_iter().map(|node| shift(node, 1)));
+    nodes.extend(above_nodes.into_iter().map(|node| shift(node, second_child)));
+    prims.extend(above_prims);
+
+    (nodes, prims)
+}
+
+/// Builds the subtree over `prims`, which start at `offset` in the final
+/// prim order, and returns the index of its root node.
+fn build_recursive(prims: &mut [BuildPrim], offset: usize, nodes: &mut Vec<BvhNode>) -> usize {
+    let node_index = nodes.len();
+    let bbox = bounds(prims);
+    nodes.push(BvhNode { bbox: bbox, offset: offset, count: prims.len() });
+
+    if let Some(mid) = split(prims, &bbox) {
+        build_recursive(&mut prims[..mid], offset, nodes);
+        let second_child = build_recursive(&mut prims[mid..], offset + mid, nodes);
+        nodes[node_index].offset = second_child;
+        nodes[node_index].count = 0;
+    }
+
+    node_index
+}
+
+fn bounds(prims: &[BuildPrim]) -> BBox {
+    prims.iter().skip(1).fold(prims[0].bbox, |acc, p| union_bbox(&acc, &p.bbox))
+}
+
+/// Partitions `prims` along the cheapest SAH split and returns the size of the
+/// first half, or `None` if they should stay together in a leaf.
+fn split(prims: &mut [BuildPrim], bbox: &BBox) -> Option<usize> {
+    if prims.len() == 1 {
+        return None;
+    }
+
+    let centroid_bounds = prims.iter().skip(1).fold(
+        BBox { min: prims[0].bbox.centroid(), max: prims[0].bbox.centroid() },
+        |acc, p| union_point(&acc, &p.bbox.centroid()));
+    let axis = centroid_bounds.max_extent() as usize;
+    let axis_min = centroid_bounds.min.axis(axis);
+    let axis_extent = centroid_bounds.max.axis(axis) - axis_min;
+
+    // All centroids coincide, there is no way to split them
+    if axis_extent <= 0.0 {
+        return None;
+    }
+
+    let bucket_of = |p: &BuildPrim| {
+        let relative = (p.bbox.centroid().axis(axis) - axis_min) / axis_extent;
+        ((relative * SAH_BUCKETS as f64) as usize).min(SAH_BUCKETS - 1)
+    };
+
+    let mut buckets = [SahBucket { count: 0, bbox: None }; SAH_BUCKETS];
+    for p in prims.iter() {
+        let bucket = &mut buckets[bucket_of(p)];
+        bucket.count += 1;
+        bucket.bbox = Some(bucket.bbox.map_or(p.bbox, |b| union_bbox(&b, &p.bbox)));
+    }
+
+    // Cost of splitting after each bucket
+    let mut best_bucket = 0;
+    let mut best_cost = ::std::f64::INFINITY;
+    for split in 0..SAH_BUCKETS - 1 {
+        let (below, above) = buckets.split_at(split + 1);
+        let (below_count, below_area) = bucket_totals(below);
+        let (above_count, above_area) = bucket_totals(above);
+        if below_count == 0 || above_count == 0 {
+            continue;
+        }
+
+        let cost = SAH_TRAVERSAL_COST +
+            (below_count as f64 * below_area + above_count as f64 * above_area) / bbox.surface_area();
+        if cost < best_cost {
+            best_cost = cost;
+            best_bucket = split;
+        }
+    }
+
+    if prims.len() <= MAX_LEAF_PRIMS && best_cost >= prims.len() as f64 {
+        return None;
+    }
+
+    Some(partition(prims, |p| bucket_of(p) <= best_bucket))
+}
+
+impl<T> Bvh<T> where T: PartialBoundingBox {
+    /// Bounds of all prims, `None` if there are infinite ones (or none at all)
+    pub fn bounds(&self) -> Option<BBox> {
+        if self.infinites.is_empty() { self.nodes.first().map(|root| root.bbox) } else { None }
+    }
+
+    /// Calls `visit` with every prim whose bounds the ray passes through,
+    /// nearest node first. `visit` is given the current maximum distance and
+    /// returns the distance to its hit, if any; nodes the ray only enters
+    /// beyond the nearest hit so far are skipped.
+    pub fn traverse<'a, F>(&'a self, ray: &Ray, t_max: f64, mut visit: F)
+            where F: FnMut(&'a T, f64) -> Option<f64> {
+
+        let mut t_max = t_max;
+
+        for prim in self.infinites.iter() {
+            if let Some(t) = visit(prim, t_max) {
+                t_max = t_max.min(t);
+            }
+        }
+
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut stack: Vec<(usize, f64)> = Vec::with_capacity(64);
+        if let Some((t_enter, _)) = self.nodes[0].bbox.intersect_range(ray) {
+            stack.push((0, t_enter));
+        }
+
+        while let Some((index, t_enter)) = stack.pop() {
+            if t_enter > t_max {
+                continue;
+            }
+
+            let node = &self.nodes[index];
+            if node.count > 0 {
+                for &prim_index in self.indices[node.offset..node.offset + node.count].iter() {
+                    if let Some(t) = visit(&self.prims[prim_index], t_max) {
+                        t_max = t_max.min(t);
+                    }
+                }
+                continue;
+            }
+
+            let first = (index + 1, self.nodes[index + 1].bbox.intersect_range(ray));
+            let second = (node.offset, self.nodes[node.offset].bbox.intersect_range(ray));
+
+            // Push the farther child first so the nearer one is visited next
+            let (near, far) = match (first.1, second.1) {
+                (Some((t1, _)), Some((t2, _))) if t2 < t1 => (second, first),
+                _ => (first, second)
+            };
+            if let (far_index, Some((t_far, _))) = far {
+                stack.push((far_index, t_far));
+            }
+            if let (near_index, Some((t_near, _))) = near {
+                stack.push((near_index, t_near));
+            }
+        }
+    }
+
+    pub fn intersect_iter<'a>(&'a self, ray: &'a Ray) -> BvhIterator<'a, T> {
+        BvhIterator {
+            bvh: self,
+            ray: ray,
+            stack: if self.nodes.is_empty() { vec![] } else { vec![0] },
+            leaf_iter: self.indices[..0].iter(),
+            infinites: self.infinites.iter().enumerate(),
+        }
+    }
+}
+
+fn bucket_totals(buckets: &[SahBucket]) -> (usize, f64) {
+    let count = buckets.iter().fold(0, |acc, b| acc + b.count);
+    let bbox = BBox::from_union(buckets.iter().map(|b| b.bbox));
+    (count, bbox.map_or(0.0, |b| b.surface_area()))
+}
+
+/// Moves the items matching `pred` to the front, returning how many there are
+fn partition<F>(prims: &mut [BuildPrim], pred: F) -> usize where F: Fn(&BuildPrim) -> bool {
+    let mut mid = 0;
+    for i in 0..prims.len() {
+        if pred(&prims[i]) {
+            prims.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+/// Yields every prim whose bounds the ray passes through, in no particular
+/// order, with its index in the hierarchy.
+pub struct BvhIterator<'a, T: 'a> where T: PartialBoundingBox {
+    bvh: &'a Bvh<T>,
+    ray: &'a Ray,
+    stack: Vec<usize>,
+    leaf_iter: Iter<'a, usize>,
+    infinites: Enumerate<Iter<'a, T>>,
+}
+
+impl<'a, T> Iterator for BvhIterator<'a, T> where T: PartialBoundingBox {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<(usize, &'a T)> {
+        // Infinite prims are numbered after the finite ones
+        if let Some((i, prim)) = self.infinites.next() {
+            return Some((self.bvh.prims.len() + i, prim));
+        }
+
+        loop {
+            if let Some(&index) = self.leaf_iter.next() {
+                return Some((index, &self.bvh.prims[index]));
+            }
+
+            let index = match self.stack.pop() {
+                Some(index) => index,
+                None => return None
+            };
+
+            let node = &self.bvh.nodes[index];
+            if !node.bbox.intersects(self.ray) {
+                continue;
+            }
+
+            if node.count > 0 {
+                self.leaf_iter = self.bvh.indices[node.offset..node.offset + node.count].iter();
+            } else {
+                self.stack.push(node.offset);
+                self.stack.push(index + 1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use geometry::Prim;
+#[cfg(test)]
+use geometry::prims::Sphere;
+#[cfg(test)]
+use material::materials::FlatMaterial;
+#[cfg(test)]
+use vec3::Vec3;
+
+#[cfg(test)]
+fn get_sphere_grid() -> Vec<Sphere> {
+    let mut spheres = Vec::new();
+    for x in 0..10 {
+        for y in 0..10 {
+            for z in 0..10 {
+                spheres.push(Sphere::new(
+                    Vec3 { x: x as f64 * 3.0, y: y as f64 * 3.0, z: z as f64 * 3.0 },
+                    1.0,
+                    Box::new(FlatMaterial { color: Vec3::one() })
+                ));
+            }
+        }
+    }
+    spheres
+}
+
+#[test]
+fn it_finds_the_same_nearest_hit_as_brute_force() {
+    let bvh: Bvh<Sphere> = get_sphere_grid().into_iter().collect();
+    let spheres = get_sphere_grid();
+
+    for i in 0..200 {
+        let origin = Vec3 { x: -5.0, y: (i % 20) as f64 * 1.5, z: (i / 20) as f64 * 3.0 };
+        let spread = Vec3 { x: 0.0, y: (i % 7) as f64 - 3.0, z: (i % 11) as f64 - 5.0 }.scale(0.08);
+        let ray = Ray::new(origin, (Vec3 { x: 1.0, y: 0.1, z: 0.05 } + spread).unit());
+
+        let expected = spheres.iter()
+            .filter_map(|s| s.intersects(&ray, 0.0, ::std::f64::INFINITY).map(|hit| hit.t))
+            .fold(::std::f64::INFINITY, f64::min);
+
+        let mut nearest = ::std::f64::INFINITY;
+        bvh.traverse(&ray, ::std::f64::INFINITY, |sphere, t_max| {
+            sphere.intersects(&ray, 0.0, t_max).map(|hit| {
+                nearest = hit.t;
+                hit.t
+            })
+        });
+
+        assert_eq!(expected, nearest);
+    }
+}
+
+#[test]
+fn it_skips_nodes_beyond_the_nearest_hit() {
+    let bvh: Bvh<Sphere> = get_sphere_grid().into_iter().collect();
+    let ray = Ray::new(Vec3 { x: -5.0, y: 0.0, z: 0.0 }, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+
+    let mut visited = 0;
+    bvh.traverse(&ray, ::std::f64::INFINITY, |sphere, t_max| {
+        visited += 1;
+        sphere.intersects(&ray, 0.0, t_max).map(|hit| hit.t)
+    });
+
+    // All ten spheres along the row are candidates, but only the first needs testing
+    assert_eq!(10, bvh.intersect_iter(&ray).filter(|&(_, s)| s.center.y == 0.0 && s.center.z == 0.0).count());
+    assert!(visited < 10, "visited {} prims", visited);
+}
+
+#[test]
+fn it_builds_the_same_tree_on_several_threads() {
+    let build_prims: Vec<BuildPrim> = (0..10000).map(|i| {
+        let min = Vec3 { x: (i * 7919 % 10007) as f64, y: (i * 104729 % 10009) as f64, z: (i % 101) as f64 };
+        BuildPrim { index: i, bbox: BBox { min: min, max: min + 2.0 } }
+    }).collect();
+
+    let (serial_nodes, serial_prims) = build_parallel(build_prims.clone(), 0, 1);
+    let (parallel_nodes, parallel_prims) = build_parallel(build_prims, 0, 4);
+
+    assert_eq!(serial_nodes.len(), parallel_nodes.len());
+    for (serial, parallel) in serial_nodes.iter().zip(parallel_nodes.iter()) {
+        assert!(serial.bbox == parallel.bbox);
+        assert_eq!((serial.offset, serial.count), (parallel.offset, parallel.count));
+    }
+    assert!(serial_prims.iter().map(|p| p.index).eq(parallel_prims.iter().map(|p| p.index)));
+}
@@ -0,0 +1,192 @@
+//! Saves built acceleration structures next to the models they index, so
+//! repeat renders of the same scene can skip the build.
+//!
+//! Only the index is stored, never the prims themselves: loading pairs it
+//! with the prims the scene has just created. A fingerprint of the prims'
+//! bounding boxes guards against reusing a stale file after the model or
+//! scene changes.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::iter::FromIterator;
+use geometry::{BBox, PartialBoundingBox};
+use vec3::Vec3;
+
+const MAGIC: &'static [u8; 4] = b"RTAC";
+const VERSION: u64 = 1;
+
+/// An acceleration structure that can be split into its prims and an index over them.
+pub trait CachedIndex<T>: FromIterator<T> {
+    type Index;
+
+    fn write_index<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+    /// `finite_count` is the number of prims with a bounding box, which the index covers
+    fn read_index<R: Read>(reader: &mut R, finite_count: usize) -> io::Result<Self::Index>;
+
+    /// Rebuilds the structure from prims in the order they were originally built from
+    fn with_index(prims: Vec<T>, index: Self::Index) -> Self;
+}
+
+/// Loads the structure over `prims` from `path`, or builds it and saves it there
+/// if the file is missing or was built from different prims.
+pub fn load_or_build<T, A>(prims: Vec<T>, path: &str) -> A where T: PartialBoundingBox, A: CachedIndex<T> {
+    let expected = fingerprint(&prims);
+
+    let finite_count = prims.iter().filter(|prim| prim.partial_bounding_box().is_some()).count();
+
+    match read_file::<T, A>(path, expected, finite_count) {
+        Ok(index) => {
+            println!("Loaded cached acceleration structure {}", path);
+            return A::with_index(prims, index);
+        },
+        Err(err) => println!("Not using cached acceleration structure {}: {}", path, err)
+    }
+
+    let accelerator: A = prims.into_iter().collect();
+    match write_file(&accelerator, path, expected) {
+        Ok(()) => println!("Saved acceleration structure to {}", path),
+        Err(err) => println!("Failed to save acceleration structure to {}: {}", path, err)
+    }
+
+    accelerator
+}
+
+fn read_file<T, A>(path: &str, expected: u64, finite_count: usize) -> io::Result<A::Index> where A: CachedIndex<T> {
+    let mut reader = BufReader::new(try!(File::open(path)));
+
+    let mut magic = [0u8; 4];
+    try!(reader.read_exact(&mut magic));
+    if &magic != MAGIC || try!(read_u64(&mut reader)) != VERSION {
+        return Err(invalid_data("unrecognised file format"));
+    }
+    if try!(read_u64(&mut reader)) != expected {
+        return Err(invalid_data("built from different prims"));
+    }
+
+    A::read_index(&mut reader, finite_count)
+}
+
+fn write_file<T, A>(accelerator: &A, path: &str, fingerprint: u64) -> io::Result<()> where A: CachedIndex<T> {
+    let mut writer = BufWriter::new(try!(File::create(path)));
+    try!(writer.write_all(MAGIC));
+    try!(write_u64(&mut writer, VERSION));
+    try!(write_u64(&mut writer, fingerprint));
+    try!(accelerator.write_index(&mut writer));
+    writer.flush()
+}
+
+/// FNV-1a hash of the prims' bounding boxes, in order
+pub fn fingerprint<T>(prims: &[T]) -> u64 where T: PartialBoundingBox {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut mix = |value: u64| {
+        for i in 0..8 {
+            hash ^= (value >> (8 * i)) & 0xff;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+
+    mix(prims.len() as u64);
+    for prim in prims.iter() {
+        match prim.partial_bounding_box() {
+            Some(bbox) => {
+                for v in [bbox.min.x, bbox.min.y, bbox.min.z, bbox.max.x, bbox.max.y, bbox.max.z].iter() {
+                    mix(v.to_bits());
+                }
+            },
+            None => mix(!0)
+        }
+    }
+
+    hash
+}
+
+pub fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    let mut bytes = [0u8; 8];
+    for i in 0..8 {
+        bytes[i] = (value >> (8 * i)) as u8;
+    }
+    writer.write_all(&bytes)
+}
+
+pub fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    try!(reader.read_exact(&mut bytes));
+    Ok(bytes.iter().enumerate().fold(0, |acc, (i, &b)| acc | (b as u64) << (8 * i)))
+}
+
+pub fn write_f64<W: Write>(writer: &mut W, value: f64) -> io::Result<()> {
+    write_u64(writer, value.to_bits())
+}
+
+pub fn read_f64<R: Read>(reader: &mut R) -> io::Result<f64> {
+    read_u64(reader).map(f64::from_bits)
+}
+
+pub fn write_bbox<W: Write>(writer: &mut W, bbox: &BBox) -> io::Result<()> {
+    for v in [bbox.min.x, bbox.min.y, bbox.min.z, bbox.max.x, bbox.max.y, bbox.max.z].iter() {
+        try!(write_f64(writer, *v));
+    }
+    Ok(())
+}
+
+pub fn read_bbox<R: Read>(reader: &mut R) -> io::Result<BBox> {
+    let mut v = [0.0; 6];
+    for i in 0..6 {
+        v[i] = try!(read_f64(reader));
+    }
+    Ok(BBox {
+        min: Vec3 { x: v[0], y: v[1], z: v[2] },
+        max: Vec3 { x: v[3], y: v[4], z: v[5] }
+    })
+}
+
+/// Reads a length prefix, refusing ones larger than the structure could hold
+pub fn read_len<R: Read>(reader: &mut R, max: usize) -> io::Result<usize> {
+    let len = try!(read_u64(reader));
+    if len > max as u64 {
+        Err(invalid_data("length out of range"))
+    } else {
+        Ok(len as usize)
+    }
+}
+
+/// Reads `len` indices, each of which must be below `bound`
+pub fn read_indices<R: Read>(reader: &mut R, len: usize, bound: usize) -> io::Result<Vec<usize>> {
+    let mut indices = Vec::with_capacity(len);
+    for _ in 0..len {
+        let index = try!(read_u64(reader));
+        if index >= bound as u64 {
+            return Err(invalid_data("index out of range"));
+        }
+        indices.push(index as usize);
+    }
+    Ok(indices)
+}
+
+#[test]
+fn it_round_trips_values() {
+    let mut bytes: Vec<u8> = Vec::new();
+    write_u64(&mut bytes, 0x0123456789abcdef).unwrap();
+    write_f64(&mut bytes, -1.5).unwrap();
+    write_bbox(&mut bytes, &BBox { min: Vec3::zero(), max: Vec3::one() }).unwrap();
+
+    let mut reader = &bytes[..];
+    assert_eq!(0x0123456789abcdef, read_u64(&mut reader).unwrap());
+    assert_eq!(-1.5, read_f64(&mut reader).unwrap());
+    assert!(BBox { min: Vec3::zero(), max: Vec3::one() } == read_bbox(&mut reader).unwrap());
+    assert!(read_u64(&mut reader).is_err());
+}
+
+#[test]
+fn it_fingerprints_prim_bounds() {
+    let a = vec![Some(BBox { min: Vec3::zero(), max: Vec3::one() }), None];
+    let b = vec![Some(BBox { min: Vec3::zero(), max: Vec3 { x: 1.0, y: 1.0, z: 2.0 } }), None];
+
+    assert_eq!(fingerprint(&a), fingerprint(&a.clone()));
+    assert!(fingerprint(&a) != fingerprint(&b));
+    assert!(fingerprint(&a) != fingerprint(&a[..1]));
+}
@@ -1,11 +1,173 @@
+use geometry::prim::frame_around;
 use material::Material;
 use vec3::Vec3;
 
+#[cfg(test)]
+use geometry::prim::Prim;
+#[cfg(test)]
+use geometry::prims::{Cone, Csg, CsgOp, Cuboid, Implicit, Sphere, Torus, TriangleOptions};
+#[cfg(test)]
+use geometry::sdf::{SdfSphere, SdfTorus};
+#[cfg(test)]
+use material::materials::FlatMaterial;
+#[cfg(test)]
+use raytracer::Ray;
+
+/// Rays leaving a surface start this far off it per unit of distance from
+/// the world origin, which is about how far rounding can move a hit point
+const RELATIVE_OFFSET: f64 = 1e-9;
+
 pub struct Intersection<'a> {
+    /// Shading normal, after smoothing and bump mapping
     pub n: Vec3,
+    /// Normal of the surface itself, before smoothing or bump mapping, on
+    /// the same side as `n`
+    pub ng: Vec3,
     pub t: f64,
     pub u: f64,
     pub v: f64,
+    /// How the surface moves as u and v increase
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
     pub position: Vec3,
+    /// `position` moved out onto the curved surface the shading normals
+    /// describe, where smooth shading puts a flat facet. Rays leaving on the
+    /// outside start from here so they clear the neighbouring facets.
+    pub shading_position: Vec3,
     pub material: &'a Box<Material + Send + Sync + 'a>
 }
+
+impl<'a> Intersection<'a> {
+    /// Unit direction of increasing u in the shading plane. Hair and other
+    /// anisotropic materials shade along it.
+    pub fn tangent(&self) -> Vec3 {
+        let n = self.n.unit();
+        let tangent = self.dpdu - n.scale(n.dot(&self.dpdu));
+        if tangent.len() < ::std::f64::EPSILON * self.dpdu.len().max(1.0) {
+            frame_around(&n).0
+        } else {
+            tangent.unit()
+        }
+    }
+
+    /// Completes the shading frame with `tangent` and `n`, on the side of
+    /// increasing v where the UVs are not mirrored
+    #[allow(dead_code)]
+    pub fn bitangent(&self) -> Vec3 {
+        self.n.unit().cross(&self.tangent())
+    }
+
+    /// Where a ray leaving the hit towards `direction` should start: moved
+    /// off the surface along the geometric normal, to the side the ray is
+    /// going, by an amount that grows with the scale of the scene. Such
+    /// rays can then be traced from a `t_min` of zero without hitting the
+    /// surface they start on.
+    pub fn offset_origin(&self, direction: &Vec3) -> Vec3 {
+        let leaving_outside = direction.dot(&self.ng) >= 0.0;
+        let p = if leaving_outside { self.shading_position } else { self.position };
+        let magnitude = p.x.abs().max(p.y.abs()).max(p.z.abs()).max(1e-6);
+        let offset = self.ng.unit().scale(magnitude * RELATIVE_OFFSET);
+
+        if leaving_outside { p + offset } else { p - offset }
+    }
+}
+
+#[test]
+fn it_offsets_rays_off_the_surface_at_any_scale() {
+    for &scale in [1e-3, 1.0, 1e3, 1e6].iter() {
+        let ground = TriangleOptions::new(Vec3 { x: -3.0, y: 0.1, z: -3.0 }.scale(scale),
+                                          Vec3 { x: 3.0, y: 0.1, z: -3.0 }.scale(scale),
+                                          Vec3 { x: 0.0, y: 0.1, z: 5.0 }.scale(scale)).build();
+
+        for i in 0..50 {
+            let x = (i as f64 / 50.0 - 0.5) * 2.0;
+            let eye = Vec3 { x: 0.37, y: 1.3, z: 3.1 }.scale(scale);
+            let target = Vec3 { x: x, y: 0.1, z: -x * 0.9 + 0.3 }.scale(scale);
+            let hit = ground.intersects(&Ray::new(eye, (target - eye).unit()), 0.0, ::std::f64::INFINITY).unwrap();
+
+            // Grazing shadow rays off either side do not hit the ground again
+            for &towards in [Vec3 { x: 1.0, y: 1e-4, z: 0.3 }, Vec3 { x: -0.2, y: -1e-4, z: 1.0 }].iter() {
+                let direction = towards.unit();
+                let ray = Ray::new(hit.offset_origin(&direction), direction);
+                assert!(ground.intersects(&ray, 0.0, ::std::f64::INFINITY).is_none(), "self-hit at scale {}", scale);
+            }
+        }
+    }
+}
+
+#[test]
+fn it_offsets_rays_off_curved_and_compound_surfaces_at_any_scale() {
+    let material = || Box::new(FlatMaterial { color: Vec3::one() });
+
+    for &scale in [1e-3, 1.0, 1e3, 1e6].iter() {
+        let at = |x: f64, y: f64, z: f64| Vec3 { x: x, y: y, z: z }.scale(scale);
+        let prims: Vec<(&str, Box<Prim+Send+Sync>)> = vec![
+            ("torus", Box::new(Torus::new(at(0.1, 0.0, 0.0), Vec3 { x: 0.2, y: 1.0, z: 0.1 }, 1.0 * scale, 0.4 * scale, material()))),
+            ("sdf sphere", Box::new(Implicit::new(Box::new(SdfSphere { center: at(0.1, 0.0, 0.0), radius: 1.2 * scale }), material()))),
+            ("sdf torus", Box::new(Implicit::new(Box::new(SdfTorus { center: at(0.0, 0.1, 0.0), major_radius: 1.0 * scale, minor_radius: 0.4 * scale }), material()))),
+            ("csg", Box::new(Csg::new(CsgOp::Difference,
+                                      Box::new(Sphere::new(at(0.0, 0.0, 0.0), 1.3 * scale, material())),
+                                      Box::new(Cuboid::new(at(-2.0, 0.5, -2.0), at(2.0, 2.0, 2.0), material())))))
+        ];
+
+        for &(name, ref prim) in prims.iter() {
+            let mut hits = 0;
+            for i in 0..50 {
+                let x = (i as f64 / 50.0 - 0.5) * 1.6;
+                let eye = at(0.37, 3.3, 4.1);
+                let target = at(x, 0.3, -x * 0.7);
+                let hit = match prim.intersects(&Ray::new(eye, (target - eye).unit()), 0.0, ::std::f64::INFINITY) {
+                    Some(hit) => hit,
+                    None => continue
+                };
+                hits += 1;
+
+                // Rays grazing off the outside and cutting back in only hit
+                // the surface again well away from where they start
+                let ng = hit.ng.unit();
+                let (tangent, _) = frame_around(&ng);
+                for &towards in [tangent + ng.scale(1e-2), tangent - ng.scale(1e-2), -ng].iter() {
+                    let direction = towards.unit();
+                    let ray = Ray::new(hit.offset_origin(&direction), direction);
+                    if let Some(again) = prim.intersects(&ray, 0.0, ::std::f64::INFINITY) {
+                        assert!(again.t > 1e-3 * scale, "{} self-hit at scale {}: t = {}", name, scale, again.t);
+                    }
+                }
+            }
+            assert!(hits > 25, "{} mostly missed at scale {}", name, scale);
+        }
+    }
+}
+
+#[test]
+fn it_gives_derivatives_that_follow_the_uvs() {
+    let material = || Box::new(FlatMaterial { color: Vec3::one() });
+    let mut triopts = TriangleOptions::new(Vec3 { x: -1.0, y: -1.0, z: 0.5 }, Vec3 { x: 2.0, y: -0.5, z: 0.0 }, Vec3 { x: 0.0, y: 2.0, z: 0.2 });
+    triopts.texinfo([(0.0, 0.0), (0.5, 0.2), (0.1, 0.9)]);
+
+    let prims: Vec<Box<Prim+Send+Sync>> = vec![
+        Box::new(Sphere::new(Vec3 { x: 0.2, y: 0.1, z: 0.0 }, 1.3, material())),
+        Box::new(Torus::new(Vec3::zero(), Vec3 { x: 0.3, y: 1.0, z: 0.2 }, 1.2, 0.5, material())),
+        Box::new(Cone::new(Vec3 { x: 0.0, y: -1.0, z: 0.0 }, Vec3 { x: 0.5, y: 1.5, z: 0.0 }, 1.0, material())),
+        Box::new(triopts.build())
+    ];
+
+    let origin = Vec3 { x: 0.3, y: 0.4, z: -6.0 };
+    let delta = 1e-5;
+    for prim in prims.iter() {
+        let hit = prim.intersects(&Ray::new(origin, (Vec3 { x: 0.15, y: 0.2, z: 0.0 } - origin).unit()), 0.0, 100.0).unwrap();
+        let n = hit.n.unit();
+
+        // The shading frame is orthonormal and the derivatives lie in the surface
+        assert!((hit.tangent().len() - 1.0).abs() < 1e-9 && hit.tangent().dot(&n).abs() < 1e-9);
+        assert!((hit.bitangent() - n.cross(&hit.tangent())).len() < 1e-9);
+        assert!(hit.dpdu.dot(&hit.ng.unit()).abs() < 1e-6 * hit.dpdu.len());
+        assert!(hit.dpdv.dot(&hit.ng.unit()).abs() < 1e-6 * hit.dpdv.len());
+
+        // Stepping along them moves u or v alone
+        let along_u = prim.intersects(&Ray::new(origin, (hit.position + hit.dpdu.scale(delta) - origin).unit()), 0.0, 100.0).unwrap();
+        assert!(((along_u.u - hit.u) / delta - 1.0).abs() < 1e-3 && (along_u.v - hit.v).abs() < 1e-3 * delta);
+        let along_v = prim.intersects(&Ray::new(origin, (hit.position + hit.dpdv.scale(delta) - origin).unit()), 0.0, 100.0).unwrap();
+        assert!(((along_v.v - hit.v) / delta - 1.0).abs() < 1e-3 && (along_v.u - hit.u).abs() < 1e-3 * delta);
+    }
+}
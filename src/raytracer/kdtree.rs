@@ -0,0 +1,531 @@
+use std::cmp::Ordering;
+use std::io::{self, Read, Write};
+use std::iter::{Enumerate, FromIterator};
+use std::slice::Iter;
+use std::sync::Arc;
+use std::thread;
+use geometry::{BBox, PartialBoundingBox};
+use raytracer::Ray;
+use raytracer::cache::{self, CachedIndex};
+
+/// Relative cost of a ray-primitive test against one traversal step (pbrt, 4.4)
+const KD_INTERSECT_COST: f64 = 80.0;
+const KD_TRAVERSAL_COST: f64 = 1.0;
+/// Splits that cut off empty space are favoured by this fraction
+const KD_EMPTY_BONUS: f64 = 0.5;
+const KD_MAX_LEAF_PRIMS: usize = 2;
+/// Subtrees smaller than this are not worth handing to another thread
+const PARALLEL_MIN_PRIMS: usize = 4096;
+
+/// k-d tree with splitting planes placed by the surface area heuristic.
+/// Prims straddling a split are referenced from both sides.
+pub struct KdTree<T> where T: PartialBoundingBox {
+    prims: Vec<T>,
+    infinites: Vec<T>, // for infinite prims (planes)
+    bounds: BBox,
+    nodes: Vec<KdNode>,
+    leaf_prims: Vec<usize>, // Indices into prims, each leaf covers a contiguous range
+}
+
+#[derive(Clone, Copy)]
+pub enum KdNode {
+    // Below child immediately follows the node
+    Interior { axis: usize, split: f64, above_child: usize },
+    Leaf { offset: usize, count: usize }
+}
+
+#[derive(Clone, Copy)]
+struct BoundEdge {
+    t: f64,
+    index: usize,
+    start: bool
+}
+
+impl<T> FromIterator<T> for KdTree<T> where T: PartialBoundingBox {
+    fn from_iter<I>(iterator: I) -> Self where I: IntoIterator<Item=T> {
+        let (finites, infinites): (Vec<T>, Vec<T>) =
+            iterator.into_iter().partition(|item| item.partial_bounding_box().is_some());
+
+        let prim_bounds: Vec<BBox> = finites.iter().map(|p| p.partial_bounding_box().unwrap()).collect();
+        let bounds = BBox::from_union(finites.iter().map(|p| p.partial_bounding_box()))
+            .unwrap_or(BBox::zero());
+
+        // pbrt recommended max depth for a k-d tree: 8 + 1.3 * log2(N)
+        let depth = (8.0 + 1.3 * (finites.len().max(1) as f64).log2()).round() as i32;
+        println!("k-d tree maximum depth {}", depth);
+
+        let indices = (0..finites.len()).collect();
+        let (nodes, leaf_prims) = build_parallel(Arc::new(prim_bounds), bounds, indices, depth, 0, ::num_cpus::get());
+        println!("k-d tree nodes {}", nodes.len());
+
+        KdTree {
+            prims: finites,
+            infinites: infinites,
+            bounds: bounds,
+            nodes: nodes,
+            leaf_prims: leaf_prims,
+        }
+    }
+}
+
+impl<T> CachedIndex<T> for KdTree<T> where T: PartialBoundingBox {
+    type Index = (Vec<KdNode>, Vec<usize>);
+
+    fn write_index<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        try!(cache::write_u64(writer, self.nodes.len() as u64));
+        for node in self.nodes.iter() {
+            match *node {
+                KdNode::Interior { axis, split, above_child } => {
+                    try!(cache::write_u64(writer, axis as u64));
+                    try!(cache::write_f64(writer, split));
+                    try!(cache::write_u64(writer, above_child as u64));
+                },
+                KdNode::Leaf { offset, count } => {
+                    // Axes only go up to 2, so 3 marks a leaf
+                    try!(cache::write_u64(writer, 3));
+                    try!(cache::write_u64(writer, offset as u64));
+                    try!(cache::write_u64(writer, count as u64));
+                }
+            }
+        }
+
+        try!(cache::write_u64(writer, self.leaf_prims.len() as u64));
+        for &index in self.leaf_prims.iter() {
+            try!(cache::write_u64(writer, index as u64));
+        }
+        Ok(())
+    }
+
+    fn read_index<R: Read>(reader: &mut R, finite_count: usize) -> io::Result<Self::Index> {
+        let node_count = try!(cache::read_len(reader, usize::max_value()));
+        let mut nodes = Vec::new();
+        for i in 0..node_count {
+            let tag = try!(cache::read_len(reader, 3));
+            if tag < 3 {
+                let split = try!(cache::read_f64(reader));
+                let above_child = try!(cache::read_len(reader, usize::max_value()));
+                if above_child <= i + 1 || above_child >= node_count {
+                    return Err(cache::invalid_data("k-d tree node out of range"));
+                }
+                nodes.push(KdNode::Interior { axis: tag, split: split, above_child: above_child });
+            } else {
+                let offset = try!(cache::read_len(reader, usize::max_value()));
+                let count = try!(cache::read_len(reader, finite_count));
+                nodes.push(KdNode::Leaf { offset: offset, count: count });
+            }
+        }
+
+        let leaf_prim_count = try!(cache::read_len(reader, usize::max_value()));
+        let leaf_prims = try!(cache::read_indices(reader, leaf_prim_count, finite_count));
+        for node in nodes.iter() {
+            if let KdNode::Leaf { offset, count } = *node {
+                if offset.checked_add(count).map_or(true, |end| end > leaf_prims.len()) {
+                    return Err(cache::invalid_data("k-d tree leaf out of range"));
+                }
+            }
+        }
+
+        Ok((nodes, leaf_prims))
+    }
+
+    fn with_index(prims: Vec<T>, index: Self::Index) -> KdTree<T> {
+        let (finites, infinites): (Vec<T>, Vec<T>) =
+            prims.into_iter().partition(|item| item.partial_bounding_box().is_some());
+        let bounds = BBox::from_union(finites.iter().map(|p| p.partial_bounding_box()))
+            .unwrap_or(BBox::zero());
+        let (nodes, leaf_prims) = index;
+
+        KdTree {
+            prims: finites,
+            infinites: infinites,
+            bounds: bounds,
+            nodes: nodes,
+            leaf_prims: leaf_prims,
+        }
+    }
+}
+
+/// Where to split a node and which prims end up on either side
+struct Split {
+    axis: usize,
+    position: f64,
+    below: Vec<usize>,
+    above: Vec<usize>,
+    below_bounds: BBox,
+    above_bounds: BBox,
+    bad_refines: u32,
+}
+
+/// Builds the subtree over `indices`, splitting the work across up to `threads`
+/// threads. Returns its nodes, numbered from the subtree's root, and the prim
+/// indices its leaves cover.
+fn build_parallel(prim_bounds: Arc<Vec<BBox>>, node_bounds: BBox, indices: Vec<usize>,
+                  depth: i32, bad_refines: u32, threads: usize) -> (Vec<KdNode>, Vec<usize>) {
+
+    let split = if threads > 1 && indices.len() >= PARALLEL_MIN_PRIMS {
+        choose_split(&prim_bounds, &node_bounds, &indices, depth, bad_refines)
+    } else {
+        None
+    };
+
+    let split = match split {
+        Some(split) => split,
+        None => {
+            let mut nodes = Vec::new();
+            let mut leaf_prims = Vec::new();
+            build_recursive(&prim_bounds, node_bounds, indices, depth, bad_refines, &mut nodes, &mut leaf_prims);
+            return (nodes, leaf_prims);
+        }
+    };
+
+    let above_threads = threads / 2;
+    let above_bounds = Arc::clone(&prim_bounds);
+    let (above, above_node_bounds, refines) = (split.above, split.above_bounds, split.bad_refines);
+    let above_handle = thread::spawn(move || {
+        build_parallel(above_bounds, above_node_bounds, above, depth - 1, refines, above_threads)
+    });
+    let (below_nodes, mut leaf_prims) = build_parallel(prim_bounds, split.below_bounds, split.below,
+                                                       depth - 1, split.bad_refines, threads - above_threads);
+    let (above_nodes, above_leaf_prims) = above_handle.join().unwrap();
+
+    // Renumber both subtrees to follow their new parent
+    let above_child = 1 + below_nodes.len();
+    let leaf_shift = leaf_prims.len();
+    let shift = |node: KdNode, node_shift: usize, leaf_shift: usize| match node {
+        KdNode::Interior { axis, split, above_child } =>
+            KdNode::Interior { axis: axis, split: split, above_child: above_child + node_shift },
+        KdNode::Leaf { offset, count } =>
+            KdNode::Leaf { offset: offset + leaf_shift, count: count }
+    };
+
+    let mut nodes = Vec::with_capacity(above_child + above_nodes.len());
+    nodes.push(KdNode::Interior { axis: split.axis, split: split.position, above_child: above_child });
+    nodes.extend(below_nodes.into_iter().map(|node| shift(node, 1, 0)));
+    nodes.extend(above_nodes.into_iter().map(|node| shift(node, above_child, leaf_shift)));
+    leaf_prims.extend(above_leaf_prims);
+
+    (nodes, leaf_prims)
+}
+
+fn build_recursive(prim_bounds: &[BBox], node_bounds: BBox, indices: Vec<usize>, depth: i32, bad_refines: u32,
+                   nodes: &mut Vec<KdNode>, leaf_prims: &mut Vec<usize>) {
+
+    let split = match choose_split(prim_bounds, &node_bounds, &indices, depth, bad_refines) {
+        Some(split) => split,
+        None => {
+            nodes.push(KdNode::Leaf { offset: leaf_prims.len(), count: indices.len() });
+            leaf_prims.extend_from_slice(&indices);
+            return;
+        }
+    };
+
+    let node_index = nodes.len();
+    nodes.push(KdNode::Interior { axis: split.axis, split: split.position, above_child: 0 });
+    build_recursive(prim_bounds, split.below_bounds, split.below, depth - 1, split.bad_refines, nodes, leaf_prims);
+    let above_child = nodes.len();
+    build_recursive(prim_bounds, split.above_bounds, split.above, depth - 1, split.bad_refines, nodes, leaf_prims);
+    nodes[node_index] = KdNode::Interior { axis: split.axis, split: split.position, above_child: above_child };
+}
+
+/// Decides whether to split a node, and where. `None` means it should be a leaf.
+fn choose_split(prim_bounds: &[BBox], node_bounds: &BBox, indices: &[usize],
+                depth: i32, bad_refines: u32) -> Option<Split> {
+
+    if indices.len() <= KD_MAX_LEAF_PRIMS || depth == 0 {
+        return None;
+    }
+
+    let leaf_cost = KD_INTERSECT_COST * indices.len() as f64;
+    let (axis, edges, best_offset, best_cost) = match find_split(prim_bounds, node_bounds, indices) {
+        Some(split) => split,
+        None => return None
+    };
+
+    // Tolerate a few splits that cost more than a leaf, they may enable better ones further down
+    let bad_refines = if best_cost > leaf_cost { bad_refines + 1 } else { bad_refines };
+    if (best_cost > 4.0 * leaf_cost && indices.len() < 16) || bad_refines == 3 {
+        return None;
+    }
+
+    let position = edges[best_offset].t;
+    let mut below_bounds = *node_bounds;
+    let mut above_bounds = *node_bounds;
+    match axis {
+        0 => { below_bounds.max.x = position; above_bounds.min.x = position; },
+        1 => { below_bounds.max.y = position; above_bounds.min.y = position; },
+        _ => { below_bounds.max.z = position; above_bounds.min.z = position; }
+    }
+
+    Some(Split {
+        axis: axis,
+        position: position,
+        below: edges[..best_offset].iter().filter(|e| e.start).map(|e| e.index).collect(),
+        above: edges[best_offset + 1..].iter().filter(|e| !e.start).map(|e| e.index).collect(),
+        below_bounds: below_bounds,
+        above_bounds: above_bounds,
+        bad_refines: bad_refines,
+    })
+}
+
+/// Returns the axis, sorted edges, index of the splitting edge and cost of
+/// the cheapest split. Starts with the widest axis and only tries the
+/// others if no plane on it falls inside the node.
+fn find_split(prim_bounds: &[BBox], node_bounds: &BBox, indices: &[usize])
+              -> Option<(usize, Vec<BoundEdge>, usize, f64)> {
+
+    let total_area = node_bounds.surface_area();
+    let inv_total_area = if total_area > 0.0 { 1.0 / total_area } else { 0.0 };
+    let diagonal = node_bounds.len();
+    let first_axis = node_bounds.max_extent() as usize;
+
+    for retry in 0..3 {
+        let axis = (first_axis + retry) % 3;
+        let mut edges: Vec<BoundEdge> = Vec::with_capacity(indices.len() * 2);
+        for &index in indices.iter() {
+            let bbox = &prim_bounds[index];
+            edges.push(BoundEdge { t: bbox.min.axis(axis), index: index, start: true });
+            edges.push(BoundEdge { t: bbox.max.axis(axis), index: index, start: false });
+        }
+        // Starts sort before ends at the same position so flat prims land on both sides
+        edges.sort_by(|a, b| {
+            a.t.partial_cmp(&b.t).unwrap_or(Ordering::Equal).then(b.start.cmp(&a.start))
+        });
+
+        let other0 = (axis + 1) % 3;
+        let other1 = (axis + 2) % 3;
+        let (node_min, node_max) = (node_bounds.min.axis(axis), node_bounds.max.axis(axis));
+
+        let mut best: Option<(usize, f64)> = None;
+        let mut below_count = 0;
+        let mut above_count = indices.len();
+
+        for (i, edge) in edges.iter().enumerate() {
+            if !edge.start {
+                above_count -= 1;
+            }
+
+            if edge.t > node_min && edge.t < node_max {
+                let below_len = edge.t - node_min;
+                let above_len = node_max - edge.t;
+                let cross_section = diagonal.axis(other0) * diagonal.axis(other1);
+                let perimeter = diagonal.axis(other0) + diagonal.axis(other1);
+                let below_prob = 2.0 * (cross_section + below_len * perimeter) * inv_total_area;
+                let above_prob = 2.0 * (cross_section + above_len * perimeter) * inv_total_area;
+
+                let bonus = if below_count == 0 || above_count == 0 { KD_EMPTY_BONUS } else { 0.0 };
+                let cost = KD_TRAVERSAL_COST + KD_INTERSECT_COST * (1.0 - bonus) *
+                    (below_prob * below_count as f64 + above_prob * above_count as f64);
+
+                if best.map_or(true, |(_, best_cost)| cost < best_cost) {
+                    best = Some((i, cost));
+                }
+            }
+
+            if edge.start {
+                below_count += 1;
+            }
+        }
+
+        if let Some((offset, cost)) = best {
+            return Some((axis, edges, offset, cost));
+        }
+    }
+
+    None
+}
+
+impl<T> KdTree<T> where T: PartialBoundingBox {
+    /// Bounds of all prims, `None` if there are infinite ones (or none at all)
+    pub fn bounds(&self) -> Option<BBox> {
+        if self.infinites.is_empty() && !self.prims.is_empty() { Some(self.bounds) } else { None }
+    }
+
+    /// Calls `visit` with the prims of every leaf the ray passes through,
+    /// nearest leaf first. `visit` is given the current maximum distance and
+    /// returns the distance to its hit, if any; leaves the ray only reaches
+    /// beyond the nearest hit so far are skipped.
+    pub fn traverse<'a, F>(&'a self, ray: &Ray, t_max: f64, mut visit: F)
+            where F: FnMut(&'a T, f64) -> Option<f64> {
+
+        let mut t_max = t_max;
+
+        for prim in self.infinites.iter() {
+            if let Some(t) = visit(prim, t_max) {
+                t_max = t_max.min(t);
+            }
+        }
+
+        let (mut t_enter, mut t_exit) = match self.bounds.intersect_range(ray) {
+            Some(range) if !self.nodes.is_empty() => range,
+            _ => return
+        };
+
+        let mut stack: Vec<(usize, f64, f64)> = Vec::with_capacity(64);
+        let mut index = 0;
+
+        loop {
+            if t_enter > t_max {
+                break;
+            }
+
+            match self.nodes[index] {
+                KdNode::Interior { axis, split, above_child } => {
+                    let (first, second, t_plane) = self.children(index, axis, split, above_child, ray);
+
+                    if t_plane > t_exit || t_plane <= 0.0 {
+                        index = first;
+                    } else if t_plane < t_enter {
+                        index = second;
+                    } else {
+                        stack.push((second, t_plane, t_exit));
+                        index = first;
+                        t_exit = t_plane;
+                    }
+                    continue;
+                },
+                KdNode::Leaf { offset, count } => {
+                    for &prim_index in self.leaf_prims[offset..offset + count].iter() {
+                        if let Some(t) = visit(&self.prims[prim_index], t_max) {
+                            t_max = t_max.min(t);
+                        }
+                    }
+                }
+            }
+
+            match stack.pop() {
+                Some((next, next_enter, next_exit)) => {
+                    index = next;
+                    t_enter = next_enter;
+                    t_exit = next_exit;
+                },
+                None => break
+            }
+        }
+    }
+
+    /// Returns the children of an interior node in the order the ray passes
+    /// through them, and the distance at which it crosses the splitting plane
+    fn children(&self, index: usize, axis: usize, split: f64, above_child: usize, ray: &Ray) -> (usize, usize, f64) {
+        let origin = ray.origin.axis(axis);
+        let t_plane = (split - origin) * ray.inverse_dir.axis(axis);
+        let below_first = origin < split || (origin == split && ray.direction.axis(axis) <= 0.0);
+
+        if below_first {
+            (index + 1, above_child, t_plane)
+        } else {
+            (above_child, index + 1, t_plane)
+        }
+    }
+
+    pub fn intersect_iter<'a>(&'a self, ray: &'a Ray) -> KdTreeIterator<'a, T> {
+        let stack = match self.bounds.intersect_range(ray) {
+            Some((t_enter, t_exit)) if !self.nodes.is_empty() => vec![(0, t_enter, t_exit)],
+            _ => vec![]
+        };
+
+        KdTreeIterator {
+            tree: self,
+            ray: ray,
+            stack: stack,
+            leaf_iter: self.leaf_prims[..0].iter(),
+            infinites: self.infinites.iter().enumerate(),
+        }
+    }
+}
+
+/// Yields the prims of every leaf the ray passes through, with their indices
+/// in the tree. Prims spanning several leaves are yielded once for each.
+pub struct KdTreeIterator<'a, T: 'a> where T: PartialBoundingBox {
+    tree: &'a KdTree<T>,
+    ray: &'a Ray,
+    stack: Vec<(usize, f64, f64)>,
+    leaf_iter: Iter<'a, usize>,
+    infinites: Enumerate<Iter<'a, T>>,
+}
+
+impl<'a, T> Iterator for KdTreeIterator<'a, T> where T: PartialBoundingBox {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<(usize, &'a T)> {
+        // Infinite prims are numbered after the finite ones
+        if let Some((i, prim)) = self.infinites.next() {
+            return Some((self.tree.prims.len() + i, prim));
+        }
+
+        loop {
+            if let Some(&index) = self.leaf_iter.next() {
+                return Some((index, &self.tree.prims[index]));
+            }
+
+            let (index, t_enter, t_exit) = match self.stack.pop() {
+                Some(entry) => entry,
+                None => return None
+            };
+
+            match self.tree.nodes[index] {
+                KdNode::Interior { axis, split, above_child } => {
+                    let (first, second, t_plane) = self.tree.children(index, axis, split, above_child, self.ray);
+
+                    if t_plane > t_exit || t_plane <= 0.0 {
+                        self.stack.push((first, t_enter, t_exit));
+                    } else if t_plane < t_enter {
+                        self.stack.push((second, t_enter, t_exit));
+                    } else {
+                        self.stack.push((second, t_plane, t_exit));
+                        self.stack.push((first, t_enter, t_plane));
+                    }
+                },
+                KdNode::Leaf { offset, count } => {
+                    self.leaf_iter = self.tree.leaf_prims[offset..offset + count].iter();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use geometry::Prim;
+#[cfg(test)]
+use geometry::prims::Sphere;
+#[cfg(test)]
+use material::materials::FlatMaterial;
+#[cfg(test)]
+use vec3::Vec3;
+
+#[test]
+fn it_finds_the_same_nearest_hit_as_brute_force() {
+    let get_spheres = || (0..500).map(|i| Sphere::new(
+        Vec3 { x: (i % 10) as f64 * 2.5, y: (i / 10 % 10) as f64 * 2.5, z: (i / 100) as f64 * 2.5 },
+        0.5 + (i % 7) as f64 * 0.2,
+        Box::new(FlatMaterial { color: Vec3::one() })
+    )).collect::<Vec<Sphere>>();
+
+    let tree: KdTree<Sphere> = get_spheres().into_iter().collect();
+    let spheres = get_spheres();
+
+    for i in 0..200 {
+        // A fixed spread of rays from above the grid, through points around its middle
+        let origin = Vec3 { x: 11.0, y: 11.0, z: 30.0 } + Vec3 { x: (i % 5) as f64 - 2.0, y: (i / 5 % 5) as f64 - 2.0, z: (i / 25) as f64 - 3.5 }.scale(5.0);
+        let target = Vec3 { x: 11.0, y: 11.0, z: 5.0 } + Vec3 { x: (i % 3) as f64 - 1.0, y: (i % 7) as f64 - 3.0, z: (i % 4) as f64 - 1.5 }.scale(3.0);
+        let ray = Ray::new(origin, (target - origin).unit());
+
+        let expected = spheres.iter()
+            .filter_map(|s| s.intersects(&ray, 0.0, ::std::f64::INFINITY).map(|hit| hit.t))
+            .fold(::std::f64::INFINITY, f64::min);
+
+        let mut nearest = ::std::f64::INFINITY;
+        tree.traverse(&ray, ::std::f64::INFINITY, |sphere, t_max| {
+            sphere.intersects(&ray, 0.0, t_max).map(|hit| {
+                nearest = hit.t;
+                hit.t
+            })
+        });
+
+        let iterated = tree.intersect_iter(&ray)
+            .filter_map(|(_, s)| s.intersects(&ray, 0.0, ::std::f64::INFINITY).map(|hit| hit.t))
+            .fold(::std::f64::INFINITY, f64::min);
+
+        assert_eq!(expected, nearest, "ray {}", i);
+        assert_eq!(expected, iterated, "ray {}", i);
+    }
+}
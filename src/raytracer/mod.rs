@@ -1,12 +1,21 @@
+pub use self::accelerator::{Accelerator, AcceleratorKind, AcceleratorOptions};
 pub use self::animator::{Animator, CameraKeyframe};
+pub use self::bvh::Bvh;
 pub use self::intersection::Intersection;
+pub use self::kdtree::KdTree;
 pub use self::ray::Ray;
 pub use self::octree::Octree;
 pub use self::renderer::{Renderer, RenderOptions};
+pub use self::stereo::Stereo;
 
+pub mod accelerator;
 pub mod animator;
+pub mod bvh;
+pub mod cache;
 pub mod compositor;
 pub mod intersection;
+pub mod kdtree;
 pub mod octree;
 pub mod ray;
 pub mod renderer;
+pub mod stereo;
@@ -1,7 +1,11 @@
+use std::io::{self, Read, Write};
 use std::slice::Iter;
-use std::iter::FromIterator;
+use std::iter::{Enumerate, FromIterator};
+use std::sync::mpsc::channel;
+use std::thread;
 use geometry::{BBox, PartialBoundingBox};
 use raytracer::Ray;
+use raytracer::cache::{self, CachedIndex};
 use vec3::Vec3;
 
 //
@@ -29,10 +33,10 @@ impl<T> FromIterator<T> for Octree<T> where T: PartialBoundingBox {
         let depth = (1.2 * (finites.len() as f64).log(8.0)).round() as i32;
 
         println!("Octree maximum depth {}", depth);
-        let mut root_node = OctreeNode::new(bounds, depth);
-        for (i, prim) in finites.iter().enumerate() {
-            root_node.insert(i, prim.partial_bounding_box().unwrap());
-        }
+        let data = finites.iter().enumerate().map(|(i, prim)| {
+            OctreeData { index: i, bbox: prim.partial_bounding_box().unwrap() }
+        }).collect();
+        let root_node = OctreeNode::new_parallel(bounds, depth, data);
 
         Octree {
             prims: finites,
@@ -42,7 +46,57 @@ impl<T> FromIterator<T> for Octree<T> where T: PartialBoundingBox {
     }
 }
 
+impl<T> CachedIndex<T> for Octree<T> where T: PartialBoundingBox {
+    type Index = OctreeNode;
+
+    fn write_index<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.root.write(writer)
+    }
+
+    fn read_index<R: Read>(reader: &mut R, finite_count: usize) -> io::Result<OctreeNode> {
+        OctreeNode::read(reader, finite_count)
+    }
+
+    fn with_index(prims: Vec<T>, root: OctreeNode) -> Octree<T> {
+        let (finites, infinites): (Vec<T>, Vec<T>) =
+            prims.into_iter().partition(|item| item.partial_bounding_box().is_some());
+
+        Octree {
+            prims: finites,
+            infinites: infinites,
+            root: root,
+        }
+    }
+}
+
 impl<T> Octree<T> where T: PartialBoundingBox {
+    /// Bounds of all prims, `None` if there are infinite ones (or none at all)
+    pub fn bounds(&self) -> Option<BBox> {
+        if self.infinites.is_empty() && !self.prims.is_empty() { Some(self.root.bbox) } else { None }
+    }
+
+    /// Calls `visit` with every prim whose bounds the ray passes through.
+    /// Unlike `intersect_iter`, the ray only needs to outlive the call.
+    pub fn traverse<'a, F>(&'a self, ray: &Ray, mut visit: F) where F: FnMut(&'a T) {
+        for prim in self.infinites.iter() {
+            visit(prim);
+        }
+
+        let mut stack = vec![&self.root];
+        while let Some(node) = stack.pop() {
+            for data in node.leaf_data.iter() {
+                if data.bbox.intersects(ray) {
+                    visit(&self.prims[data.index]);
+                }
+            }
+            for child in node.children.iter() {
+                if child.bbox.intersects(ray) {
+                    stack.push(child);
+                }
+            }
+        }
+    }
+
     pub fn intersect_iter<'a>(&'a self, ray: &'a Ray) -> OctreeIterator<'a, T> {
         OctreeIterator::new(self, ray)
     }
@@ -72,6 +126,88 @@ impl OctreeNode {
         }
     }
 
+    /// Builds the same tree as inserting every item in order, but fills in
+    /// the root's children on separate threads.
+    fn new_parallel(bbox: BBox, depth: i32, data: Vec<OctreeData>) -> OctreeNode {
+        let mut node = OctreeNode::new(bbox, depth);
+
+        // Below two items the root never subdivides
+        if data.len() < 2 || depth <= 0 {
+            for item in data.iter() {
+                node.insert(item.index, item.bbox);
+            }
+            return node;
+        }
+
+        node.subdivide();
+        let (tx, rx) = channel();
+
+        for (i, mut child) in node.children.drain(..).enumerate() {
+            let task_tx = tx.clone();
+            let child_data: Vec<OctreeData> = data.iter().filter(|item| child.bbox.overlaps(&item.bbox)).cloned().collect();
+
+            thread::spawn(move || {
+                for item in child_data.iter() {
+                    child.insert(item.index, item.bbox);
+                }
+                task_tx.send((i, child)).unwrap();
+            });
+        }
+        drop(tx);
+
+        let mut children: Vec<(usize, OctreeNode)> = rx.iter().collect();
+        children.sort_by_key(|&(i, _)| i);
+        node.children = children.into_iter().map(|(_, child)| child).collect();
+
+        node
+    }
+
+    /// Writes the subtree depth-first, each node followed by its children
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        try!(cache::write_bbox(writer, &self.bbox));
+        try!(cache::write_u64(writer, self.depth as i64 as u64));
+
+        try!(cache::write_u64(writer, self.leaf_data.len() as u64));
+        for data in self.leaf_data.iter() {
+            try!(cache::write_u64(writer, data.index as u64));
+            try!(cache::write_bbox(writer, &data.bbox));
+        }
+
+        try!(cache::write_u64(writer, self.children.len() as u64));
+        for child in self.children.iter() {
+            try!(child.write(writer));
+        }
+        Ok(())
+    }
+
+    fn read<R: Read>(reader: &mut R, prim_count: usize) -> io::Result<OctreeNode> {
+        let bbox = try!(cache::read_bbox(reader));
+        let depth = try!(cache::read_u64(reader)) as i64 as i32;
+        let mut node = OctreeNode::new(bbox, depth);
+
+        let leaf_count = try!(cache::read_len(reader, prim_count));
+        for _ in 0..leaf_count {
+            let index = try!(cache::read_indices(reader, 1, prim_count))[0];
+            let bbox = try!(cache::read_bbox(reader));
+            node.leaf_data.push(OctreeData { index: index, bbox: bbox });
+        }
+
+        // Children only ever come in eights, and the depth bounds the recursion
+        let child_count = try!(cache::read_len(reader, 8));
+        if child_count != 0 && (child_count != 8 || depth <= 0) {
+            return Err(cache::invalid_data("malformed octree node"));
+        }
+        for _ in 0..child_count {
+            let child = try!(OctreeNode::read(reader, prim_count));
+            if child.depth != depth - 1 {
+                return Err(cache::invalid_data("malformed octree node"));
+            }
+            node.children.push(child);
+        }
+
+        Ok(node)
+    }
+
     fn subdivide(&mut self) {
         for x in 0u32..2 {
             for y in 0u32..2 {
@@ -137,7 +273,7 @@ pub struct OctreeIterator<'a, T:'a> {
     stack: Vec<&'a OctreeNode>,
     leaf_iter: Option<Iter<'a, OctreeData>>,
     ray: &'a Ray,
-    infinites: Iter<'a, T>,
+    infinites: Enumerate<Iter<'a, T>>,
     just_infinites: bool
 }
 
@@ -149,7 +285,7 @@ impl<'a, T> OctreeIterator<'a, T> where T: PartialBoundingBox {
             stack: vec![&octree.root],
             leaf_iter: None,
             ray: ray,
-            infinites: octree.infinites.iter(),
+            infinites: octree.infinites.iter().enumerate(),
             just_infinites: false
         }
     }
@@ -157,18 +293,20 @@ impl<'a, T> OctreeIterator<'a, T> where T: PartialBoundingBox {
 
 
 impl<'a, T> Iterator for OctreeIterator<'a, T> where T: PartialBoundingBox {
-    type Item = &'a T;
+    type Item = (usize, &'a T);
 
-    fn next(&mut self) -> Option<&'a T> {
+    fn next(&mut self) -> Option<(usize, &'a T)> {
+        // Infinite prims are numbered after the finite ones
+        let finite_count = self.prims.len();
         if self.just_infinites {
-            return self.infinites.next();
+            return self.infinites.next().map(|(i, prim)| (finite_count + i, prim));
         }
 
         loop {
             let ray = self.ray;
             if let Some(leaf_iter) = self.leaf_iter.as_mut() {
                 if let Some(val) = leaf_iter.filter(|x| x.bbox.intersects(ray)).next() {
-                    return Some(&self.prims[val.index]);
+                    return Some((val.index, &self.prims[val.index]));
                 }
                 // iterator went empty, so we'll pop from the stack and
                 // iterate on the next node's children now,
@@ -183,7 +321,7 @@ impl<'a, T> Iterator for OctreeIterator<'a, T> where T: PartialBoundingBox {
                 self.leaf_iter = Some(node.leaf_data.iter());
             } else {
                 self.just_infinites = true;
-                return self.infinites.next()
+                return self.infinites.next().map(|(i, prim)| (finite_count + i, prim));
             }
         }
     }
@@ -10,6 +10,8 @@ use geometry::prims::Sphere;
 #[cfg(test)]
 use light::light::Light;
 #[cfg(test)]
+use raytracer::AcceleratorKind;
+#[cfg(test)]
 use material::materials::FlatMaterial;
 
 pub struct Ray {
@@ -17,6 +19,7 @@ pub struct Ray {
     pub direction: Vec3,
     pub inverse_dir: Vec3, // This is used to optimise ray-bbox intersection checks
     pub signs: [bool; 3], // Handle degenerate case in bbox intersection
+    pub time: f64, // Point in the shutter interval the ray samples, for motion blur
 }
 
 impl Ray {
@@ -37,32 +40,20 @@ impl Ray {
                 inv_x > 0.0,
                 inv_y > 0.0,
                 inv_z > 0.0
-            ]
+            ],
+            time: 0.0
         }
     }
 
-    pub fn get_nearest_hit<'a>(&'a self, scene: &'a Scene) -> Option<Intersection<'a>> {
-        let t_min = 0.000001;
-        let mut nearest_hit = None;
-        let mut nearest_t = INFINITY;
-
-        for prim in scene.octree.intersect_iter(self) {
-            let intersection = prim.intersects(self, t_min, nearest_t);
-
-            nearest_hit = match intersection {
-                Some(intersection) => {
-                    if intersection.t > t_min && intersection.t < nearest_t {
-                        nearest_t = intersection.t;
-                        Some(intersection)
-                    } else {
-                        nearest_hit
-                    }
-                },
-                None => nearest_hit
-            };
-        }
+    /// Secondary rays should use this to stay at the same point in time as their parent.
+    pub fn new_at_time(origin: Vec3, direction: Vec3, time: f64) -> Ray {
+        Ray { time: time, ..Ray::new(origin, direction) }
+    }
 
-        nearest_hit
+    /// Secondary rays start just off the surface they leave (see
+    /// `Intersection::offset_origin`), so nothing is skipped near the origin
+    pub fn get_nearest_hit<'a>(&'a self, scene: &'a Scene) -> Option<Intersection<'a>> {
+        scene.accelerator.nearest_hit(self, 0.0, INFINITY)
     }
 
     pub fn perturb(&self, magnitude: f64) -> Ray {
@@ -77,7 +68,7 @@ impl Ray {
 
         let direction = (corrected_rand_vec + self.direction).unit();
 
-        Ray::new(self.origin, direction)
+        Ray::new_at_time(self.origin, direction, self.time)
     }
 }
 
@@ -87,33 +78,17 @@ fn it_gets_the_nearest_hit() {
 
     let mut prims: Vec<Box<Prim+Send+Sync>> = Vec::new();
     let mat = FlatMaterial { color: Vec3::one() };
-    let sphere_top = Sphere {
-        center: Vec3::zero(),
-        radius: 1.0,
-        material: Box::new(mat.clone()),
-    };
-    let sphere_mid = Sphere {
-        center: Vec3 { x: -1.0, y: 0.0, z: 0.0 },
-        radius: 1.0,
-        material: Box::new(mat.clone()),
-    };
-    let sphere_bot = Sphere {
-        center: Vec3 { x: -2.0, y: 0.0, z: 0.0 },
-        radius: 1.0,
-        material: Box::new(mat.clone()),
-    };
+    let sphere_top = Sphere::new(Vec3::zero(), 1.0, Box::new(mat.clone()));
+    let sphere_mid = Sphere::new(Vec3 { x: -1.0, y: 0.0, z: 0.0 }, 1.0, Box::new(mat.clone()));
+    let sphere_bot = Sphere::new(Vec3 { x: -2.0, y: 0.0, z: 0.0 }, 1.0, Box::new(mat.clone()));
     prims.push(Box::new(sphere_top));
     prims.push(Box::new(sphere_mid));
     prims.push(Box::new(sphere_bot));
 
-    println!("Generating octree...");
-    let octree = prims.into_iter().collect();
-    println!("Octree generated...");
-
     let scene = Scene {
         lights: lights,
         background: Vec3::one(),
-        octree: octree,
+        accelerator: AcceleratorKind::Octree.build(prims),
         skybox: None
     };
 
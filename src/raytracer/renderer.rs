@@ -9,7 +9,8 @@ use vec3::Vec3;
 use rand::{thread_rng, Rng};
 use threadpool::ThreadPool;
 
-pub static EPSILON: f64 = ::std::f64::EPSILON * 10000.0;
+#[cfg(test)]
+use raytracer::AcceleratorKind;
 
 #[derive(Clone, Copy)]
 pub struct RenderOptions {
@@ -88,8 +89,14 @@ impl Renderer {
                             (0.0, 0.0)
                         };
 
-                        let ray = camera.get_ray(abs_x as f64 + j_x, abs_y as f64 + j_y);
-                        let result = Renderer::trace(scene, &ray, options, false);
+                        let (x, y) = (abs_x as f64 + j_x, abs_y as f64 + j_y);
+                        let ray = if camera.is_motion_blurred() {
+                            camera.get_ray_in_shutter(x, y, rng.gen::<f64>())
+                        } else {
+                            camera.get_ray(x, y)
+                        };
+                        let result = Renderer::trace(scene, &ray, options)
+                            .scale(camera.exposure_scale(x, y));
                         // Clamp subpixels for now to avoid intense aliasing when combined value is clamped later
                         // Should think of a better way to handle this
                         color = color + result.clamp(0.0, 1.0).scale(1.0 / (pixel_samples * pixel_samples) as f64);
@@ -102,20 +109,29 @@ impl Renderer {
         tile
     }
 
-    fn trace(scene: &Scene, ray: &Ray, options: RenderOptions, inside: bool) -> Vec3 {
+    fn trace(scene: &Scene, ray: &Ray, options: RenderOptions) -> Vec3 {
         if options.reflect_depth <= 0 || options.refract_depth <= 0 { return Vec3::zero() }
 
         match ray.get_nearest_hit(scene) {
             Some(hit) => {
-                let n = hit.n.unit();
                 let i = (-ray.direction).unit();
 
+                // Whether the ray is inside is up to the facet it crossed.
+                // Smooth normals can turn away from the eye near silhouettes,
+                // which would shade black and refract wrongly; use the facet
+                // there instead.
+                let ng = hit.ng.unit();
+                let inside = i.dot(&ng) < 0.0;
+                let n = if i.dot(&hit.n) * i.dot(&ng) <= 0.0 { ng } else { hit.n.unit() };
+
                 // Local lighting computation: surface shading, shadows
                 let mut result = scene.lights.iter().fold(Vec3::zero(), |color_acc, light| {
-                    let shadow = Renderer::shadow_intensity(scene, &hit, light, options.shadow_samples);
+                    let shadow = Renderer::shadow_intensity(scene, &hit, ray.time, light, options.shadow_samples);
                     let l = (light.center() - hit.position).unit();
 
-                    color_acc + light.color() * hit.material.sample(n, i, l, hit.u, hit.v) * shadow
+                    let color = hit.material.sample_tangent(n, hit.tangent(), i, l, hit.u, hit.v);
+
+                    color_acc + light.color() * color * shadow
                 });
 
                 // Global lighting computation: reflections, refractions
@@ -124,12 +140,12 @@ impl Renderer {
                     let refract_fresnel = 1.0 - reflect_fresnel;
 
                     if hit.material.is_reflective() {
-                        result = result + Renderer::global_reflection(scene, &hit, options, inside,
+                        result = result + Renderer::global_reflection(scene, &hit, ray.time, options,
                                                                       &i, &n, reflect_fresnel);
                     }
 
                     if hit.material.is_refractive() {
-                        result = result + Renderer::global_transmission(scene, &hit, options, inside,
+                        result = result + Renderer::global_transmission(scene, &hit, ray.time, options, inside,
                                                                         &i, &n, refract_fresnel);
                     }
                 }
@@ -145,11 +161,11 @@ impl Renderer {
         }
     }
 
-    fn global_reflection(scene: &Scene, hit: &Intersection, options: RenderOptions, inside: bool,
+    fn global_reflection(scene: &Scene, hit: &Intersection, time: f64, options: RenderOptions,
                          i: &Vec3, n: &Vec3, reflect_fresnel: f64) -> Vec3 {
 
         let r = Vec3::reflect(&i, &n);
-        let reflect_ray = Ray::new(hit.position, r);
+        let reflect_ray = Ray::new_at_time(hit.offset_origin(&r), r, time);
         let next_reflect_options = RenderOptions { reflect_depth: options.reflect_depth - 1, ..options };
 
         let reflection = if hit.material.is_glossy() {
@@ -157,17 +173,17 @@ impl Renderer {
             // Potential overflow by scaling after everything is done instead of scaling every iteration?
             (0..options.gloss_samples).fold(Vec3::zero(), |acc, _| {
                 let gloss_reflect_ray = reflect_ray.perturb(hit.material.glossiness());
-                acc + Renderer::trace(scene, &gloss_reflect_ray, next_reflect_options, inside)
+                acc + Renderer::trace(scene, &gloss_reflect_ray, next_reflect_options)
             }).scale(1.0 / options.gloss_samples as f64)
         } else {
             // For mirror-like materials just shoot a perfectly reflected ray instead
-            Renderer::trace(scene, &reflect_ray, next_reflect_options, inside)
+            Renderer::trace(scene, &reflect_ray, next_reflect_options)
         };
 
         hit.material.global_specular(&reflection).scale(reflect_fresnel)
     }
 
-    fn global_transmission(scene: &Scene, hit: &Intersection, options: RenderOptions, inside: bool,
+    fn global_transmission(scene: &Scene, hit: &Intersection, time: f64, options: RenderOptions, inside: bool,
                            i: &Vec3, n: &Vec3, refract_fresnel: f64) -> Vec3 {
 
         let (t, actual_refract_fresnel) = match Vec3::refract(&i, &n, hit.material.ior(), inside) {
@@ -177,15 +193,14 @@ impl Renderer {
             }
         };
 
-        // Offset ray origin by EPSILON * direction to avoid hitting self when refracting
-        let refract_ray = Ray::new(hit.position + t.scale(EPSILON), t);
+        let refract_ray = Ray::new_at_time(hit.offset_origin(&t), t, time);
         let next_refract_options = RenderOptions { refract_depth: options.refract_depth - 1, ..options };
-        let refraction = Renderer::trace(scene, &refract_ray, next_refract_options, !inside);
+        let refraction = Renderer::trace(scene, &refract_ray, next_refract_options);
 
         hit.material.global_transmissive(&refraction).scale(actual_refract_fresnel)
     }
 
-    fn shadow_intensity(scene: &Scene, hit: &Intersection,
+    fn shadow_intensity(scene: &Scene, hit: &Intersection, time: f64,
                         light: &Box<Light+Send+Sync>, shadow_samples: u32) -> Vec3 {
 
         if shadow_samples <= 0 { return Vec3::one() }
@@ -201,20 +216,13 @@ impl Renderer {
             // until light source.
             let sampled_light_position = light.position();
             let shadow_l = (sampled_light_position - hit.position).unit();
-            let shadow_ray = Ray::new(hit.position, shadow_l);
-            let distance_to_light = (sampled_light_position - hit.position).len();
-
-            // Check against candidate primitives in scene for occlusion
-            // and multiply shadow color by occluders' shadow colors
-            let candidate_nodes = scene.octree.intersect_iter(&shadow_ray);
-
-            shadow = shadow + candidate_nodes.fold(Vec3::one(), |shadow_acc, prim| {
-                let occlusion = prim.intersects(&shadow_ray, EPSILON, distance_to_light);
-                match occlusion {
-                    Some(occlusion) => shadow_acc * occlusion.material.transmission(),
-                    None => shadow_acc
-                }
-            });
+            let origin = hit.offset_origin(&shadow_l);
+            let shadow_ray = Ray::new_at_time(origin, shadow_l, time);
+            let distance_to_light = (sampled_light_position - origin).len();
+
+            // Multiply shadow color by occluders' shadow colors,
+            // any opaque occluder fully shadows the sample
+            shadow = shadow + scene.accelerator.transmittance(&shadow_ray, 0.0, distance_to_light);
         }
 
         shadow.scale(1.0 / shadow_sample_tries as f64)
@@ -260,7 +268,7 @@ fn it_renders_the_background_of_an_empty_scene() {
 
     let test_scene = Scene {
         lights: vec!(),
-        octree: vec!().into_iter().collect(),
+        accelerator: AcceleratorKind::Octree.build(vec!()),
         background: Vec3 { x: 1.0, y: 0.0, z: 0.0 },
         skybox: None
     };
@@ -0,0 +1,96 @@
+use raytracer::compositor::{ColorRGBA, Surface};
+use raytracer::Renderer;
+use scene::{Camera, Scene};
+use std::sync::Arc;
+
+/// How the two eyes are written out.
+#[derive(Clone, Copy, PartialEq, Debug, RustcDecodable, RustcEncodable)]
+pub enum StereoLayout {
+    SideBySide, // Left eye on the left, in one double-width image
+    OverUnder,  // Left eye on top, in one double-height image
+    Separate    // One file per eye
+}
+
+#[derive(Clone, Copy, RustcDecodable, RustcEncodable)]
+pub struct Stereo {
+    pub eye_separation: f64,       // Distance between the eyes, in scene units
+    pub convergence_distance: f64, // Objects this far away appear at screen depth
+    pub layout: StereoLayout
+}
+
+impl Stereo {
+    /// Checks settings read from a config. Eyes converging at or behind the
+    /// camera would give rays of infinite or flipped parallax.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.convergence_distance > 0.0) {
+            return Err(format!("stereo convergence_distance must be greater than 0, got {}", self.convergence_distance));
+        }
+        Ok(())
+    }
+
+    /// Renders both eyes and returns the images to write out, each paired
+    /// with the suffix to append to its filename.
+    pub fn render(&self, renderer: &Renderer, camera: Camera, shared_scene: Arc<Scene>)
+                  -> Vec<(&'static str, Surface)> {
+
+        let (left_camera, right_camera) = camera.stereo_pair(self.eye_separation, self.convergence_distance);
+        let left = renderer.render(left_camera, Arc::clone(&shared_scene));
+        let right = renderer.render(right_camera, shared_scene);
+
+        match self.layout {
+            StereoLayout::SideBySide => vec![("", Stereo::compose(left, right, true))],
+            StereoLayout::OverUnder => vec![("", Stereo::compose(left, right, false))],
+            StereoLayout::Separate => vec![("_left", left), ("_right", right)]
+        }
+    }
+
+    fn compose(mut left: Surface, mut right: Surface, side_by_side: bool) -> Surface {
+        let (width, height) = if side_by_side {
+            (left.width * 2, left.height)
+        } else {
+            (left.width, left.height * 2)
+        };
+
+        let mut surface = Surface::new(width, height, ColorRGBA::new_rgb(0, 0, 0));
+
+        left.x_off = 0;
+        left.y_off = 0;
+        if side_by_side {
+            right.x_off = left.width;
+            right.y_off = 0;
+        } else {
+            right.x_off = 0;
+            right.y_off = left.height;
+        }
+
+        surface.merge(&left);
+        surface.merge(&right);
+        surface
+    }
+}
+
+#[test]
+fn it_lays_out_eyes() {
+    let left = Surface::new(2, 1, ColorRGBA::new_rgb(255, 0, 0));
+    let right = Surface::new(2, 1, ColorRGBA::new_rgb(0, 0, 255));
+
+    let side_by_side = Stereo::compose(left.clone(), right.clone(), true);
+    assert_eq!((side_by_side.width, side_by_side.height), (4, 1));
+    assert_eq!(side_by_side[(1, 0)].r, 255);
+    assert_eq!(side_by_side[(2, 0)].b, 255);
+
+    let over_under = Stereo::compose(left, right, false);
+    assert_eq!((over_under.width, over_under.height), (2, 2));
+    assert_eq!(over_under[(1, 0)].r, 255);
+    assert_eq!(over_under[(0, 1)].b, 255);
+}
+
+#[test]
+fn it_rejects_convergence_at_or_behind_the_camera() {
+    let stereo = |convergence_distance: f64| Stereo { eye_separation: 6.5, convergence_distance: convergence_distance, layout: StereoLayout::SideBySide };
+
+    assert!(stereo(300.0).validate().is_ok());
+    assert!(stereo(0.0).validate().is_err());
+    assert!(stereo(-300.0).validate().is_err());
+    assert!(stereo(::std::f64::NAN).validate().is_err());
+}
@@ -1,19 +1,47 @@
 use raytracer::Ray;
 use raytracer::animator::CameraKeyframe;
 use raytracer::animator::easing::Easing;
+use scene::{Exposure, Projection};
+use std::f64::consts::PI;
 use vec3::Vec3;
 
+/// Which image axis `fov_deg` is measured along. The other axis follows from the aspect ratio.
+#[derive(Clone, Copy, PartialEq, Debug, RustcDecodable, RustcEncodable)]
+pub enum FovAxis {
+    Horizontal,
+    Vertical
+}
+
 #[derive(Clone)]
 pub struct Camera {
     pub position: Vec3,
     pub look_at: Vec3,
     pub up: Vec3,
     pub fov_deg: f64,
+    pub fov_axis: FovAxis,
+    pub roll_deg: f64, // Clockwise rotation about the view direction
     pub image_width: u32,
     pub image_height: u32,
+    pub aspect_ratio: Option<f64>, // Width / height of the image plane, from the image size if None
+    pub projection: Projection,
+
+    // Time the camera was posed at, and the shutter interval relative to it.
+    // Primary rays are spread over the interval when it is non-empty.
+    pub time: f64,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+
+    // For stereo pairs: how far this eye sits along `right` from `position`,
+    // and the distance in front of the camera where both eyes' images line up.
+    pub eye_offset: f64,
+    pub convergence_distance: f64,
 
+    pub exposure: Option<Exposure>,
+
+    // Orthonormal camera frame. `view_up` is `up` made perpendicular to `eye` and rolled.
     pub eye: Vec3,
     pub right: Vec3,
+    pub view_up: Vec3,
     pub half_width: f64,
     pub half_height: f64,
     pub pixel_width: f64,
@@ -32,10 +60,21 @@ impl Camera {
             look_at: look_at,
             up: up,
             fov_deg: fov_deg,
+            fov_axis: FovAxis::Horizontal,
+            roll_deg: 0.0,
             image_width: image_width,
             image_height: image_height,
+            aspect_ratio: None,
+            projection: Projection::Perspective,
+            time: 0.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            eye_offset: 0.0,
+            convergence_distance: 0.0,
+            exposure: None,
             eye: Vec3::zero(),
             right: Vec3::zero(),
+            view_up: Vec3::zero(),
             half_width: 0.0,
             half_height: 0.0,
             pixel_width: 0.0,
@@ -60,13 +99,136 @@ impl Camera {
     }
 
     pub fn get_ray(&self, x: f64, y: f64) -> Ray {
+        let (screen_x, screen_y) = self.screen_position(x, y);
+
+        let mut ray = match self.projection {
+            Projection::Perspective => self.get_perspective_ray(screen_x, screen_y),
+            Projection::Orthographic => self.get_orthographic_ray(screen_x, screen_y),
+            Projection::Fisheye => self.get_fisheye_ray(screen_x, screen_y),
+            Projection::Equirectangular => self.get_equirectangular_ray(screen_x, screen_y)
+        };
+
+        if self.eye_offset != 0.0 {
+            ray.origin = ray.origin + self.right.scale(self.eye_offset);
+        }
+
+        ray.time = self.time;
+        ray
+    }
+
+    /// Splits the camera into left and right eyes `eye_separation` apart. The
+    /// eyes keep parallel view directions and shift their image planes
+    /// instead (off-axis stereo), so there is no vertical parallax.
+    pub fn stereo_pair(&self, eye_separation: f64, convergence_distance: f64) -> (Camera, Camera) {
+        let mut left = self.clone();
+        left.eye_offset = -eye_separation / 2.0;
+        left.convergence_distance = convergence_distance;
+
+        let mut right = self.clone();
+        right.eye_offset = eye_separation / 2.0;
+        right.convergence_distance = convergence_distance;
+
+        (left, right)
+    }
+
+    /// Like `get_ray`, but for a point in time within the shutter interval.
+    /// `shutter_sample` in [0, 1) picks the time; keyframed cameras are
+    /// moved along their keyframes to that time first.
+    pub fn get_ray_in_shutter(&self, x: f64, y: f64, shutter_sample: f64) -> Ray {
+        let time = self.time + self.shutter_open + (self.shutter_close - self.shutter_open) * shutter_sample;
+
+        match self.keyframes {
+            Some(_) => self.at_time(time).get_ray(x, y),
+            None => {
+                let mut ray = self.get_ray(x, y);
+                ray.time = time;
+                ray
+            }
+        }
+    }
+
+    pub fn is_motion_blurred(&self) -> bool {
+        self.shutter_close > self.shutter_open
+    }
+
+    /// How much to scale the radiance arriving through (x, y) by when turning it
+    /// into a pixel value. Natural vignetting only applies to the perspective projection.
+    pub fn exposure_scale(&self, x: f64, y: f64) -> f64 {
+        let exposure = match self.exposure {
+            Some(ref exposure) => exposure,
+            None => return 1.0
+        };
+
+        if exposure.vignetting && self.projection == Projection::Perspective {
+            let (screen_x, screen_y) = self.screen_position(x, y);
+            let cos_sq = 1.0 / (1.0 + screen_x * screen_x + screen_y * screen_y);
+
+            exposure.scale() * cos_sq * cos_sq
+        } else {
+            exposure.scale()
+        }
+    }
+
+    /// Offsets from the image centre on the image plane one unit in front of the camera
+    fn screen_position(&self, x: f64, y: f64) -> (f64, f64) {
+        (x * self.pixel_width - self.half_width,
+         y * self.pixel_height - self.half_height)
+    }
+
+    fn get_perspective_ray(&self, screen_x: f64, screen_y: f64) -> Ray {
+        let shifted_x = if self.eye_offset != 0.0 {
+            screen_x - self.eye_offset / self.convergence_distance
+        } else {
+            screen_x
+        };
+
         Ray::new(
             self.position,
-            (self.eye + self.right.scale(x * self.pixel_width - self.half_width) +
-            self.up.scale(y * self.pixel_height - self.half_height)).unit()
+            (self.eye + self.right.scale(shifted_x) + self.view_up.scale(screen_y)).unit()
         )
     }
 
+    /// The image plane is scaled up to the focal plane through `look_at`,
+    /// so switching projections keeps the subject the same size.
+    fn get_orthographic_ray(&self, screen_x: f64, screen_y: f64) -> Ray {
+        let focal_distance = (self.look_at - self.position).len();
+        let origin = self.position +
+                     self.right.scale(screen_x * focal_distance) +
+                     self.view_up.scale(screen_y * focal_distance);
+
+        Ray::new(origin, self.eye)
+    }
+
+    /// Equidistant fisheye: the angle from the view axis grows linearly
+    /// with the distance from the image centre.
+    fn get_fisheye_ray(&self, screen_x: f64, screen_y: f64) -> Ray {
+        let fov_extent = match self.fov_axis {
+            FovAxis::Horizontal => self.half_width,
+            FovAxis::Vertical => self.half_height
+        };
+        let r = (screen_x * screen_x + screen_y * screen_y).sqrt() / fov_extent;
+        let theta = r * self.fov_deg.to_radians();
+        let phi = screen_y.atan2(screen_x);
+
+        let direction = self.eye.scale(theta.cos()) +
+                        (self.right.scale(phi.cos()) + self.view_up.scale(phi.sin())).scale(theta.sin());
+
+        Ray::new(self.position, direction.unit())
+    }
+
+    /// Longitude spans [-PI, PI] across the width of the image and latitude
+    /// spans [-PI/2, PI/2] across its height, centred on the view direction.
+    fn get_equirectangular_ray(&self, screen_x: f64, screen_y: f64) -> Ray {
+        let longitude = screen_x / self.half_width * PI;
+        let latitude = screen_y / self.half_height * PI / 2.0;
+
+        let direction = (self.eye.scale(longitude.cos()) + self.right.scale(longitude.sin()))
+                            .scale(latitude.cos()) +
+                        self.view_up.scale(latitude.sin());
+
+        Ray::new(self.position, direction.unit())
+    }
+
     /// Add additional keyframes to the camera. The current state of the camera
     /// is treated as t=0, and a new keyframe at t=0 is created and added.
     #[allow(dead_code)]
@@ -85,22 +247,296 @@ impl Camera {
         self.keyframes = Some(keyframes);
     }
 
+    /// Poses the camera at `time` by interpolating between its keyframes.
+    /// The returned camera does not carry the keyframes along.
+    pub fn at_time(&self, time: f64) -> Camera {
+        let keyframes = match self.keyframes {
+            Some(ref k) => k,
+            None => panic!("Cannot lerp a camera with no keyframes!")
+        };
+
+        let (first, second, alpha) = Camera::get_neighbour_keyframes(keyframes, time);
+
+        let mut camera = Camera {
+            position: Vec3::lerp(&first.position, &second.position, alpha),
+            look_at: Vec3::lerp(&first.look_at, &second.look_at, alpha),
+            up: Vec3::lerp(&first.up, &second.up, alpha),
+            time: time,
+            keyframes: None,
+            ..*self
+        };
+
+        camera.update_eye_vector();
+        camera
+    }
+
+    fn get_neighbour_keyframes(keyframes: &[CameraKeyframe], time: f64)
+                               -> (&CameraKeyframe, &CameraKeyframe, f64) {
+
+        if keyframes.len() <= 1 {
+            panic!("Not enough keyframes to interpolate: got: {} expected: >= 2", keyframes.len());
+        }
+
+        // Get the two keyframes inbetween current time
+        let mut first = &keyframes[0];
+        let mut second = &keyframes[1];
+
+        for keyframe in keyframes {
+            if keyframe.time <= time && time - keyframe.time >= first.time - time {
+                first = keyframe;
+            }
+
+            if keyframe.time > time &&
+               (keyframe.time - time < second.time - time || second.time < time) {
+                second = keyframe;
+            }
+        }
+
+        let keyframe_length = second.time - first.time;
+
+        let alpha = if keyframe_length == 0.0 {
+            0.0
+        } else {
+            second.easing.t((time - first.time) / keyframe_length)
+        };
+
+        (first, second, alpha)
+    }
+
+    pub fn set_roll(&mut self, roll_deg: f64) {
+        self.roll_deg = roll_deg;
+        self.update_eye_vector();
+    }
+
+    pub fn set_fov_axis(&mut self, fov_axis: FovAxis) {
+        self.fov_axis = fov_axis;
+        self.update_internal_sizes();
+    }
+
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f64) {
+        self.aspect_ratio = Some(aspect_ratio);
+        self.update_internal_sizes();
+    }
+
+    /// Builds an orthonormal frame from the view direction, re-deriving up
+    /// so an `up` that isn't perpendicular to the view doesn't skew the image.
     fn update_eye_vector(&mut self) {
         self.eye = (self.look_at - self.position).unit();
-        self.right = self.eye.cross(&self.up);
+        let right = self.eye.cross(&self.up).unit();
+        let up = right.cross(&self.eye);
+
+        // Clockwise roll, as seen looking down the view direction
+        let (sin_roll, cos_roll) = self.roll_deg.to_radians().sin_cos();
+        self.right = right.scale(cos_roll) - up.scale(sin_roll);
+        self.view_up = up.scale(cos_roll) + right.scale(sin_roll);
     }
 
     fn update_internal_sizes(&mut self) {
-        let fov_rad = self.fov_deg.to_radians();
-        let ratio = self.image_height as f64 / self.image_width as f64;
+        let tan_fov = self.fov_deg.to_radians().tan();
+        let aspect_ratio = self.aspect_ratio.unwrap_or(self.image_width as f64 / self.image_height as f64);
 
-        self.half_width  = fov_rad.tan();
-        self.half_height = self.half_width * ratio;
+        match self.fov_axis {
+            FovAxis::Horizontal => {
+                self.half_width  = tan_fov;
+                self.half_height = tan_fov / aspect_ratio;
+            },
+            FovAxis::Vertical => {
+                self.half_width  = tan_fov * aspect_ratio;
+                self.half_height = tan_fov;
+            }
+        }
 
         let camera_width  = self.half_width  * 2.0;
         let camera_height = self.half_height * 2.0;
 
-        self.pixel_width  = camera_width  / (self.image_width  - 1) as f64;
-        self.pixel_height = camera_height / (self.image_height - 1) as f64;
+        // Single pixel images would otherwise divide by zero
+        self.pixel_width  = camera_width  / (self.image_width.max(2)  - 1) as f64;
+        self.pixel_height = camera_height / (self.image_height.max(2) - 1) as f64;
     }
 }
+
+#[cfg(test)]
+fn get_test_camera(projection: Projection) -> Camera {
+    let mut camera = Camera::new(
+        Vec3 { x: 0.0, y: 0.0, z: 10.0 },
+        Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        45.0,
+        101,
+        51
+    );
+    camera.projection = projection;
+    camera
+}
+
+#[cfg(test)]
+fn assert_approx_eq(a: Vec3, b: Vec3) {
+    assert!((a - b).len() < 1e-9, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn it_casts_parallel_orthographic_rays() {
+    let camera = get_test_camera(Projection::Orthographic);
+    let forward = Vec3 { x: 0.0, y: 0.0, z: -1.0 };
+
+    let corner = camera.get_ray(0.0, 0.0);
+    assert_approx_eq(corner.direction, forward);
+    // tan(45deg) * 10 units to look_at
+    assert_approx_eq(corner.origin, Vec3 { x: -10.0, y: -10.0 * camera.half_height, z: 10.0 });
+
+    let centre = camera.get_ray(50.0, 25.0);
+    assert_approx_eq(centre.direction, forward);
+    assert_approx_eq(centre.origin, camera.position);
+}
+
+#[test]
+fn it_casts_fisheye_rays_by_angle() {
+    let camera = get_test_camera(Projection::Fisheye);
+
+    assert_approx_eq(camera.get_ray(50.0, 25.0).direction, Vec3 { x: 0.0, y: 0.0, z: -1.0 });
+
+    // Horizontal edges are fov_deg off-axis
+    let half_sqrt2 = 0.5f64.sqrt();
+    assert_approx_eq(camera.get_ray(100.0, 25.0).direction, Vec3 { x: half_sqrt2, y: 0.0, z: -half_sqrt2 });
+    assert_approx_eq(camera.get_ray(0.0, 25.0).direction, Vec3 { x: -half_sqrt2, y: 0.0, z: -half_sqrt2 });
+}
+
+#[test]
+fn it_casts_equirectangular_rays_all_around() {
+    let camera = get_test_camera(Projection::Equirectangular);
+
+    assert_approx_eq(camera.get_ray(50.0, 25.0).direction, Vec3 { x: 0.0, y: 0.0, z: -1.0 });
+    assert_approx_eq(camera.get_ray(75.0, 25.0).direction, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+    assert_approx_eq(camera.get_ray(0.0, 25.0).direction, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    assert_approx_eq(camera.get_ray(50.0, 50.0).direction, Vec3 { x: 0.0, y: 1.0, z: 0.0 });
+    assert_approx_eq(camera.get_ray(50.0, 0.0).direction, Vec3 { x: 0.0, y: -1.0, z: 0.0 });
+}
+
+#[test]
+fn it_samples_keyframes_within_the_shutter_interval() {
+    let mut camera = Camera::new_with_keyframes(
+        Vec3 { x: 0.0, y: 0.0, z: 10.0 },
+        Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+        45.0,
+        101,
+        51,
+        vec![
+            CameraKeyframe {
+                time: 1.0,
+                position: Vec3 { x: 10.0, y: 0.0, z: 10.0 },
+                look_at: Vec3 { x: 10.0, y: 0.0, z: 0.0 },
+                up: Vec3 { x: 0.0, y: 1.0, z: 0.0 },
+                easing: Easing::linear()
+            }
+        ]
+    );
+    camera.shutter_open = 0.0;
+    camera.shutter_close = 0.5;
+    assert!(camera.is_motion_blurred());
+
+    let opening = camera.get_ray_in_shutter(50.0, 25.0, 0.0);
+    assert_eq!(opening.time, 0.0);
+    assert_approx_eq(opening.origin, Vec3 { x: 0.0, y: 0.0, z: 10.0 });
+
+    let closing = camera.get_ray_in_shutter(50.0, 25.0, 1.0);
+    assert_eq!(closing.time, 0.5);
+    assert_approx_eq(closing.origin, Vec3 { x: 5.0, y: 0.0, z: 10.0 });
+    assert_approx_eq(closing.direction, Vec3 { x: 0.0, y: 0.0, z: -1.0 });
+}
+
+#[test]
+fn it_converges_stereo_eyes() {
+    let camera = get_test_camera(Projection::Perspective);
+    let (left, right) = camera.stereo_pair(0.5, 4.0);
+
+    let left_ray = left.get_ray(50.0, 25.0);
+    let right_ray = right.get_ray(50.0, 25.0);
+    assert_approx_eq(left_ray.origin, Vec3 { x: -0.25, y: 0.0, z: 10.0 });
+    assert_approx_eq(right_ray.origin, Vec3 { x: 0.25, y: 0.0, z: 10.0 });
+
+    // Centre rays meet on the view axis at the convergence distance
+    let convergence_point = Vec3 { x: 0.0, y: 0.0, z: 6.0 };
+    assert_approx_eq(left_ray.direction, (convergence_point - left_ray.origin).unit());
+    assert_approx_eq(right_ray.direction, (convergence_point - right_ray.origin).unit());
+}
+
+#[test]
+fn it_vignettes_towards_the_edges() {
+    let mut camera = get_test_camera(Projection::Perspective);
+    assert_eq!(camera.exposure_scale(0.0, 0.0), 1.0);
+
+    camera.exposure = Some(Exposure { f_number: 2.0, shutter_speed: 1.0, iso: 100.0, vignetting: true });
+    assert!((camera.exposure_scale(50.0, 25.0) - 0.25).abs() < 1e-12);
+
+    // 45 degrees off-axis at the horizontal edge: cos^4 = 0.25
+    assert!((camera.exposure_scale(100.0, 25.0) - 0.25 * 0.25).abs() < 1e-12);
+    assert!(camera.exposure_scale(100.0, 50.0) < camera.exposure_scale(100.0, 25.0));
+}
+
+#[test]
+fn it_orthonormalizes_a_skewed_up_vector() {
+    let camera = Camera::new(
+        Vec3 { x: 0.0, y: 0.0, z: 10.0 },
+        Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+        Vec3 { x: 0.0, y: 1.0, z: 1.0 },
+        45.0,
+        100,
+        100
+    );
+
+    assert_approx_eq(camera.eye, Vec3 { x: 0.0, y: 0.0, z: -1.0 });
+    assert_approx_eq(camera.right, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+    assert_approx_eq(camera.view_up, Vec3 { x: 0.0, y: 1.0, z: 0.0 });
+}
+
+#[test]
+fn it_rolls_the_camera_frame() {
+    let mut camera = get_test_camera(Projection::Perspective);
+    camera.set_roll(90.0);
+
+    // Rolled clockwise: the top of the image now points along +x
+    assert_approx_eq(camera.view_up, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+    assert_approx_eq(camera.right, Vec3 { x: 0.0, y: -1.0, z: 0.0 });
+    assert_approx_eq(camera.eye, Vec3 { x: 0.0, y: 0.0, z: -1.0 });
+}
+
+#[test]
+fn it_sizes_the_image_plane_from_a_horizontal_fov() {
+    let camera = get_test_camera(Projection::Perspective);
+
+    assert!((camera.half_width - 1.0).abs() < 1e-12);
+    assert!((camera.half_height - 51.0 / 101.0).abs() < 1e-12);
+    assert!((camera.pixel_width - 2.0 / 100.0).abs() < 1e-12);
+    assert!((camera.pixel_height - 2.0 * 51.0 / 101.0 / 50.0).abs() < 1e-12);
+}
+
+#[test]
+fn it_sizes_the_image_plane_from_a_vertical_fov() {
+    let mut camera = get_test_camera(Projection::Perspective);
+    camera.set_fov_axis(FovAxis::Vertical);
+
+    assert!((camera.half_height - 1.0).abs() < 1e-12);
+    assert!((camera.half_width - 101.0 / 51.0).abs() < 1e-12);
+    assert!((camera.pixel_height - 2.0 / 50.0).abs() < 1e-12);
+}
+
+#[test]
+fn it_sizes_the_image_plane_from_an_aspect_ratio() {
+    let mut camera = get_test_camera(Projection::Perspective);
+    camera.set_aspect_ratio(2.39);
+
+    // Anamorphic: image plane is 2.39:1 regardless of the 101x51 image
+    assert!((camera.half_width - 1.0).abs() < 1e-12);
+    assert!((camera.half_height - 1.0 / 2.39).abs() < 1e-12);
+    assert!((camera.pixel_height - 2.0 / 2.39 / 50.0).abs() < 1e-12);
+}
+
+#[test]
+fn it_handles_single_pixel_images() {
+    let camera = Camera::new(Vec3::zero(), Vec3 { x: 0.0, y: 0.0, z: -1.0 },
+                             Vec3 { x: 0.0, y: 1.0, z: 0.0 }, 45.0, 1, 1);
+
+    assert!(camera.pixel_width.is_finite());
+    assert!(camera.pixel_height.is_finite());
+}
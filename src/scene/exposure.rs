@@ -0,0 +1,41 @@
+/// Photographic exposure settings for the camera.
+///
+/// Rendered radiance is scaled by `shutter_speed * iso / (100 * f_number^2)`,
+/// so f/1 at 1s and ISO 100 (EV100 0) leaves the image at its unscaled
+/// brightness. Each stop of aperture, shutter or ISO halves or doubles it.
+#[derive(Clone, Copy, RustcDecodable, RustcEncodable)]
+pub struct Exposure {
+    pub f_number: f64,      // Aperture, e.g. 2.8 for f/2.8
+    pub shutter_speed: f64, // In seconds, e.g. 0.004 for 1/250s
+    pub iso: f64,
+    pub vignetting: bool    // Apply natural cos^4 falloff towards the image edges
+}
+
+impl Exposure {
+    /// Exposure value at ISO 100 for these settings
+    pub fn ev100(&self) -> f64 {
+        (self.f_number * self.f_number / self.shutter_speed * 100.0 / self.iso).log2()
+    }
+
+    pub fn scale(&self) -> f64 {
+        (-self.ev100()).exp2()
+    }
+}
+
+#[test]
+fn it_scales_by_stops() {
+    let reference = Exposure { f_number: 1.0, shutter_speed: 1.0, iso: 100.0, vignetting: false };
+    assert_eq!(reference.ev100(), 0.0);
+    assert_eq!(reference.scale(), 1.0);
+
+    // Sunny 16: f/16, 1/100s, ISO 100 is roughly EV 15
+    let sunny = Exposure { f_number: 16.0, shutter_speed: 0.01, iso: 100.0, vignetting: false };
+    assert!((sunny.ev100() - 14.64).abs() < 0.01);
+
+    // One stop each of aperture and ISO cancel out
+    let compensated = Exposure { f_number: 1.4142135623730951, shutter_speed: 1.0, iso: 200.0, vignetting: false };
+    assert!((compensated.scale() - 1.0).abs() < 1e-12);
+
+    let faster = Exposure { shutter_speed: 0.5, ..reference };
+    assert_eq!(faster.scale(), 0.5);
+}
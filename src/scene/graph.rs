@@ -0,0 +1,143 @@
+use std::sync::Arc;
+use geometry::prim::Prim;
+use geometry::prims::{Instance, TriangleOptions};
+use material::Material;
+use material::materials::{FlatMaterial, SharedMaterial};
+use mat4::Transform;
+use raytracer::Accelerator;
+use vec3::Vec3;
+
+#[allow(dead_code)]
+pub enum Geometry {
+    /// Prims that already have their own materials
+    Prims(Vec<Box<Prim+Send+Sync>>),
+    /// Triangles that take the material their node inherits, eg. from `from_obj_groups`
+    Triangles(Vec<TriangleOptions>),
+    /// Any prim built from the material its node inherits
+    Shape(Box<Fn(Box<Material+Send+Sync>) -> Box<Prim+Send+Sync>>),
+    /// A shared object that is placed with an Instance rather than copied
+    Instance(Arc<Accelerator+Send+Sync>)
+}
+
+/// A named group of geometry in a scene hierarchy. Each node's transform is
+/// relative to its parent, and nodes without a material use their parent's,
+/// so articulated models can be posed by changing a single node's transform.
+pub struct SceneNode {
+    pub name: String,
+    pub transform: Transform,
+    pub material: Option<SharedMaterial>,
+    pub geometry: Vec<Geometry>,
+    pub children: Vec<SceneNode>
+}
+
+impl SceneNode {
+    pub fn new(name: &str) -> SceneNode {
+        SceneNode {
+            name: name.to_owned(),
+            transform: Transform::identity(),
+            material: None,
+            geometry: Vec::new(),
+            children: Vec::new()
+        }
+    }
+
+    /// Returns the added child so it can be filled in
+    pub fn add_child(&mut self, child: SceneNode) -> &mut SceneNode {
+        self.children.push(child);
+        self.children.last_mut().unwrap()
+    }
+
+    /// Depth-first search for the first node called `name`, including this one
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut SceneNode> {
+        if self.name == name {
+            return Some(self);
+        }
+
+        for child in self.children.iter_mut() {
+            if let Some(node) = child.find_mut(name) {
+                return Some(node);
+            }
+        }
+
+        None
+    }
+
+    /// Moves every node's geometry into world space, ready for the top-level
+    /// acceleration structure. Geometry without any inherited material is flat white.
+    pub fn flatten(self) -> Vec<Box<Prim+Send+Sync>> {
+        let mut prims = Vec::new();
+        let default_material = SharedMaterial::new(FlatMaterial { color: Vec3::one() });
+        self.flatten_into(&Transform::identity(), &default_material, &mut prims);
+        prims
+    }
+
+    fn flatten_into(self, parent: &Transform, parent_material: &SharedMaterial, prims: &mut Vec<Box<Prim+Send+Sync>>) {
+        let transform = Transform::compose(parent, &self.transform);
+        let material = self.material.as_ref().unwrap_or(parent_material);
+
+        for geometry in self.geometry.into_iter() {
+            match geometry {
+                Geometry::Prims(node_prims) => {
+                    for mut prim in node_prims.into_iter() {
+                        prim.mut_transform(&transform);
+                        prims.push(prim);
+                    }
+                },
+                Geometry::Triangles(triangles) => {
+                    for mut triopts in triangles.into_iter() {
+                        triopts.material(Box::new(material.clone()));
+                        let mut triangle = triopts.build();
+                        triangle.mut_transform(&transform);
+                        prims.push(Box::new(triangle));
+                    }
+                },
+                Geometry::Shape(shape) => {
+                    let mut prim = shape(Box::new(material.clone()));
+                    prim.mut_transform(&transform);
+                    prims.push(prim);
+                },
+                Geometry::Instance(object) => {
+                    prims.push(Box::new(Instance::new(object, transform)));
+                }
+            }
+        }
+
+        for child in self.children.into_iter() {
+            child.flatten_into(&transform, material, prims);
+        }
+    }
+}
+
+#[cfg(test)]
+use geometry::prims::Sphere;
+#[cfg(test)]
+use mat4::Mat4;
+#[cfg(test)]
+use raytracer::Ray;
+
+#[test]
+fn it_composes_transforms_and_inherits_materials() {
+    let mut root = SceneNode::new("root");
+    root.transform = Transform::new(Mat4::translate_matrix(&Vec3 { x: 10.0, y: 0.0, z: 0.0 }));
+    root.material = Some(SharedMaterial::new(FlatMaterial { color: Vec3 { x: 1.0, y: 0.0, z: 0.0 } }));
+
+    {
+        let arm = root.add_child(SceneNode::new("arm"));
+        arm.transform = Transform::new(Mat4::translate_matrix(&Vec3 { x: 0.0, y: 5.0, z: 0.0 }));
+        arm.geometry.push(Geometry::Shape(Box::new(|material| {
+            Box::new(Sphere::new(Vec3::zero(), 1.0, material))
+        })));
+    }
+
+    // Posing the arm moves it relative to the root
+    root.find_mut("arm").unwrap().transform = Transform::new(Mat4::translate_matrix(&Vec3 { x: 0.0, y: 0.0, z: 5.0 }));
+    assert!(root.find_mut("leg").is_none());
+
+    let prims = root.flatten();
+    assert_eq!(prims.len(), 1);
+
+    let ray = Ray::new(Vec3 { x: 10.0, y: 10.0, z: 5.0 }, Vec3 { x: 0.0, y: -1.0, z: 0.0 });
+    let hit = prims[0].intersects(&ray, 0.0, 100.0).unwrap();
+    assert!((hit.t - 9.0).abs() < 1e-9);
+    assert_eq!(hit.material.sample(hit.n, ray.direction, hit.n, 0.0, 0.0), Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+}
@@ -1,5 +1,11 @@
-pub use self::camera::Camera;
+pub use self::camera::{Camera, FovAxis};
+pub use self::exposure::Exposure;
+pub use self::graph::{Geometry, SceneNode};
+pub use self::projection::Projection;
 pub use self::scene::Scene;
 
 pub mod camera;
+pub mod exposure;
+pub mod graph;
+pub mod projection;
 pub mod scene;
@@ -0,0 +1,16 @@
+/// How the camera maps a pixel coordinate to a primary ray.
+///
+/// * `Perspective`: pinhole camera, `fov` is the horizontal half-angle
+/// * `Orthographic`: parallel rays. The view is sized so that the plane through
+///   `look_at` is framed the same way the perspective camera would frame it
+/// * `Fisheye`: equidistant fisheye, the horizontal edge of the image is `fov`
+///   degrees off-axis. 90 gives a 180 degree image circle across the width
+/// * `Equirectangular`: full 360x180 degree panorama, `fov` is ignored.
+///   Use a 2:1 image for undistorted output
+#[derive(Clone, Copy, PartialEq, Debug, RustcDecodable, RustcEncodable)]
+pub enum Projection {
+    Perspective,
+    Orthographic,
+    Fisheye,
+    Equirectangular
+}
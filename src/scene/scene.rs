@@ -1,12 +1,11 @@
 use light::Light;
 use material::textures::CubeMap;
-use geometry::Prim;
-use raytracer::Octree;
+use raytracer::Accelerator;
 use vec3::Vec3;
 
 pub struct Scene {
     pub lights: Vec<Box<Light+Send+Sync>>,
-    pub octree: Octree<Box<Prim+Send+Sync>>,
+    pub accelerator: Box<Accelerator+Send+Sync>,
     pub background: Vec3,
     pub skybox: Option<CubeMap>
 }
@@ -1,15 +1,426 @@
-use geometry::prims::TriangleOptions;
+use geometry::prims::{Heightfield, IndexedMesh, IndexedMeshBuilder, ParticleSet, TriangleOptions};
+use geometry::subdivision::{PolyFace, PolyMesh, SubdivisionScheme};
 use geometry::{Mesh, Prim};
-use material::materials::CookTorranceMaterial;
+use material::{Material, Texture};
+use material::bump::Bump;
+use material::materials::{CookTorranceMaterial, PhongMaterial};
+use material::textures::ImageTexture;
+use raytracer::animator::TrackSet;
 use raytracer::compositor::{Surface, ColorRGBA};
+use std::collections::HashMap;
 use std::fs::File;
 use std::path::Path;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read};
 use vec3::Vec3;
 
+#[cfg(test)]
+use geometry::PartialBoundingBox;
+#[cfg(test)]
+use geometry::prims::ParticleShape;
+#[cfg(test)]
+use material::materials::FlatMaterial;
+#[cfg(test)]
+use raytracer::Ray;
+
 /// This is limited to only CookTorranceMaterials, as I couldn't get a Box<Material> to clone
 /// a new material for each triangle primitive in the object model.
+/// Use `from_obj_mesh` for models with MTL materials.
 pub fn from_obj(material: CookTorranceMaterial, flip_normals: bool, filename: &str) -> Result<Mesh, String> {
+    let (_, faces) = try!(read_obj_triangles(flip_normals, filename));
+
+    let triangles = faces.into_iter().map(|(_, mut triopts)| {
+        triopts.material(Box::new(material.clone()));
+        Box::new(triopts.build()) as Box<Prim+Send+Sync>
+    }).collect();
+
+    Ok(Mesh { triangles: triangles })
+}
+
+/// Loads the triangles of each `o`/`g` group in the model separately, without
+/// materials, so parts of a model can be placed and shaded independently.
+/// Faces before the first group are put in a group named `default`.
+pub fn from_obj_groups(flip_normals: bool, filename: &str) -> Result<Vec<(String, Vec<TriangleOptions>)>, String> {
+    let (names, faces) = try!(read_obj_triangles(flip_normals, filename));
+
+    let mut groups: Vec<(String, Vec<TriangleOptions>)> = names.into_iter().map(|name| (name, Vec::new())).collect();
+    for (group, triopts) in faces.into_iter() {
+        groups[group].1.push(triopts);
+    }
+
+    Ok(groups)
+}
+
+/// The group names of a model and its triangles in file order, each with the
+/// index of the group it is in. Polygons are split into fans of triangles.
+fn read_obj_triangles(flip_normals: bool, filename: &str) -> Result<(Vec<String>, Vec<(usize, TriangleOptions)>), String> {
+    let mut groups: Vec<String> = Vec::new();
+    let mut faces: Vec<(usize, TriangleOptions)> = Vec::new();
+    let mut current_group: Option<usize> = None;
+
+    try!(read_obj(flip_normals, filename, |data, statement| {
+        match statement {
+            ObjStatement::Group(name) => {
+                current_group = match groups.iter().position(|existing| existing == name) {
+                    Some(index) => Some(index),
+                    None => {
+                        groups.push(name.to_owned());
+                        Some(groups.len() - 1)
+                    }
+                };
+            },
+            ObjStatement::Face(corners) => {
+                let index = match current_group {
+                    Some(index) => index,
+                    None => {
+                        groups.push("default".to_owned());
+                        groups.len() - 1
+                    }
+                };
+                current_group = Some(index);
+
+                for i in 1..corners.len() - 1 {
+                    let mut triangle = [corners[0], corners[i], corners[i + 1]];
+                    // Flat triangles face the way they are wound
+                    if flip_normals && triangle.iter().any(|corner| corner.normal.is_none()) {
+                        triangle.swap(1, 2);
+                    }
+                    let mut triopts = TriangleOptions::new(
+                        data.positions[triangle[0].position],
+                        data.positions[triangle[1].position],
+                        data.positions[triangle[2].position]);
+
+                    // Without normals the triangle is flat shaded, and
+                    // without texture coordinates they default to zero
+                    if let (Some(n0), Some(n1), Some(n2)) = (triangle[0].normal, triangle[1].normal, triangle[2].normal) {
+                        triopts.normals([data.normals[n0], data.normals[n1], data.normals[n2]]);
+                    }
+                    let tex_coord = |corner: &ObjCorner| corner.tex_coord.map_or((0.0, 0.0), |t| data.tex_coords[t]);
+                    triopts.texinfo([tex_coord(&triangle[0]), tex_coord(&triangle[1]), tex_coord(&triangle[2])]);
+
+                    faces.push((index, triopts));
+                }
+            },
+            _ => {}
+        }
+        Ok(())
+    }));
+
+    Ok((groups, faces))
+}
+
+/// Loads a model as one `IndexedMesh`, with vertices stored once however
+/// many faces use them. Faces take their materials from the model's MTL files
+/// (`mtllib` and `usemtl`), or `material` if they have none. Polygons are
+/// split into fans of triangles. Faces without normals are flat shaded.
+///
+/// With `cache` set, the mesh's hierarchy is saved next to the model and
+/// loaded from there on later runs, like `AcceleratorOptions::build_for_model`.
+pub fn from_obj_mesh(material: Box<Material+Send+Sync>, flip_normals: bool, cache: bool, filename: &str) -> Result<IndexedMesh, String> {
+    // Each distinct position/UV/normal combination becomes one mesh vertex
+    let mut builder = IndexedMeshBuilder::new();
+    let default_material = try!(builder.add_material(material));
+    let mut face_material = default_material;
+
+    // Library materials are added to the mesh the first time a face uses them
+    let mut library: HashMap<String, (PhongMaterial, Option<Bump>)> = HashMap::new();
+    let mut used_materials: HashMap<String, u16> = HashMap::new();
+    let mut vertex_index: HashMap<ObjCorner, u32> = HashMap::new();
+
+    try!(read_obj(flip_normals, filename, |data, statement| {
+        match statement {
+            ObjStatement::MaterialLibrary(name) => {
+                let path = Path::new(filename).with_file_name(name);
+                match from_mtl(&path.to_string_lossy()) {
+                    Ok(materials) => library.extend(materials),
+                    Err(err) => eprintln!("Could not load material library {}: {}", path.display(), err)
+                }
+            },
+            ObjStatement::UseMaterial(name) => {
+                face_material = match used_materials.get(name) {
+                    Some(&index) => index,
+                    None => match library.remove(name) {
+                        Some((material, bump)) => {
+                            let index = try!(builder.add_material(Box::new(material)));
+                            if let Some(bump) = bump {
+                                builder.bump(index, bump);
+                            }
+                            used_materials.insert(name.to_owned(), index);
+                            index
+                        },
+                        None => {
+                            eprintln!("Material {} not found, using the default", name);
+                            used_materials.insert(name.to_owned(), default_material);
+                            default_material
+                        }
+                    }
+                };
+            },
+            ObjStatement::Face(corners) => {
+                let indices: Vec<u32> = corners.iter().map(|corner| {
+                    *vertex_index.entry(*corner).or_insert_with(|| {
+                        builder.add_vertex(data.positions[corner.position],
+                                           corner.normal.map(|n| data.normals[n]),
+                                           corner.tex_coord.map(|t| data.tex_coords[t]))
+                    })
+                }).collect();
+
+                // Faces without normals of their own take theirs from the
+                // winding, so that is what gets turned around
+                let flip_winding = flip_normals && corners.iter().any(|corner| corner.normal.is_none());
+                for i in 1..indices.len() - 1 {
+                    if flip_winding {
+                        builder.add_face([indices[0], indices[i + 1], indices[i]], face_material);
+                    } else {
+                        builder.add_face([indices[0], indices[i], indices[i + 1]], face_material);
+                    }
+                }
+            },
+            _ => {}
+        }
+        Ok(())
+    }));
+
+    if cache {
+        Ok(builder.build_cached(&format!("{}.mesh.bvh", filename)))
+    } else {
+        Ok(builder.build())
+    }
+}
+
+/// Loads the materials of an MTL file by name, as Phong materials with the
+/// bump map of each if it has one:
+///
+/// * `Kd`, `Ks` and `Ns` are the diffuse and specular colours and the
+///   specular exponent. `illum` 0 and 1 turn highlights off, 3 and up add
+///   mirror reflections as strong as `Ks`.
+/// * `d` (or `Tr`, its inverse) below 1 lets light through, bent by `Ni`
+///   (1.5, as for glass, if it is left out)
+/// * `map_Kd` is a diffuse texture. `map_Bump` (or `bump`) is a height map
+///   raising the surface `-bm` units where white, `norm` a tangent-space
+///   normal map.
+///
+/// Texture paths are relative to the MTL file; textures that fail to load
+/// are left out with a warning. Other statements are ignored.
+pub fn from_mtl(filename: &str) -> Result<HashMap<String, (PhongMaterial, Option<Bump>)>, String> {
+    let file_handle = match File::open(&filename) {
+        Ok(f) => f,
+        Err(err) => return Err(format!("{}", err))
+    };
+
+    let parse_f64 = |token: Option<&&str>| -> Result<f64, String> {
+        match token.and_then(|token| token.parse().ok()) {
+            Some(value) => Ok(value),
+            None => Err(format!("Expected a number in material, found {:?}", token))
+        }
+    };
+    // A single value is grey
+    let parse_color = |tokens: &[&str]| -> Result<Vec3, String> {
+        let r = try!(parse_f64(tokens.get(1)));
+        match (tokens.get(2), tokens.get(3)) {
+            (Some(_), Some(_)) => Ok(Vec3 { x: r, y: try!(parse_f64(tokens.get(2))), z: try!(parse_f64(tokens.get(3))) }),
+            _ => Ok(Vec3 { x: r, y: r, z: r })
+        }
+    };
+    // Options such as `-bm 0.5` come before the image, which is last
+    let load_texture = |tokens: &[&str]| -> Result<ImageTexture, String> {
+        let name = match tokens.last() {
+            Some(name) if tokens.len() > 1 => name,
+            _ => return Err(format!("Texture statement `{}` has no image", tokens.join(" ")))
+        };
+        let path = Path::new(filename).with_file_name(name);
+        match from_image(&path) {
+            Ok(image) => Ok(ImageTexture { image: flipped_vertically(&image) }),
+            Err(err) => Err(format!("{}: {}", path.display(), err))
+        }
+    };
+
+    let mut materials: Vec<(String, PhongMaterial, u32, Option<Bump>)> = Vec::new();
+
+    for line_res in BufReader::new(file_handle).lines() {
+        let line = match line_res {
+            Ok(line) => line,
+            Err(err) => return Err(format!("{}", err))
+        };
+        let tokens: Vec<&str> = line[..].split_whitespace().collect();
+
+        if tokens.first() == Some(&"newmtl") {
+            let material = PhongMaterial {
+                k_a: 0.0,
+                k_d: 1.0,
+                k_s: 1.0,
+                k_sg: 0.0,
+                k_tg: 0.0,
+                ambient: Vec3::zero(),
+                diffuse: Vec3 { x: 0.8, y: 0.8, z: 0.8 },
+                transmission: Vec3::zero(),
+                specular: Vec3::zero(),
+                shininess: 10.0,
+                glossiness: 0.0,
+                ior: 1.5,
+                diffuse_texture: None
+            };
+            materials.push((tokens[1..].join(" "), material, 2, None));
+            continue;
+        }
+
+        let (material, illum, bump) = match materials.last_mut() {
+            Some(&mut (_, ref mut material, ref mut illum, ref mut bump)) => (material, illum, bump),
+            None => continue
+        };
+
+        match tokens.first() {
+            Some(&"Kd") => material.diffuse = try!(parse_color(&tokens)),
+            Some(&"Ks") => material.specular = try!(parse_color(&tokens)),
+            Some(&"Ns") => material.shininess = try!(parse_f64(tokens.get(1))),
+            Some(&"Ni") => material.ior = try!(parse_f64(tokens.get(1))),
+            Some(&"d") => material.k_tg = 1.0 - try!(parse_f64(tokens.get(1))),
+            Some(&"Tr") => material.k_tg = try!(parse_f64(tokens.get(1))),
+            Some(&"illum") => *illum = try!(parse_f64(tokens.get(1))) as u32,
+            // A missing map leaves the rest of the material as it is
+            Some(&"map_Kd") => match load_texture(&tokens) {
+                Ok(texture) => material.diffuse_texture = Some(Box::new(texture)),
+                Err(err) => eprintln!("Skipping `{}` in {}: {}", line.trim(), filename, err)
+            },
+            Some(&"map_Bump") | Some(&"map_bump") | Some(&"bump") => {
+                let scale = match tokens.iter().position(|&token| token == "-bm") {
+                    Some(option) => try!(parse_f64(tokens.get(option + 1))),
+                    None => 1.0
+                };
+                match load_texture(&tokens) {
+                    Ok(texture) => *bump = Some(Bump::Height { texture: Box::new(texture), scale: scale }),
+                    Err(err) => eprintln!("Skipping `{}` in {}: {}", line.trim(), filename, err)
+                }
+            },
+            Some(&"norm") => match load_texture(&tokens) {
+                Ok(texture) => *bump = Some(Bump::NormalMap { texture: Box::new(texture) }),
+                Err(err) => eprintln!("Skipping `{}` in {}: {}", line.trim(), filename, err)
+            },
+            _ => {}
+        }
+    }
+
+    Ok(materials.into_iter().map(|(name, mut material, illum, bump)| {
+        if illum < 2 {
+            material.k_s = 0.0;
+        }
+        if illum >= 3 {
+            material.k_sg = (material.specular.x + material.specular.y + material.specular.z) / 3.0;
+        }
+        // Shadows are as see-through as the material
+        material.transmission = Vec3::one().scale(material.k_tg);
+        (name, (material, bump))
+    }).collect())
+}
+
+/// OBJ texture coordinates have v going up the image, rows of a `Surface` go down
+fn flipped_vertically(image: &Surface) -> Surface {
+    let mut flipped = Surface::new(image.width, image.height, ColorRGBA::black());
+    for y in 0..image.height {
+        for x in 0..image.width {
+            flipped[(x, image.height - 1 - y)] = image[(x, y)];
+        }
+    }
+    flipped
+}
+
+/// Like `from_obj`, with the model subdivided `levels` times so low-poly
+/// meshes come out smooth. Normals are recomputed, sharp between faces in
+/// different smoothing groups; `flip_normals` turns them around.
+pub fn from_obj_subdivided(material: CookTorranceMaterial, flip_normals: bool, filename: &str,
+                           scheme: SubdivisionScheme, levels: u32) -> Result<Mesh, String> {
+    let polygons = try!(from_obj_polygons(filename));
+
+    let triangles = polygons.subdivide(scheme, levels).triangles(flip_normals).into_iter().map(|mut triopts| {
+        triopts.material(Box::new(material.clone()));
+        Box::new(triopts.build()) as Box<Prim+Send+Sync>
+    }).collect();
+
+    Ok(Mesh { triangles: triangles })
+}
+
+/// Like `from_obj`, with each triangle split into 4^`levels` pieces and
+/// raised along its normals by `scale` times the `height` texture, looked up
+/// with the model's UVs
+#[allow(dead_code)]
+pub fn from_obj_displaced(material: CookTorranceMaterial, flip_normals: bool, filename: &str,
+                          height: &Texture, scale: f64, levels: u32) -> Result<Mesh, String> {
+    let (_, faces) = try!(read_obj_triangles(flip_normals, filename));
+
+    let mut triangles: Vec<Box<Prim+Send+Sync>> = Vec::new();
+    for (_, mut triopts) in faces.into_iter() {
+        triopts.material(Box::new(material.clone()));
+        for piece in triopts.displaced(height, scale, levels) {
+            triangles.push(Box::new(piece.build()));
+        }
+    }
+
+    Ok(Mesh { triangles: triangles })
+}
+
+/// Loads the faces of a model with their vertices shared, keeping polygons
+/// whole and noting the smoothing group (`s`) of each. Degenerate faces,
+/// which use a vertex more than once, are an error.
+pub fn from_obj_polygons(filename: &str) -> Result<PolyMesh, String> {
+    let mut faces: Vec<PolyFace> = Vec::new();
+    let mut smoothing_group = 0;
+
+    let data = try!(read_obj(false, filename, |data, statement| {
+        match statement {
+            ObjStatement::SmoothingGroup(group) => smoothing_group = group,
+            ObjStatement::Face(corners) => {
+                let vertices: Vec<usize> = corners.iter().map(|corner| corner.position).collect();
+                if vertices.iter().enumerate().any(|(i, v)| vertices[..i].contains(v)) {
+                    return Err(format!("Face {:?} uses a vertex more than once", vertices.iter().map(|v| v + 1).collect::<Vec<_>>()));
+                }
+
+                faces.push(PolyFace {
+                    vertices: vertices,
+                    tex_coords: corners.iter().map(|corner| corner.tex_coord.map_or((0.0, 0.0), |t| data.tex_coords[t])).collect(),
+                    smoothing_group: smoothing_group
+                });
+            },
+            _ => {}
+        }
+        Ok(())
+    }));
+
+    Ok(PolyMesh { positions: data.positions, faces: faces })
+}
+
+/// One corner of an OBJ face: indices into the vertex data, which the
+/// texture coordinate and normal may be left out of
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct ObjCorner {
+    position: usize,
+    tex_coord: Option<usize>,
+    normal: Option<usize>
+}
+
+/// The vertex data of an OBJ file read so far
+struct ObjData {
+    positions: Vec<Vec3>,
+    tex_coords: Vec<(f64, f64)>,
+    normals: Vec<Vec3>
+}
+
+/// OBJ statements other than vertex data, in the order they appear
+enum ObjStatement<'a> {
+    /// At least three corners, with indices checked against `ObjData`
+    Face(&'a [ObjCorner]),
+    /// From `o` or `g`
+    Group(&'a str),
+    /// `s off` is the same as `s 0`
+    SmoothingGroup(u32),
+    MaterialLibrary(&'a str),
+    UseMaterial(&'a str)
+}
+
+/// Reads an OBJ file, passing `visit` each statement with the vertex data
+/// read up to it. Face indices are resolved, so OBJ's counting from 1 and
+/// negative indices counting back are handled here; bad indices and numbers
+/// are errors. Returns all the vertex data.
+fn read_obj<F>(flip_normals: bool, filename: &str, mut visit: F) -> Result<ObjData, String>
+        where F: FnMut(&ObjData, ObjStatement) -> Result<(), String> {
     let file_handle = match File::open(&filename) {
         Ok(f) => f,
         Err(err) => return Err(format!("{}", err))
@@ -20,103 +431,82 @@ pub fn from_obj(material: CookTorranceMaterial, flip_normals: bool, filename: &s
         Err(err) => return Err(format!("{}", err))
     };
 
-    let file = BufReader::new(file_handle);
-
     let start_time = ::time::get_time();
     let print_every = 2048;
     let mut processed_bytes = 0;
 
-    let mut vertices: Vec<Vec3> = Vec::new();
-    let mut normals : Vec<Vec3> = Vec::new();
-    let mut triangles: Vec<Box<Prim+Send+Sync>> = Vec::new();
-    let mut tex_coords: Vec<Vec<f64>> = Vec::new();
-    let mut current_line = 0;
+    let mut data = ObjData { positions: Vec::new(), tex_coords: Vec::new(), normals: Vec::new() };
+    let mut corners: Vec<ObjCorner> = Vec::new();
     let normal_scale = if flip_normals { -1.0 } else { 1.0 };
 
-    for line_res in file.lines() {
-        let line = line_res.unwrap();
+    for (number, line_res) in BufReader::new(file_handle).lines().enumerate() {
+        let line = match line_res {
+            Ok(line) => line,
+            Err(err) => return Err(format!("{}", err))
+        };
         let tokens: Vec<&str> = line[..].split_whitespace().collect();
-        if tokens.is_empty() {
-            continue;
-        }
 
-        match tokens[0] {
-            "v" => {
-                vertices.push(Vec3 {
-                    x: tokens[1].parse().unwrap(),
-                    y: tokens[2].parse().unwrap(),
-                    z: tokens[3].parse().unwrap()
-                });
+        // The first `count` numbers after the statement, or `default` for those left out
+        let numbers = |count: usize, default: Option<f64>| -> Result<Vec<f64>, String> {
+            (1..count + 1).map(|i| match (tokens.get(i), default) {
+                (Some(token), _) => token.parse().map_err(|_| format!("Line {}: bad number `{}`", number + 1, token)),
+                (None, Some(default)) => Ok(default),
+                (None, None) => Err(format!("Line {}: expected {} numbers", number + 1, count))
+            }).collect()
+        };
+
+        match tokens.first() {
+            Some(&"v") => {
+                let v = try!(numbers(3, None));
+                data.positions.push(Vec3 { x: v[0], y: v[1], z: v[2] });
             },
-            "vt" => {
-                tex_coords.push(vec![
-                    tokens[1].parse().unwrap(),
-                    tokens[2].parse().unwrap()
-                ]);
-            },
-            "vn" => {
-                normals.push(Vec3 {
-                    x: tokens[1].parse::<f64>().unwrap() * normal_scale,
-                    y: tokens[2].parse::<f64>().unwrap() * normal_scale,
-                    z: tokens[3].parse::<f64>().unwrap() * normal_scale
-                });
+            Some(&"vt") => {
+                let vt = try!(numbers(2, Some(0.0)));
+                data.tex_coords.push((vt[0], vt[1]));
             },
-            "f" => {
-                // ["f", "1/2/3", "2/2/2", "12//4"] => [[1, 2, 3], [2, 2, 2], [12, -1u, 4]]
-                let tail = match tokens.split_first() {
-                    Some((_, tail)) => tail,
-                    None => return Err("Face syntax of OBJ not supported or malformed".to_owned())
-                };
-
-                let pairs: Vec<Vec<usize>> = tail.iter().map( |token| {
-                    let str_tokens: Vec<&str> = token.split('/').collect();
-                    str_tokens.iter().map( |str_tok| {
-                        match str_tok.parse::<usize>().ok() {
-                            Some(usize_tok) => usize_tok - 1, // Have to offset as OBJ is 1-indexed
-                            None => !0 // No data available/not supplied (eg. `//` as a token)
-                        }
-                    }).collect()
-                }).collect();
-
-                // If no texture coordinates were supplied, default to zero.
-                // We stored nothing supplied as !0
-                let (u, v) = if pairs[0][1] != !0 {
-                    (vec![
-                        tex_coords[pairs[0][1]][0],
-                        tex_coords[pairs[1][1]][0],
-                        tex_coords[pairs[2][1]][0]
-                    ],
-                    vec![
-                        tex_coords[pairs[0][1]][1],
-                        tex_coords[pairs[1][1]][1],
-                        tex_coords[pairs[2][1]][1]
-                    ])
-                } else {
-                    (vec![0.0, 0.0, 0.0],
-                     vec![0.0, 0.0, 0.0])
-                };
-
-                let mut triopts = TriangleOptions::new(
-                    vertices[pairs[0][0]],
-                    vertices[pairs[1][0]],
-                    vertices[pairs[2][0]]);
+            Some(&"vn") => {
+                let n = try!(numbers(3, None));
+                data.normals.push(Vec3 { x: n[0], y: n[1], z: n[2] }.scale(normal_scale));
+            },
+            Some(&"o") | Some(&"g") => {
+                try!(visit(&data, ObjStatement::Group(&tokens[1..].join(" "))));
+            },
+            Some(&"s") => {
+                let group = tokens.get(1).and_then(|group| group.parse().ok()).unwrap_or(0);
+                try!(visit(&data, ObjStatement::SmoothingGroup(group)));
+            },
+            Some(&"mtllib") => {
+                for name in &tokens[1..] {
+                    try!(visit(&data, ObjStatement::MaterialLibrary(name)));
+                }
+            },
+            Some(&"usemtl") => {
+                try!(visit(&data, ObjStatement::UseMaterial(&tokens[1..].join(" "))));
+            },
+            Some(&"f") => {
+                if tokens.len() < 4 {
+                    return Err(format!("Line {}: a face needs at least three vertices", number + 1));
+                }
 
-                triopts.material(Box::new(material.clone()));
-                triopts.normals([
-                    normals[pairs[0][2]],
-                    normals[pairs[1][2]],
-                    normals[pairs[2][2]],
-                ]);
-                triopts.texinfo([(u[0], v[0]), (u[1], v[1]), (u[2], v[2])]);
+                corners.clear();
+                for token in &tokens[1..] {
+                    let mut indices = token.split('/');
+                    let position = match try!(obj_index(token, indices.next(), data.positions.len())) {
+                        Some(position) => position,
+                        None => return Err(format!("Malformed face vertex `{}`", token))
+                    };
+                    let tex_coord = try!(obj_index(token, indices.next(), data.tex_coords.len()));
+                    let normal = try!(obj_index(token, indices.next(), data.normals.len()));
+                    corners.push(ObjCorner { position: position, tex_coord: tex_coord, normal: normal });
+                }
 
-                triangles.push(Box::new(triopts.build()));
+                try!(visit(&data, ObjStatement::Face(&corners)));
             },
             _ => {}
         }
 
-        current_line += 1;
         processed_bytes += line.as_bytes().len();
-        if current_line % print_every == 0 {
+        if (number + 1) % print_every == 0 {
             ::util::print_progress("Bytes", start_time, processed_bytes, total_bytes as usize);
         }
     }
@@ -124,7 +514,249 @@ pub fn from_obj(material: CookTorranceMaterial, flip_normals: bool, filename: &s
     // Cheat the progress meter
     ::util::print_progress("Bytes", start_time, total_bytes as usize, total_bytes as usize);
 
-    Ok(Mesh { triangles: triangles })
+    Ok(data)
+}
+
+/// Resolves one index of the face vertex `token` against the `count` items
+/// read so far. OBJ counts from 1, or back from the latest item when
+/// negative; an index left out (as in `1//3`) is `None`.
+fn obj_index(token: &str, index: Option<&str>, count: usize) -> Result<Option<usize>, String> {
+    let index = match index {
+        Some(index) if !index.is_empty() => index,
+        _ => return Ok(None)
+    };
+
+    match index.parse::<isize>() {
+        Ok(i) if i > 0 && i as usize <= count => Ok(Some(i as usize - 1)),
+        Ok(i) if i < 0 && (-i) as usize <= count => Ok(Some(count - (-i) as usize)),
+        Ok(_) => Err(format!("Face vertex `{}` out of range", token)),
+        Err(_) => Err(format!("Malformed face vertex `{}`", token))
+    }
+}
+
+/// Type of a PLY property, by size in bytes and how to read it
+#[derive(Clone, Copy)]
+enum PlyType {
+    Int(usize),
+    Uint(usize),
+    Float,
+    Double
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Result<PlyType, String> {
+        Ok(match name {
+            "char" | "int8" => PlyType::Int(1),
+            "uchar" | "uint8" => PlyType::Uint(1),
+            "short" | "int16" => PlyType::Int(2),
+            "ushort" | "uint16" => PlyType::Uint(2),
+            "int" | "int32" => PlyType::Int(4),
+            "uint" | "uint32" => PlyType::Uint(4),
+            "float" | "float32" => PlyType::Float,
+            "double" | "float64" => PlyType::Double,
+            _ => return Err(format!("Unknown PLY property type `{}`", name))
+        })
+    }
+
+    fn size(&self) -> usize {
+        match *self {
+            PlyType::Int(size) | PlyType::Uint(size) => size,
+            PlyType::Float => 4,
+            PlyType::Double => 8
+        }
+    }
+
+    fn from_bits(&self, bits: u64) -> f64 {
+        match *self {
+            PlyType::Int(size) => {
+                // Sign extend from the top bit of the value
+                let shift = 64 - 8 * size;
+                ((bits << shift) as i64 >> shift) as f64
+            },
+            PlyType::Uint(_) => bits as f64,
+            PlyType::Float => f32::from_bits(bits as u32) as f64,
+            PlyType::Double => f64::from_bits(bits)
+        }
+    }
+
+    /// Integer colours run up to the type's maximum, float ones up to 1
+    fn color_scale(&self) -> f64 {
+        match *self {
+            PlyType::Uint(size) | PlyType::Int(size) => 1.0 / ((1u64 << (8 * size)) - 1) as f64,
+            PlyType::Float | PlyType::Double => 1.0
+        }
+    }
+}
+
+/// Reads the vertices of a PLY file, ASCII or binary, as particles. Uses the
+/// `x`, `y`, `z`, `radius` and `red`, `green`, `blue` properties; particles
+/// without a radius get `default_radius`, and without colours are white. The
+/// vertices must be the first element in the file.
+#[allow(dead_code)]
+pub fn from_ply_particles(filename: &str, default_radius: f64) -> Result<ParticleSet, String> {
+    let file_handle = match File::open(&filename) {
+        Ok(f) => f,
+        Err(err) => return Err(format!("{}", err))
+    };
+    let mut reader = BufReader::new(file_handle);
+
+    let mut format = String::new();
+    let mut vertex_count = 0;
+    let mut properties: Vec<(String, PlyType)> = Vec::new();
+    let mut in_vertex = false;
+
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) => return Err("PLY header has no end".to_owned()),
+            Ok(_) => {},
+            Err(err) => return Err(format!("{}", err))
+        }
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        match tokens.first() {
+            Some(&"format") if tokens.len() > 1 => format = tokens[1].to_owned(),
+            Some(&"element") if tokens.len() > 2 => {
+                if properties.is_empty() && tokens[1] != "vertex" && vertex_count == 0 {
+                    return Err("PLY vertices must come first".to_owned());
+                }
+                in_vertex = tokens[1] == "vertex";
+                if in_vertex {
+                    vertex_count = match tokens[2].parse::<usize>() {
+                        Ok(count) => count,
+                        Err(err) => return Err(format!("{}", err))
+                    };
+                }
+            },
+            Some(&"property") if in_vertex => {
+                if tokens.len() != 3 {
+                    return Err("PLY vertex lists are not supported".to_owned());
+                }
+                properties.push((tokens[2].to_owned(), try!(PlyType::parse(tokens[1]))));
+            },
+            Some(&"end_header") => break,
+            _ => {}
+        }
+    }
+
+    let find = |name: &str| properties.iter().position(|&(ref property, _)| property == name);
+    let (x, y, z) = match (find("x"), find("y"), find("z")) {
+        (Some(x), Some(y), Some(z)) => (x, y, z),
+        _ => return Err("PLY vertices have no position".to_owned())
+    };
+    let radius = find("radius");
+    let color = match (find("red"), find("green"), find("blue")) {
+        (Some(r), Some(g), Some(b)) => Some((r, g, b, properties[r].1.color_scale())),
+        _ => None
+    };
+
+    let mut particles = ParticleSet::new();
+    let mut values = vec![0.0; properties.len()];
+    let mut lines = String::new();
+    let mut ascii_tokens = Vec::new();
+    if format == "ascii" {
+        if let Err(err) = reader.read_to_string(&mut lines) {
+            return Err(format!("{}", err));
+        }
+        ascii_tokens = lines.split_whitespace().collect();
+    } else if format != "binary_little_endian" && format != "binary_big_endian" {
+        return Err(format!("Unknown PLY format `{}`", format));
+    }
+    let big_endian = format == "binary_big_endian";
+
+    for vertex in 0..vertex_count {
+        for (i, &(_, kind)) in properties.iter().enumerate() {
+            values[i] = if format == "ascii" {
+                match ascii_tokens.get(vertex * properties.len() + i).map(|token| token.parse::<f64>()) {
+                    Some(Ok(value)) => value,
+                    _ => return Err(format!("Malformed PLY vertex {}", vertex))
+                }
+            } else {
+                let mut bytes = [0u8; 8];
+                if let Err(err) = reader.read_exact(&mut bytes[..kind.size()]) {
+                    return Err(format!("{}", err));
+                }
+                let bytes = &bytes[..kind.size()];
+                let bits = if big_endian {
+                    bytes.iter().fold(0, |acc, &b| acc << 8 | b as u64)
+                } else {
+                    bytes.iter().rev().fold(0, |acc, &b| acc << 8 | b as u64)
+                };
+                kind.from_bits(bits)
+            };
+        }
+
+        let position = Vec3 { x: values[x], y: values[y], z: values[z] };
+        let rgb = match color {
+            Some((r, g, b, scale)) => Vec3 { x: values[r] * scale, y: values[g] * scale, z: values[b] * scale },
+            None => Vec3::one()
+        };
+        particles.add(position, radius.map_or(default_radius, |r| values[r]), rgb);
+    }
+
+    Ok(particles)
+}
+
+/// Reads particles from rows of `x,y,z[,radius[,red,green,blue]]`, with
+/// colours from 0 to 1. A first line that is not numbers is taken as a
+/// header naming the columns, in which case they may come in any order.
+#[allow(dead_code)]
+pub fn from_csv_particles(filename: &str, default_radius: f64) -> Result<ParticleSet, String> {
+    let file_handle = match File::open(&filename) {
+        Ok(f) => f,
+        Err(err) => return Err(format!("{}", err))
+    };
+
+    let mut columns: Vec<String> = ["x", "y", "z", "radius", "red", "green", "blue"].iter().map(|&c| c.to_owned()).collect();
+    let mut particles = ParticleSet::new();
+
+    for (number, line_res) in BufReader::new(file_handle).lines().enumerate() {
+        let line = match line_res {
+            Ok(line) => line,
+            Err(err) => return Err(format!("{}", err))
+        };
+        let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let values: Vec<Option<f64>> = fields.iter().map(|field| field.parse().ok()).collect();
+        if values.iter().any(|value| value.is_none()) {
+            if number == 0 {
+                columns = fields.iter().map(|field| field.to_lowercase()).collect();
+                continue;
+            }
+            return Err(format!("Malformed CSV particle on line {}", number + 1));
+        }
+
+        let value = |name: &str| columns.iter().position(|column| column == name).and_then(|i| values.get(i).and_then(|&v| v));
+        let position = match (value("x"), value("y"), value("z")) {
+            (Some(x), Some(y), Some(z)) => Vec3 { x: x, y: y, z: z },
+            _ => return Err(format!("CSV particle on line {} has no position", number + 1))
+        };
+        let color = match (value("red"), value("green"), value("blue")) {
+            (Some(r), Some(g), Some(b)) => Vec3 { x: r, y: g, z: b },
+            _ => Vec3::one()
+        };
+        particles.add(position, value("radius").unwrap_or(default_radius), color);
+    }
+
+    Ok(particles)
+}
+
+/// Keyframe tracks for animated scenes, see `TrackSet`
+pub fn from_tracks(filename: &str) -> Result<TrackSet, String> {
+    let mut file_handle = match File::open(&filename) {
+        Ok(f) => f,
+        Err(err) => return Err(format!("{}", err))
+    };
+
+    let mut json_data = String::new();
+    if let Err(err) = file_handle.read_to_string(&mut json_data) {
+        return Err(format!("{}", err));
+    }
+
+    TrackSet::from_json(&json_data)
 }
 
 pub fn from_image<P: AsRef<Path>>(path: P) -> Result<Surface, String> {
@@ -144,6 +776,17 @@ pub fn from_image<P: AsRef<Path>>(path: P) -> Result<Surface, String> {
     Ok(surface)
 }
 
+/// A heightfield from a grayscale image, `size.y` high where the image is white
+#[allow(dead_code)]
+pub fn from_heightmap<P: AsRef<Path>>(path: P, size: Vec3, material: Box<Material+Send+Sync>) -> Result<Heightfield, String> {
+    let image = try!(from_image(path));
+    if image.width < 2 || image.height < 2 {
+        return Err("A heightmap needs at least 2x2 pixels".to_owned());
+    }
+
+    Ok(Heightfield::new(&image, size, material))
+}
+
 #[test]
 pub fn test_obj_loads_correct_number_of_triangles() {
     let material: CookTorranceMaterial = Default::default();
@@ -153,6 +796,213 @@ pub fn test_obj_loads_correct_number_of_triangles() {
     assert_eq!(mesh.triangles.len(), 12);
 }
 
+#[test]
+pub fn test_obj_loads_indexed_mesh() {
+    let mesh = from_obj_mesh(Box::new(FlatMaterial { color: Vec3::one() }), false, false, "test/res/cube.obj")
+            .ok().expect("failed to load test obj `test/res/cube.obj`");
+
+    // From the outside of each face, seeing its own normal
+    let ray = Ray::new(Vec3 { x: 0.5, y: 0.25, z: 5.0 }, Vec3 { x: 0.0, y: 0.0, z: -1.0 });
+    let hit = mesh.intersects(&ray, 0.0, 100.0).unwrap();
+    assert!((hit.t - 4.0).abs() < 1e-6);
+    assert!((hit.n - Vec3 { x: 0.0, y: 0.0, z: 1.0 }).len() < 1e-6);
+
+    let bounds = mesh.partial_bounding_box().unwrap();
+    assert!((bounds.min - Vec3 { x: -1.0, y: -1.0, z: -1.0 }).len() < 1e-6);
+}
+
+#[test]
+pub fn test_obj_mesh_caches_hierarchy() {
+    let model_path = ::std::env::temp_dir().join(format!("mesh-cache-{}.obj", ::std::process::id()));
+    ::std::fs::copy("test/res/cube.obj", &model_path).unwrap();
+    let model_path = model_path.to_str().unwrap();
+
+    let ray = Ray::new(Vec3 { x: 0.5, y: 0.25, z: 5.0 }, Vec3 { x: 0.0, y: 0.0, z: -1.0 });
+    for _ in 0..2 {
+        let mesh = from_obj_mesh(Box::new(FlatMaterial { color: Vec3::one() }), false, true, model_path)
+                .ok().expect("failed to load copied test obj");
+        assert!((mesh.intersects(&ray, 0.0, 100.0).unwrap().t - 4.0).abs() < 1e-6);
+    }
+
+    ::std::fs::remove_file(format!("{}.mesh.bvh", model_path)).unwrap();
+    ::std::fs::remove_file(model_path).unwrap();
+}
+
+#[test]
+pub fn test_mtl_loads_materials() {
+    let materials = from_mtl("test/res/materials.mtl")
+            .ok().expect("failed to load test mtl `test/res/materials.mtl`");
+    assert_eq!(materials.len(), 3);
+
+    let &(ref glass, ref bump) = materials.get("glass").unwrap();
+    assert!(glass.is_reflective() && glass.is_refractive());
+    assert_eq!(glass.ior, 1.5);
+    assert_eq!(glass.shininess, 200.0);
+    assert!((glass.transmission - Vec3 { x: 0.75, y: 0.75, z: 0.75 }).len() < 1e-9);
+    match *bump {
+        Some(Bump::Height { scale, .. }) => assert_eq!(scale, 0.5),
+        _ => panic!("expected a height map on glass")
+    }
+
+    let &(ref red, _) = materials.get("red").unwrap();
+    assert!(!red.is_reflective() && !red.is_refractive());
+    assert_eq!(red.k_s, 0.0);
+    assert_eq!(red.ior, 1.5);
+}
+
+#[test]
+pub fn test_mtl_skips_missing_textures() {
+    let materials = from_mtl("test/res/missing_map.mtl")
+            .ok().expect("failed to load test mtl `test/res/missing_map.mtl`");
+
+    let &(ref red, ref bump) = materials.get("red").unwrap();
+    assert!(red.diffuse_texture.is_none() && bump.is_none());
+    assert_eq!(red.diffuse, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+    assert_eq!(red.shininess, 40.0);
+}
+
+#[test]
+pub fn test_obj_uses_materials_per_face() {
+    let mesh = from_obj_mesh(Box::new(FlatMaterial { color: Vec3::one() }), false, false, "test/res/materials.obj")
+            .ok().expect("failed to load test obj `test/res/materials.obj`");
+
+    // Default, red and textured. Glass is never used, so it is left out.
+    assert_eq!(mesh.materials.len(), 3);
+
+    // Lit head on, so only the diffuse colour shows
+    let n = Vec3 { x: 0.0, y: 0.0, z: 1.0 };
+    let color_at = |x: f64, y: f64| {
+        let ray = Ray::new(Vec3 { x: x, y: y, z: 1.0 }, -n);
+        let hit = mesh.intersects(&ray, 0.0, 10.0).unwrap();
+        hit.material.sample(n, n, n, hit.u, hit.v)
+    };
+
+    assert!((color_at(0.5, 0.5) - Vec3 { x: 1.0, y: 0.0, z: 0.0 }).len() < 1e-9);
+    assert!((color_at(2.2, 0.1) - Vec3::one()).len() < 1e-9);
+
+    // The bottom row of the image is at v = 0: red, then green a third of the way across
+    assert!((color_at(1.05, 0.01) - Vec3 { x: 1.0, y: 0.0, z: 0.0 }).len() < 0.05);
+    let textured = color_at(1.35, 0.01);
+    assert!(textured.y > 0.9 && textured.x < 0.1 && textured.z < 0.1);
+}
+
+#[test]
+pub fn test_obj_loads_groups() {
+    let groups = from_obj_groups(false, "test/res/groups.obj")
+            .ok().expect("failed to load test obj `test/res/groups.obj`");
+
+    let names: Vec<&str> = groups.iter().map(|&(ref name, _)| &name[..]).collect();
+    assert_eq!(names, vec!["default", "top", "bottom"]);
+    assert_eq!(groups[0].1.len(), 1);
+    assert_eq!(groups[1].1.len(), 2);
+    assert_eq!(groups[2].1.len(), 1);
+}
+
+#[test]
+pub fn test_obj_keeps_file_order() {
+    let material: CookTorranceMaterial = Default::default();
+    let mesh = from_obj(material, false, "test/res/groups.obj")
+            .ok().expect("failed to load test obj `test/res/groups.obj`");
+
+    // Each face is a step further along z, whichever group it is in
+    let depths: Vec<f64> = mesh.triangles.iter().map(|triangle| triangle.partial_bounding_box().unwrap().min.z).collect();
+    assert_eq!(depths, vec![0.0, 1.0, 2.0, 3.0]);
+}
+
+#[test]
+pub fn test_obj_subdivides() {
+    let polygons = from_obj_polygons("test/res/cube.obj")
+            .ok().expect("failed to load test obj `test/res/cube.obj`");
+    assert_eq!(polygons.positions.len(), 8);
+    assert_eq!(polygons.faces.len(), 12);
+
+    let material: CookTorranceMaterial = Default::default();
+    let mesh = from_obj_subdivided(material, false, "test/res/cube.obj", SubdivisionScheme::Loop, 1)
+            .ok().expect("failed to subdivide test obj `test/res/cube.obj`");
+    assert_eq!(mesh.triangles.len(), 48);
+}
+
+#[test]
+pub fn test_obj_displaces() {
+    use material::textures::CheckerTexture;
+
+    // White everywhere, so every vertex moves a whole unit out along its normal
+    let white = CheckerTexture { color1: ColorRGBA::white(), color2: ColorRGBA::white(), scale: 1.0 };
+    let material: CookTorranceMaterial = Default::default();
+    let mesh = from_obj_displaced(material, false, "test/res/cube.obj", &white, 1.0, 2)
+            .ok().expect("failed to displace test obj `test/res/cube.obj`");
+    assert_eq!(mesh.triangles.len(), 12 * 16);
+
+    let bounds = mesh.triangles.iter().fold(mesh.triangles[0].partial_bounding_box().unwrap(), |bounds, triangle| {
+        ::geometry::bbox::union_bbox(&bounds, &triangle.partial_bounding_box().unwrap())
+    });
+    assert!(bounds.max.x > 1.5);
+}
+
+#[test]
+pub fn test_obj_polygons_resolve_indices() {
+    let polygons = from_obj_polygons("test/res/relative.obj")
+            .ok().expect("failed to load test obj `test/res/relative.obj`");
+    assert_eq!(polygons.faces[0].vertices, vec![0, 1, 2]);
+    assert_eq!(polygons.faces[1].vertices, vec![0, 2, 3]);
+    assert_eq!(polygons.faces[1].tex_coords[2], (0.0, 1.0));
+
+    assert!(from_obj_polygons("test/res/zero_index.obj").is_err());
+    assert!(from_obj_polygons("test/res/out_of_range.obj").is_err());
+    assert!(from_obj_polygons("test/res/degenerate.obj").is_err());
+}
+
+#[test]
+pub fn test_obj_flips_faces_without_normals() {
+    // The quad is wound to face +z
+    let ray = Ray::new(Vec3 { x: 0.75, y: 0.25, z: 5.0 }, Vec3 { x: 0.0, y: 0.0, z: -1.0 });
+
+    for &(flip_normals, expected_z) in [(false, 1.0), (true, -1.0)].iter() {
+        let mesh = from_obj_mesh(Box::new(FlatMaterial { color: Vec3::one() }), flip_normals, false, "test/res/relative.obj").unwrap();
+        assert_eq!(mesh.intersects(&ray, 0.0, 100.0).unwrap().n.z, expected_z);
+
+        let triangles = from_obj(Default::default(), flip_normals, "test/res/relative.obj").unwrap().triangles;
+        let hit = triangles.iter().filter_map(|triangle| triangle.intersects(&ray, 0.0, 100.0)).next().unwrap();
+        assert_eq!(hit.n.z, expected_z);
+    }
+}
+
+#[test]
+pub fn test_obj_loaders_share_index_rules() {
+    let groups = from_obj_groups(false, "test/res/relative.obj").unwrap();
+    assert_eq!(groups[0].1.len(), 2);
+    let mesh = from_obj_mesh(Box::new(FlatMaterial { color: Vec3::one() }), false, false, "test/res/relative.obj").unwrap();
+    assert!(mesh.partial_bounding_box().is_some());
+
+    for filename in ["test/res/zero_index.obj", "test/res/out_of_range.obj"].iter() {
+        assert!(from_obj_groups(false, filename).is_err());
+        assert!(from_obj_mesh(Box::new(FlatMaterial { color: Vec3::one() }), false, false, filename).is_err());
+    }
+}
+
+#[test]
+pub fn test_loads_particles() {
+    let ascii = from_ply_particles("test/res/particles.ply", 0.1)
+            .ok().expect("failed to load test ply `test/res/particles.ply`");
+    let binary = from_ply_particles("test/res/particles_binary.ply", 0.1)
+            .ok().expect("failed to load test ply `test/res/particles_binary.ply`");
+    let csv = from_csv_particles("test/res/particles.csv", 0.1)
+            .ok().expect("failed to load test csv `test/res/particles.csv`");
+
+    assert_eq!(ascii.len(), 3);
+    assert_eq!(binary.len(), 3);
+    assert_eq!(csv.len(), 3);
+
+    // All three hold a red particle of radius 0.5 at (1, 2, 3) in front of the others
+    for particles in vec![ascii, binary, csv] {
+        let cloud = particles.build(ParticleShape::Sphere, |color| Box::new(FlatMaterial { color: color }));
+        let ray = Ray::new(Vec3 { x: 1.0, y: 2.0, z: -10.0 }, Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+        let hit = cloud.intersects(&ray, 0.0, 100.0).unwrap();
+        assert!((hit.t - 12.5).abs() < 1e-6);
+        assert!((hit.material.sample(Vec3::zero(), Vec3::zero(), Vec3::zero(), 0.0, 0.0) - Vec3 { x: 1.0, y: 0.0, z: 0.0 }).len() < 1e-9);
+    }
+}
+
 #[test]
 pub fn test_from_png24() {
     let surface = from_image("test/res/png24.png")
@@ -178,3 +1028,16 @@ pub fn test_from_png24() {
         }
     }
 }
+
+#[test]
+pub fn test_loads_spin_tracks() {
+    use raytracer::animator::Pose;
+
+    let tracks = from_tracks("./docs/animations/spin.json").unwrap();
+    let pose = tracks.track::<Pose>("teapot_pose").unwrap();
+    assert_eq!(pose.at(10.0).rotation_deg, Vec3 { x: 0.0, y: 360.0, z: 0.0 });
+
+    let roughness = tracks.track::<f64>("sphere_roughness").unwrap();
+    assert_eq!(roughness.at(5.0), 0.4);
+    assert!(from_tracks("./docs/animations/missing.json").is_err());
+}
@@ -3,7 +3,7 @@ use std::cmp;
 use std::fmt;
 use std::ops::{Add, Div, Mul, Neg, Sub};
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Default, RustcDecodable)]
 pub struct Vec3 {
     pub x: f64,
     pub y: f64,
@@ -61,6 +61,15 @@ impl Vec3 {
         }
     }
 
+    /// Component along an axis. 0: x, 1: y, 2: z, as returned by `BBox::max_extent`
+    pub fn axis(&self, axis: usize) -> f64 {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z
+        }
+    }
+
     /// V, N should be unit vectors
     ///
     ///  ^  ^
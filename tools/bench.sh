@@ -1,10 +1,21 @@
 #!/bin/sh
+# Usage: tools/bench.sh ./rust-raytracer-<version> [accelerator...]
+# Renders each benchmark scene with every acceleration structure given
+# (all of them by default).
+
+BIN="$1"
+shift
+ACCELERATORS="${*:-Octree Bvh KdTree}"
+TMP_CONF="$(mktemp)"
+trap 'rm -f "$TMP_CONF"' EXIT
 
 for conf in cow bunny box teapot; do
     CONF_FILE="tools/conf/${conf}.json"
     test -e "$CONF_FILE" && {
-        echo "=== $1 $conf ==="
-        time "$1" "$CONF_FILE"
+        for accelerator in $ACCELERATORS; do
+            sed "s/^{/{\n    \"accelerator\": \"${accelerator}\",/" "$CONF_FILE" > "$TMP_CONF"
+            echo "=== $BIN ${conf}-${accelerator} ==="
+            time "$BIN" "$TMP_CONF"
+        done
     }
 done
-